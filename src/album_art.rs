@@ -0,0 +1,225 @@
+use crate::app::App;
+use crate::idle_mode::draw_video_frame;
+use crate::security::{bounded_output, external_command};
+use crate::tagging::fetch_cover_art;
+use crate::video_screensaver::VideoFrame;
+use ratatui::{
+    Frame,
+    layout::{Constraint, Layout, Rect},
+    widgets::{Block, Borders},
+};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+/// Covers are decoded once per track at this square size and scaled to the
+/// panel by the half-block renderer.
+const ART_PIXELS: u16 = 128;
+
+/// Folder images music managers leave beside an album's files.
+const SIDECAR_NAMES: [&str; 6] = [
+    "cover.jpg",
+    "cover.png",
+    "folder.jpg",
+    "folder.png",
+    "front.jpg",
+    "album.jpg",
+];
+
+/// What the background decoder needs to know about the current track.
+pub struct ArtRequest {
+    pub path: String,
+    pub video_id: Option<String>,
+    /// Folder images only make sense for imported albums; downloads share a
+    /// folder with every other download.
+    pub sidecar: bool,
+}
+
+/// Decodes the current track's cover on a background thread. Tried in
+/// order: art embedded in the file, a folder image, the YouTube thumbnail.
+#[derive(Default)]
+pub struct AlbumArtLoader {
+    track: Option<String>,
+    receiver: Option<Receiver<Option<VideoFrame>>>,
+}
+
+impl AlbumArtLoader {
+    pub fn track(&self) -> Option<&str> {
+        self.track.as_deref()
+    }
+
+    /// Start decoding art for a new track, abandoning any earlier load.
+    pub fn load(&mut self, request: Option<ArtRequest>) {
+        self.track = request.as_ref().map(|request| request.path.clone());
+        self.receiver = request.map(|request| {
+            let (sender, receiver) = mpsc::channel();
+            std::thread::spawn(move || {
+                let _ = sender.send(load_cover(&request));
+            });
+            receiver
+        });
+    }
+
+    /// The finished cover, once, or `Some(None)` when the track has none.
+    pub fn poll(&mut self) -> Option<Option<VideoFrame>> {
+        let frame = self.receiver.as_ref()?.try_recv().ok()?;
+        self.receiver = None;
+        Some(frame)
+    }
+}
+
+fn load_cover(request: &ArtRequest) -> Option<VideoFrame> {
+    let path = Path::new(&request.path);
+    decode_cover(path)
+        .or_else(|| {
+            request
+                .sidecar
+                .then(|| sidecar_cover(path))
+                .flatten()
+                .and_then(|cover| decode_cover(&cover))
+        })
+        .or_else(|| {
+            let video_id = request.video_id.as_deref()?;
+            let bytes = fetch_cover_art(video_id)?;
+            let thumbnail = std::env::temp_dir().join(format!(
+                "crest-player-cover-{}-{video_id}.jpg",
+                std::process::id()
+            ));
+            std::fs::write(&thumbnail, bytes).ok()?;
+            let frame = decode_cover(&thumbnail);
+            let _ = std::fs::remove_file(&thumbnail);
+            frame
+        })
+}
+
+fn sidecar_cover(path: &Path) -> Option<PathBuf> {
+    let directory = path.parent()?;
+    let names: Vec<_> = std::fs::read_dir(directory)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.file_name())
+        .collect();
+    SIDECAR_NAMES.iter().find_map(|wanted| {
+        names
+            .iter()
+            .find(|name| name.to_string_lossy().eq_ignore_ascii_case(wanted))
+            .map(|name| directory.join(name))
+            .filter(|cover| cover.is_file())
+    })
+}
+
+/// The first picture in `input`, center-cropped to a square. Audio files
+/// expose embedded art as a video stream, so one command handles every
+/// source.
+fn decode_cover(input: &Path) -> Option<VideoFrame> {
+    let mut command = external_command("ffmpeg");
+    command
+        .args(["-nostdin", "-loglevel", "error", "-i"])
+        .arg(input)
+        .args([
+            "-map",
+            "0:v:0",
+            "-frames:v",
+            "1",
+            "-vf",
+            &format!("crop='min(iw,ih)':'min(iw,ih)',scale={ART_PIXELS}:{ART_PIXELS}"),
+            "-f",
+            "rawvideo",
+            "-pix_fmt",
+            "rgb24",
+            "pipe:1",
+        ]);
+    let expected = ART_PIXELS as usize * ART_PIXELS as usize * 3;
+    let output = bounded_output(command, expected).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    VideoFrame::from_rgb(ART_PIXELS, ART_PIXELS, output.stdout)
+}
+
+/// The largest rectangle inside `area` that shows a `width`×`height` pixel
+/// image undistorted, centered. Half-block cells hold two pixels vertically.
+pub fn fit_rect(area: Rect, width: u16, height: u16) -> Rect {
+    if width == 0 || height == 0 || area.width == 0 || area.height == 0 {
+        return Rect::new(area.x, area.y, 0, 0);
+    }
+    let (width, height) = (width as u32, height as u32);
+    let rows = area.height as u32;
+    let mut columns = area.width as u32;
+    // Pixel height is twice the row count.
+    if columns * height > rows * 2 * width {
+        columns = rows * 2 * width / height;
+    }
+    let rows = (columns * height).div_ceil(2 * width).min(rows);
+    let (columns, rows) = (columns.max(1) as u16, rows.max(1) as u16);
+    Rect::new(
+        area.x + (area.width - columns) / 2,
+        area.y + (area.height - rows) / 2,
+        columns,
+        rows,
+    )
+}
+
+/// Draw the current cover at the top of `column`, beside the queue, and
+/// return the space left below it. Without a cover, or in a column too small
+/// to spare the rows, the whole column is returned.
+pub fn render_cover_panel(frame: &mut Frame, column: Rect, app: &App) -> Rect {
+    let Some(cover) = &app.album_art else {
+        return column;
+    };
+    let height = panel_height(column);
+    if height == 0 {
+        return column;
+    }
+    let [area, rest] =
+        Layout::vertical([Constraint::Length(height), Constraint::Min(0)]).areas(column);
+    let block = Block::default().borders(Borders::ALL).title("Cover");
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    draw_video_frame(
+        frame,
+        fit_rect(inner, cover.width, cover.height),
+        cover,
+        app.idle_video_render_mode,
+        app.color_precision,
+    );
+    rest
+}
+
+/// Rows to give the cover panel at the top of a column `area`, or zero when
+/// the column is too small to spare them.
+pub fn panel_height(area: Rect) -> u16 {
+    // A square cover fills `width - 2` columns and half as many rows.
+    let height = area.width.saturating_sub(2) / 2 + 2;
+    if area.height < 16 || area.width < 12 {
+        0
+    } else {
+        height.min(area.height / 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fit_rect, panel_height};
+    use ratatui::layout::Rect;
+
+    #[test]
+    fn covers_keep_their_aspect_ratio_in_half_block_cells() {
+        // A square image needs twice as many columns as rows.
+        assert_eq!(
+            fit_rect(Rect::new(0, 0, 80, 20), 128, 128),
+            Rect::new(20, 0, 40, 20)
+        );
+        assert_eq!(
+            fit_rect(Rect::new(5, 5, 20, 40), 128, 128),
+            Rect::new(5, 20, 20, 10)
+        );
+        assert_eq!(fit_rect(Rect::new(0, 0, 0, 10), 128, 128).area(), 0);
+    }
+
+    #[test]
+    fn the_panel_only_appears_when_the_column_has_room() {
+        assert_eq!(panel_height(Rect::new(0, 0, 40, 60)), 21);
+        assert_eq!(panel_height(Rect::new(0, 0, 80, 30)), 15);
+        assert_eq!(panel_height(Rect::new(0, 0, 40, 12)), 0);
+    }
+}
@@ -1,21 +1,89 @@
+use crate::download_format::{AUDIO_EXTENSIONS, DownloadFormat};
+use crate::download_manager::DownloadLimits;
+use crate::download_progress::DownloadProgress;
+use crate::history::PlayHistory;
 use crate::idle_mode::{ColorPrecision, VideoRenderMode};
+use crate::library_check::partial_owner;
+use crate::library_duplicates::DuplicateView;
+use crate::library_filter::LibrarySort;
+use crate::library_roots::LibraryRoots;
+use crate::loudness::{NormalizationMode, ReplayGain, TrackLoudness, album_gain};
 use crate::lyrics::LyricLine;
-use crate::security::{read_file_limited, sanitize_display_text};
+use crate::player::{MAX_CROSSFADE_SECONDS, MAX_VOLUME, PlayOutcome, RepeatMode};
+use crate::playlists::Playlists;
+use crate::security::{
+    containing_root, read_file_limited, sanitize_display_text, valid_youtube_id,
+};
+use crate::smart_playlists::SmartPlaylists;
+use crate::track_metadata::TrackMetadata;
+use crate::video_screensaver::VideoFrame;
 use crate::wallpaper::HomeWallpaper;
 use dirs::audio_dir;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Version 1 indexes are bare `title|path` lines (optionally followed by
+/// loudness fields); version 2 adds a header and descriptive metadata.
+pub const LIBRARY_INDEX_VERSION: u32 = 2;
+const LIBRARY_INDEX_HEADER: &str = "#crest-library-index v";
 
 pub struct DownloadJob {
     pub path: String,
     pub title: String,
     pub state: DownloadState,
+    /// A library download run by `DownloadManager`, rather than a temporary
+    /// stream download for the queue.
+    pub permanent: bool,
+    /// The latest report from a permanent download's worker.
+    pub progress: Option<DownloadProgress>,
 }
 
 #[derive(Clone, Copy, Eq, PartialEq)]
 pub enum DownloadState {
     Queued,
     Downloading,
+    Paused,
+}
+
+/// Loudness data stored alongside a library entry in the index.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LibraryLoudness {
+    pub album: Option<String>,
+    pub gain: ReplayGain,
+}
+
+/// Listening data stored alongside a library entry in the index.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrackStats {
+    pub favorite: bool,
+    /// Stars from 0 (unrated) to `MAX_RATING`.
+    pub rating: u8,
+    /// Times the track played to its end.
+    pub plays: u32,
+    /// Times the track was skipped with `Ctrl+N`.
+    pub skips: u32,
+    /// Seconds since the Unix epoch when the track last played to its end.
+    pub last_played: Option<u64>,
+}
+
+pub const MAX_RATING: u8 = 5;
+
+impl TrackStats {
+    /// Favorite mark and stars for list rows, e.g. `♥ ★★★★`.
+    pub fn badge(&self) -> String {
+        let mut badge = String::new();
+        if self.favorite {
+            badge.push('♥');
+        }
+        if self.rating > 0 {
+            if !badge.is_empty() {
+                badge.push(' ');
+            }
+            badge.push_str(&"★".repeat(usize::from(self.rating)));
+        }
+        badge
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -31,6 +99,20 @@ struct PersistedSettings {
     hardware_acceleration_enabled: bool,
     autoplay_enabled: bool,
     discord_presence_enabled: bool,
+    volume: u8,
+    muted: bool,
+    normalization_mode: String,
+    crossfade_seconds: u8,
+    repeat_mode: String,
+    shuffle_enabled: bool,
+    library_folders: Vec<String>,
+    dismissed_imports: Vec<String>,
+    library_roots: Vec<String>,
+    download_root: Option<String>,
+    library_sort: String,
+    download_workers: usize,
+    encode_workers: usize,
+    download_format: String,
 }
 
 impl Default for PersistedSettings {
@@ -46,6 +128,20 @@ impl Default for PersistedSettings {
             hardware_acceleration_enabled: false,
             autoplay_enabled: false,
             discord_presence_enabled: false,
+            volume: 100,
+            muted: false,
+            normalization_mode: "off".to_string(),
+            crossfade_seconds: 0,
+            repeat_mode: "off".to_string(),
+            shuffle_enabled: false,
+            library_folders: Vec::new(),
+            dismissed_imports: Vec::new(),
+            library_roots: Vec::new(),
+            download_root: None,
+            library_sort: "added".to_string(),
+            download_workers: 2,
+            encode_workers: 1,
+            download_format: "mp3-v2".to_string(),
         }
     }
 }
@@ -57,9 +153,29 @@ pub struct App {
     pub searching: bool,
     pub error: Option<String>,
     pub library: Vec<(String, String)>,
+    pub library_loudness: HashMap<String, LibraryLoudness>,
+    pub library_metadata: HashMap<String, TrackMetadata>,
+    pub library_stats: HashMap<String, TrackStats>,
+    library_index_version: u32,
     library_paths: HashSet<String>,
     available_library_paths: HashSet<String>,
     pub show_library: bool,
+    pub history: PlayHistory,
+    pub show_history: bool,
+    pub history_selected: usize,
+    pub playlists: Playlists,
+    pub show_playlists: bool,
+    pub playlist_selected: usize,
+    pub smart_playlists: SmartPlaylists,
+    /// The smart playlist Downloaded Music Only is narrowed to, by name.
+    pub smart_view: Option<String>,
+    /// Open while comparing duplicate library tracks.
+    pub duplicates: Option<DuplicateView>,
+    pub queue_focused: bool,
+    pub queue_selected: usize,
+    /// Tab moves focus from the queue on to the Download Queue panel.
+    pub downloads_focused: bool,
+    pub download_selected: usize,
     pub lyrics: Vec<LyricLine>,
     pub lyrics_message: String,
     pub lyrics_synced: bool,
@@ -75,15 +191,39 @@ pub struct App {
     pub hardware_acceleration_enabled: bool,
     pub autoplay_enabled: bool,
     pub discord_presence_enabled: bool,
+    pub volume: u8,
+    pub muted: bool,
+    pub normalization_mode: NormalizationMode,
+    pub crossfade_seconds: u8,
+    pub repeat_mode: RepeatMode,
+    pub shuffle_enabled: bool,
+    /// Folders the library scanner imports music from.
+    pub library_folders: Vec<String>,
+    /// Imported files dropped from the library, which scans leave out.
+    pub dismissed_imports: HashSet<String>,
+    /// Folders downloads are stored in, including the Music directory.
+    pub library_roots: LibraryRoots,
+    pub library_sort: LibrarySort,
+    /// Concurrent fetch and encode workers for permanent downloads.
+    pub download_limits: DownloadLimits,
+    pub download_format: DownloadFormat,
     pub downloads: Vec<DownloadJob>,
     cancelled_downloads: HashSet<String>,
     pub home_wallpaper: Option<HomeWallpaper>,
+    /// Cover of the current track, decoded by `AlbumArtLoader`.
+    pub album_art: Option<VideoFrame>,
 }
 
 impl App {
     pub fn new() -> Self {
         let settings = load_settings();
-        let library = load_library();
+        let LibraryIndex {
+            tracks: library,
+            loudness: library_loudness,
+            metadata: library_metadata,
+            stats: library_stats,
+            version: library_index_version,
+        } = load_library_index();
         let library_paths = library.iter().map(|(_, path)| path.clone()).collect();
         let available_library_paths = library
             .iter()
@@ -97,9 +237,26 @@ impl App {
             searching: false,
             error: None,
             library,
+            library_loudness,
+            library_metadata,
+            library_stats,
+            library_index_version,
             library_paths,
             available_library_paths,
             show_library: false,
+            history: PlayHistory::load(),
+            show_history: false,
+            history_selected: 0,
+            playlists: Playlists::load(),
+            show_playlists: false,
+            playlist_selected: 0,
+            smart_playlists: SmartPlaylists::load(),
+            smart_view: None,
+            duplicates: None,
+            queue_focused: false,
+            queue_selected: 0,
+            downloads_focused: false,
+            download_selected: 0,
             lyrics: Vec::new(),
             lyrics_message: "Play a song to load lyrics.".to_string(),
             lyrics_synced: false,
@@ -126,9 +283,28 @@ impl App {
             hardware_acceleration_enabled: settings.hardware_acceleration_enabled,
             autoplay_enabled: settings.autoplay_enabled,
             discord_presence_enabled: settings.discord_presence_enabled,
+            volume: settings.volume.min(MAX_VOLUME),
+            muted: settings.muted,
+            normalization_mode: NormalizationMode::from_id(&settings.normalization_mode),
+            crossfade_seconds: settings.crossfade_seconds.min(MAX_CROSSFADE_SECONDS),
+            repeat_mode: RepeatMode::from_id(&settings.repeat_mode).unwrap_or_default(),
+            shuffle_enabled: settings.shuffle_enabled,
+            library_folders: settings.library_folders,
+            dismissed_imports: settings.dismissed_imports.into_iter().collect(),
+            library_roots: LibraryRoots::new(
+                &settings.library_roots,
+                settings.download_root.as_deref(),
+            ),
+            library_sort: LibrarySort::from_id(&settings.library_sort).unwrap_or_default(),
+            download_limits: DownloadLimits::new(
+                settings.download_workers,
+                settings.encode_workers,
+            ),
+            download_format: DownloadFormat::from_id(&settings.download_format).unwrap_or_default(),
             downloads: Vec::new(),
             cancelled_downloads: HashSet::new(),
             home_wallpaper: HomeWallpaper::load(),
+            album_art: None,
         }
     }
 
@@ -140,10 +316,43 @@ impl App {
         self.available_library_paths.contains(path)
     }
 
+    /// A missing song whose library root or scanned folder is unmounted; it
+    /// comes back with the disk.
+    pub fn is_library_track_offline(&self, path: &str) -> bool {
+        if self.is_library_file_available(path) {
+            return false;
+        }
+        self.library_roots.is_offline_track(path)
+            || (self.is_imported_track(path)
+                && self.library_folders.iter().any(|folder| {
+                    std::path::Path::new(path).starts_with(folder)
+                        && !std::path::Path::new(folder).is_dir()
+                }))
+    }
+
+    /// Recheck which indexed files exist after other programs changed the
+    /// disk. Returns whether anything changed.
+    pub fn refresh_library_availability(&mut self) -> bool {
+        let roots_changed = self.library_roots.refresh();
+        let available: HashSet<String> = self
+            .library
+            .iter()
+            .filter(|(_, path)| std::path::Path::new(path).is_file())
+            .map(|(_, path)| path.clone())
+            .collect();
+        let changed = available != self.available_library_paths;
+        self.available_library_paths = available;
+        changed || roots_changed
+    }
+
     pub fn add_library_track(&mut self, title: String, path: String) {
         let path = normalize_existing_path(path);
         self.library_paths.insert(path.clone());
         self.available_library_paths.insert(path.clone());
+        self.library_metadata
+            .entry(path.clone())
+            .or_default()
+            .fill_from_file(std::path::Path::new(&path));
         if let Some(track) = self
             .library
             .iter_mut()
@@ -155,9 +364,206 @@ impl App {
         }
     }
 
+    /// Replace a track's descriptive metadata. An album name also groups the
+    /// track for album gain unless its file tags already name one.
+    pub fn set_track_metadata(&mut self, path: &str, mut metadata: TrackMetadata) {
+        let path = normalize_existing_path(path.to_string());
+        metadata.fill_from_file(std::path::Path::new(&path));
+        if let Some(album) = &metadata.album
+            && let Some(loudness) = self.library_loudness.get_mut(&path)
+            && loudness.album.is_none()
+        {
+            loudness.album = Some(album.clone());
+            self.refresh_album_gains();
+        }
+        self.library_metadata.insert(path, metadata);
+    }
+
+    pub fn track_metadata(&self, path: &str) -> Option<&TrackMetadata> {
+        self.library_metadata.get(path)
+    }
+
+    /// Upgrade an index loaded from an older schema by reading file sizes and
+    /// dates from disk. Returns whether the index should be saved.
+    pub fn migrate_library_index(&mut self) -> bool {
+        if self.library_index_version >= LIBRARY_INDEX_VERSION {
+            return false;
+        }
+        for (_, path) in &self.library {
+            self.library_metadata
+                .entry(path.clone())
+                .or_default()
+                .fill_from_file(std::path::Path::new(path));
+        }
+        self.library_index_version = LIBRARY_INDEX_VERSION;
+        !self.library.is_empty()
+    }
+
+    pub fn replay_gain(&self, path: &str) -> ReplayGain {
+        self.library_loudness
+            .get(path)
+            .map(|loudness| loudness.gain)
+            .unwrap_or_default()
+    }
+
+    /// Record a measurement and refresh the album gain of every track sharing
+    /// its album tag.
+    pub fn set_track_loudness(&mut self, path: &str, loudness: TrackLoudness) {
+        let path = normalize_existing_path(path.to_string());
+        let album = loudness.album.clone().or_else(|| {
+            self.library_metadata
+                .get(&path)
+                .and_then(|metadata| metadata.album.clone())
+        });
+        let entry = self.library_loudness.entry(path).or_default();
+        entry.gain.track_gain = Some(loudness.track_gain());
+        entry.album = album;
+        self.refresh_album_gains();
+    }
+
+    /// Available tracks that have never been measured, in library order.
+    pub fn tracks_missing_loudness(&self) -> Vec<String> {
+        self.library
+            .iter()
+            .map(|(_, path)| path)
+            .filter(|path| self.is_library_file_available(path))
+            .filter(|path| {
+                self.library_loudness
+                    .get(path.as_str())
+                    .is_none_or(|loudness| loudness.gain.track_gain.is_none())
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn refresh_album_gains(&mut self) {
+        let mut albums: HashMap<String, Vec<f64>> = HashMap::new();
+        for loudness in self.library_loudness.values() {
+            if let (Some(album), Some(gain)) = (&loudness.album, loudness.gain.track_gain) {
+                albums.entry(album.clone()).or_default().push(gain);
+            }
+        }
+        for loudness in self.library_loudness.values_mut() {
+            loudness.gain.album_gain = loudness
+                .album
+                .as_ref()
+                .and_then(|album| albums.get(album))
+                .and_then(|gains| album_gain(gains));
+        }
+    }
+
+    pub fn track_stats(&self, path: &str) -> Option<&TrackStats> {
+        self.library_stats.get(path)
+    }
+
+    /// Flip a library track's favorite mark and return the new state.
+    pub fn toggle_favorite(&mut self, path: &str) -> Option<bool> {
+        if !self.is_library_path(path) {
+            return None;
+        }
+        let stats = self.library_stats.entry(path.to_string()).or_default();
+        stats.favorite = !stats.favorite;
+        Some(stats.favorite)
+    }
+
+    /// Rate a library track from 0 (clears the rating) to `MAX_RATING` stars.
+    pub fn set_rating(&mut self, path: &str, rating: u8) -> bool {
+        if !self.is_library_path(path) || rating > MAX_RATING {
+            return false;
+        }
+        self.library_stats
+            .entry(path.to_string())
+            .or_default()
+            .rating = rating;
+        true
+    }
+
+    /// Count a finished or skipped playback. Streams and other files outside
+    /// the library are not tracked.
+    pub fn record_track_outcome(&mut self, path: &str, outcome: PlayOutcome) -> bool {
+        if !self.is_library_path(path) || outcome == PlayOutcome::Stopped {
+            return false;
+        }
+        let stats = self.library_stats.entry(path.to_string()).or_default();
+        if outcome == PlayOutcome::Finished {
+            stats.plays = stats.plays.saturating_add(1);
+            stats.last_played = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .ok();
+        } else {
+            stats.skips = stats.skips.saturating_add(1);
+        }
+        true
+    }
+
+    pub fn is_imported_track(&self, path: &str) -> bool {
+        self.track_metadata(path)
+            .is_some_and(|metadata| metadata.imported)
+    }
+
+    /// Drop an imported track from the library for good: its file stays on
+    /// disk, and later scans and watcher events do not import it again.
+    pub fn dismiss_imported_track(&mut self, path: &str) {
+        self.forget_library_track(path);
+        self.dismissed_imports.insert(path.to_string());
+    }
+
+    /// Drop a track from the index without touching its file.
+    pub fn forget_library_track(&mut self, path: &str) {
+        self.library
+            .retain(|(_, library_path)| library_path != path);
+        self.library_paths.remove(path);
+        self.available_library_paths.remove(path);
+        self.library_metadata.remove(path);
+        self.library_stats.remove(path);
+        if self.library_loudness.remove(path).is_some() {
+            self.refresh_album_gains();
+        }
+    }
+
+    /// Keep a track's title, metadata and loudness when its file moves.
+    pub fn move_library_track(&mut self, from: &str, to: String) {
+        let Some(index) = self
+            .library
+            .iter()
+            .position(|(_, library_path)| library_path == from)
+        else {
+            return;
+        };
+        self.library[index].1 = to.clone();
+        self.library_paths.remove(from);
+        self.available_library_paths.remove(from);
+        self.library_paths.insert(to.clone());
+        self.available_library_paths.insert(to.clone());
+        if let Some(mut metadata) = self.library_metadata.remove(from) {
+            metadata.fill_from_file(std::path::Path::new(&to));
+            self.library_metadata.insert(to.clone(), metadata);
+        }
+        if let Some(stats) = self.library_stats.remove(from) {
+            self.library_stats.insert(to.clone(), stats);
+        }
+        if let Some(loudness) = self.library_loudness.remove(from) {
+            self.library_loudness.insert(to, loudness);
+        }
+    }
+
+    /// Imported tracks are only removed from the index; their files belong to
+    /// the user's own collection.
     pub fn remove_library_track(&mut self, path: &str) -> std::io::Result<()> {
-        let path = validated_library_media_path(path)?;
-        let video_cache = validated_library_media_path(path.with_extension("crestvid"))?;
+        if self.is_imported_track(path) {
+            self.forget_library_track(path);
+            return Ok(());
+        }
+        if self.library_roots.is_offline_track(path) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "its library root is offline",
+            ));
+        }
+        let roots = self.library_roots.paths();
+        let path = validated_library_media_path(path, &roots)?;
+        let video_cache = validated_library_media_path(path.with_extension("crestvid"), &roots)?;
         match std::fs::remove_file(&path) {
             Ok(()) => {}
             Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
@@ -173,6 +579,11 @@ impl App {
         let path = path.to_string_lossy();
         self.library_paths.remove(path.as_ref());
         self.available_library_paths.remove(path.as_ref());
+        self.library_metadata.remove(path.as_ref());
+        self.library_stats.remove(path.as_ref());
+        if self.library_loudness.remove(path.as_ref()).is_some() {
+            self.refresh_album_gains();
+        }
         Ok(())
     }
 
@@ -181,6 +592,8 @@ impl App {
             path,
             title,
             state: DownloadState::Downloading,
+            permanent: false,
+            progress: None,
         });
     }
 
@@ -189,6 +602,8 @@ impl App {
             path,
             title,
             state: DownloadState::Queued,
+            permanent: true,
+            progress: None,
         });
     }
 
@@ -198,6 +613,35 @@ impl App {
         }
     }
 
+    /// Show a permanent download as paused or queued again. Progress starts
+    /// over when it resumes.
+    pub fn set_download_state(&mut self, path: &str, state: DownloadState) {
+        if let Some(job) = self.downloads.iter_mut().find(|job| job.path == path) {
+            job.state = state;
+            job.progress = None;
+        }
+    }
+
+    /// Move a waiting download ahead of every other waiting one.
+    pub fn move_download_to_front(&mut self, path: &str) {
+        let Some(index) = self.downloads.iter().position(|job| job.path == path) else {
+            return;
+        };
+        let job = self.downloads.remove(index);
+        let front = self
+            .downloads
+            .iter()
+            .position(|job| job.state != DownloadState::Downloading)
+            .unwrap_or(self.downloads.len());
+        self.downloads.insert(front, job);
+    }
+
+    pub fn set_download_progress(&mut self, path: &str, progress: DownloadProgress) {
+        if let Some(job) = self.downloads.iter_mut().find(|job| job.path == path) {
+            job.progress = Some(progress);
+        }
+    }
+
     /// Remove a completed job and report whether cleanup cancelled it.
     pub fn finish_download(&mut self, path: &str) -> bool {
         self.downloads.retain(|job| job.path != path);
@@ -212,15 +656,29 @@ impl App {
         self.downloads.iter().any(|job| job.path == path)
     }
 
+    /// The worker keeps running; its completion is discarded and its file removed.
+    pub fn cancel_download(&mut self, path: &str) {
+        if self.is_downloading(path) {
+            self.downloads.retain(|job| job.path != path);
+            self.cancelled_downloads.insert(path.to_string());
+        }
+    }
+
     pub fn cancel_active_downloads(&mut self) {
         self.cancelled_downloads
             .extend(self.downloads.iter().map(|job| job.path.clone()));
         self.downloads.clear();
     }
 
-    /// Remove only files explicitly recorded in Crest Player's library index.
+    /// Remove only files Crest Player downloaded into its library index.
+    /// Imported collections are left in place.
     pub fn delete_all_library_media(&mut self) -> Vec<String> {
-        let paths: Vec<String> = self.library.iter().map(|(_, path)| path.clone()).collect();
+        let paths: Vec<String> = self
+            .library
+            .iter()
+            .map(|(_, path)| path.clone())
+            .filter(|path| !self.is_imported_track(path))
+            .collect();
         let mut errors = Vec::new();
         for path in paths {
             if let Err(error) = self.remove_library_track(&path) {
@@ -237,22 +695,53 @@ fn normalize_existing_path(path: String) -> String {
         .unwrap_or(path)
 }
 
-fn validated_library_media_path(
+/// A media path Crest Player may delete: an audio file or video cache
+/// directly inside one of the library `roots`, with no symlink leading
+/// elsewhere.
+pub fn validated_library_media_path(
     path: impl AsRef<std::path::Path>,
+    roots: &[std::path::PathBuf],
 ) -> std::io::Result<std::path::PathBuf> {
-    let root = audio_dir()
-        .ok_or_else(|| std::io::Error::other("music directory is unavailable"))?
-        .canonicalize()?;
     let candidate = path.as_ref();
-    let parent = candidate.parent().ok_or_else(|| {
-        std::io::Error::new(std::io::ErrorKind::InvalidInput, "media path has no parent")
-    })?;
-    if parent.canonicalize()? != root {
+    let media = candidate
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension == "crestvid" || AUDIO_EXTENSIONS.contains(&extension));
+    if !media {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "refusing to delete a file that is not library media",
+        ));
+    }
+    contained_library_file(candidate, roots)
+}
+
+/// A leftover partial download Crest Player may delete, held to the same
+/// containment rules as [`validated_library_media_path`].
+pub fn validated_library_partial_path(
+    path: impl AsRef<std::path::Path>,
+    roots: &[std::path::PathBuf],
+) -> std::io::Result<std::path::PathBuf> {
+    let candidate = path.as_ref();
+    if partial_owner(candidate).is_none() {
         return Err(std::io::Error::new(
             std::io::ErrorKind::PermissionDenied,
-            "refusing to delete media outside the Music directory",
+            "refusing to delete a file that is not a partial download",
         ));
     }
+    contained_library_file(candidate, roots)
+}
+
+fn contained_library_file(
+    candidate: &std::path::Path,
+    roots: &[std::path::PathBuf],
+) -> std::io::Result<std::path::PathBuf> {
+    let root = containing_root(candidate, roots).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "refusing to delete media outside the library roots",
+        )
+    })?;
     if candidate.exists() {
         let canonical = candidate.canonicalize()?;
         if canonical.parent() != Some(root.as_path()) || !canonical.is_file() {
@@ -269,6 +758,12 @@ fn settings_path() -> Option<std::path::PathBuf> {
     dirs::config_dir().map(|directory| directory.join("crest-player/settings.json"))
 }
 
+/// The configured library roots, for command-line tools without an [`App`].
+pub fn load_library_roots() -> LibraryRoots {
+    let settings = load_settings();
+    LibraryRoots::new(&settings.library_roots, settings.download_root.as_deref())
+}
+
 fn load_settings() -> PersistedSettings {
     settings_path()
         .and_then(|path| read_file_limited(path, 1024 * 1024).ok())
@@ -299,6 +794,24 @@ pub fn save_settings(app: &App) {
         hardware_acceleration_enabled: app.hardware_acceleration_enabled,
         autoplay_enabled: app.autoplay_enabled,
         discord_presence_enabled: app.discord_presence_enabled,
+        volume: app.volume,
+        muted: app.muted,
+        normalization_mode: app.normalization_mode.id().to_string(),
+        crossfade_seconds: app.crossfade_seconds,
+        repeat_mode: app.repeat_mode.id().to_string(),
+        shuffle_enabled: app.shuffle_enabled,
+        library_folders: app.library_folders.clone(),
+        dismissed_imports: {
+            let mut dismissed: Vec<String> = app.dismissed_imports.iter().cloned().collect();
+            dismissed.sort();
+            dismissed
+        },
+        library_roots: app.library_roots.extra(),
+        download_root: app.library_roots.download_setting(),
+        library_sort: app.library_sort.id().to_string(),
+        download_workers: app.download_limits.fetch,
+        encode_workers: app.download_limits.encode,
+        download_format: app.download_format.id().to_string(),
     };
     if let Some(parent) = path.parent() {
         let _ = std::fs::create_dir_all(parent);
@@ -308,35 +821,374 @@ pub fn save_settings(app: &App) {
     }
 }
 
-pub fn save_library(library: &[(String, String)]) {
+pub fn save_library(app: &App) {
     if let Some(dir) = audio_dir() {
         let path = dir.join("ytmusic_library.csv");
-        let _ = std::fs::write(
+        let mut index = format!("{LIBRARY_INDEX_HEADER}{LIBRARY_INDEX_VERSION}\n");
+        for (title, path) in &app.library {
+            index.push_str(&library_index_line(
+                title,
+                path,
+                app.library_metadata.get(path),
+                app.library_loudness.get(path),
+                app.library_stats.get(path),
+            ));
+        }
+        let _ = std::fs::write(path, index);
+    }
+}
+
+fn library_index_line(
+    title: &str,
+    path: &str,
+    metadata: Option<&TrackMetadata>,
+    loudness: Option<&LibraryLoudness>,
+    stats: Option<&TrackStats>,
+) -> String {
+    let field = |value: &str| sanitize_display_text(value).replace('|', "_");
+    let mut line = format!("{}|{}", field(title), path);
+    let album = metadata
+        .and_then(|metadata| metadata.album.as_ref())
+        .or_else(|| loudness.and_then(|loudness| loudness.album.as_ref()));
+    if let Some(metadata) = metadata {
+        if let Some(artist) = &metadata.artist {
+            line.push_str(&format!("|artist={}", field(artist)));
+        }
+        if let Some(year) = metadata.year {
+            line.push_str(&format!("|year={year}"));
+        }
+        if let Some(duration) = metadata.duration {
+            line.push_str(&format!("|duration={:.3}", duration.as_secs_f64()));
+        }
+        if let Some(video_id) = &metadata.video_id {
+            line.push_str(&format!("|video_id={video_id}"));
+        }
+        if let Some(added_at) = metadata.added_at {
+            line.push_str(&format!("|added={added_at}"));
+        }
+        if let Some(file_size) = metadata.file_size {
+            line.push_str(&format!("|size={file_size}"));
+        }
+        if let Some(modified_at) = metadata.modified_at {
+            line.push_str(&format!("|modified={modified_at}"));
+        }
+        if metadata.imported {
+            line.push_str("|imported=1");
+        }
+    }
+    if let Some(album) = album {
+        line.push_str(&format!("|album={}", field(album)));
+    }
+    if let Some(stats) = stats {
+        if stats.favorite {
+            line.push_str("|favorite=1");
+        }
+        if stats.rating > 0 {
+            line.push_str(&format!("|rating={}", stats.rating));
+        }
+        if stats.plays > 0 {
+            line.push_str(&format!("|plays={}", stats.plays));
+        }
+        if stats.skips > 0 {
+            line.push_str(&format!("|skips={}", stats.skips));
+        }
+        if let Some(last_played) = stats.last_played {
+            line.push_str(&format!("|played={last_played}"));
+        }
+    }
+    if let Some(loudness) = loudness {
+        if let Some(gain) = loudness.gain.track_gain {
+            line.push_str(&format!("|track_gain={gain:.2}"));
+        }
+        if let Some(gain) = loudness.gain.album_gain {
+            line.push_str(&format!("|album_gain={gain:.2}"));
+        }
+    }
+    line.push('\n');
+    line
+}
+
+/// Add the tracks of an archived index that `existing` lacks, with their
+/// paths passed through `rebase`. Returns the merged index and how many
+/// tracks it gained.
+pub fn merge_library_index(
+    existing: &str,
+    archived: &str,
+    rebase: impl Fn(&str) -> String,
+) -> (String, usize) {
+    let existing = parse_library_index(existing);
+    let archived = parse_library_index(archived);
+    // An older version on either side makes the next launch migrate it.
+    let version = if existing.tracks.is_empty() {
+        archived.version
+    } else {
+        existing.version.min(archived.version)
+    };
+    let mut index = format!("{LIBRARY_INDEX_HEADER}{version}\n");
+    let mut paths = HashSet::new();
+    for (title, path) in &existing.tracks {
+        paths.insert(path.clone());
+        index.push_str(&library_index_line(
+            title,
             path,
-            library
-                .iter()
-                .map(|(t, p)| format!("{}|{}\n", sanitize_display_text(t).replace('|', "_"), p))
-                .collect::<String>(),
-        );
+            existing.metadata.get(path),
+            existing.loudness.get(path),
+            existing.stats.get(path),
+        ));
+    }
+    let mut added = 0;
+    for (title, path) in &archived.tracks {
+        let rebased = rebase(path);
+        if !paths.insert(rebased.clone()) {
+            continue;
+        }
+        index.push_str(&library_index_line(
+            title,
+            &rebased,
+            archived.metadata.get(path),
+            archived.loudness.get(path),
+            archived.stats.get(path),
+        ));
+        added += 1;
     }
+    (index, added)
 }
 
 pub fn load_library() -> Vec<(String, String)> {
+    load_library_index().tracks
+}
+
+pub struct LibraryIndex {
+    pub tracks: Vec<(String, String)>,
+    pub loudness: HashMap<String, LibraryLoudness>,
+    pub metadata: HashMap<String, TrackMetadata>,
+    pub stats: HashMap<String, TrackStats>,
+    pub version: u32,
+}
+
+pub fn load_library_index() -> LibraryIndex {
     if let Some(dir) = audio_dir() {
         let path = dir.join("ytmusic_library.csv");
         if let Ok(data) = read_file_limited(path, 16 * 1024 * 1024) {
-            return String::from_utf8_lossy(&data)
-                .lines()
-                .filter_map(|l| {
-                    l.split_once('|').map(|(t, p)| {
-                        (
-                            sanitize_display_text(t),
-                            normalize_existing_path(p.to_string()),
-                        )
-                    })
-                })
-                .collect();
-        }
-    }
-    Vec::new()
+            return parse_library_index(&String::from_utf8_lossy(&data));
+        }
+    }
+    // There is nothing to migrate without an existing index.
+    LibraryIndex {
+        tracks: Vec::new(),
+        loudness: HashMap::new(),
+        metadata: HashMap::new(),
+        stats: HashMap::new(),
+        version: LIBRARY_INDEX_VERSION,
+    }
+}
+
+fn parse_library_index(data: &str) -> LibraryIndex {
+    let mut library = Vec::new();
+    let mut loudness_by_path = HashMap::new();
+    let mut metadata_by_path = HashMap::new();
+    let mut stats_by_path = HashMap::new();
+    let mut version = 1;
+    for line in data.lines() {
+        if let Some(header) = line.strip_prefix(LIBRARY_INDEX_HEADER) {
+            version = header.trim().parse().unwrap_or(1);
+            continue;
+        }
+        let Some((title, rest)) = line.split_once('|') else {
+            continue;
+        };
+        // Optional fields trail the path so older two-column indexes load
+        // unchanged; the path itself may legitimately contain `|`.
+        let mut fields: Vec<&str> = rest.split('|').collect();
+        let mut loudness = LibraryLoudness::default();
+        let mut metadata = TrackMetadata::default();
+        let mut stats = TrackStats::default();
+        while fields.len() > 1 {
+            let field = fields[fields.len() - 1];
+            let Some((key, value)) = field.split_once('=') else {
+                break;
+            };
+            match key {
+                "album" => {
+                    loudness.album = Some(sanitize_display_text(value));
+                    metadata.album = loudness.album.clone();
+                }
+                "track_gain" => {
+                    loudness.gain.track_gain =
+                        value.parse().ok().filter(|gain: &f64| gain.is_finite());
+                }
+                "album_gain" => {
+                    loudness.gain.album_gain =
+                        value.parse().ok().filter(|gain: &f64| gain.is_finite());
+                }
+                "artist" => metadata.artist = Some(sanitize_display_text(value)),
+                "duration" => {
+                    metadata.duration = value
+                        .parse()
+                        .ok()
+                        .filter(|seconds: &f64| seconds.is_finite() && *seconds > 0.0)
+                        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok());
+                }
+                "video_id" => {
+                    metadata.video_id = valid_youtube_id(value).then(|| value.to_string());
+                }
+                "year" => metadata.year = value.parse().ok(),
+                "added" => metadata.added_at = value.parse().ok(),
+                "size" => metadata.file_size = value.parse().ok(),
+                "modified" => metadata.modified_at = value.parse().ok(),
+                "imported" => metadata.imported = value == "1",
+                "favorite" => stats.favorite = value == "1",
+                "rating" => {
+                    stats.rating = value.parse::<u8>().unwrap_or_default().min(MAX_RATING);
+                }
+                "plays" => stats.plays = value.parse().unwrap_or_default(),
+                "skips" => stats.skips = value.parse().unwrap_or_default(),
+                "played" => stats.last_played = value.parse().ok(),
+                // Fields added by a newer build are skipped so they never end
+                // up in the path. Older indexes have no such fields, and a
+                // path there may itself end in `|name=value`.
+                _ if version > LIBRARY_INDEX_VERSION
+                    && !key.is_empty()
+                    && key
+                        .bytes()
+                        .all(|byte| byte.is_ascii_lowercase() || byte == b'_') => {}
+                _ => break,
+            }
+            fields.pop();
+        }
+        let path = normalize_existing_path(fields.join("|"));
+        if loudness != LibraryLoudness::default() {
+            loudness_by_path.insert(path.clone(), loudness);
+        }
+        if metadata != TrackMetadata::default() {
+            metadata_by_path.insert(path.clone(), metadata);
+        }
+        if stats != TrackStats::default() {
+            stats_by_path.insert(path.clone(), stats);
+        }
+        library.push((sanitize_display_text(title), path));
+    }
+    LibraryIndex {
+        tracks: library,
+        loudness: loudness_by_path,
+        metadata: metadata_by_path,
+        stats: stats_by_path,
+        version,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        LIBRARY_INDEX_VERSION, TrackStats, library_index_line, parse_library_index,
+        validated_library_media_path, validated_library_partial_path,
+    };
+    use crate::track_metadata::TrackMetadata;
+    use std::time::Duration;
+
+    #[test]
+    fn library_index_reads_legacy_and_loudness_fields() {
+        let index = parse_library_index(
+            "Old|/music/old.mp3\nNew|/music/a|b.mp3|album=Blue|track_gain=-4.20|album_gain=-3.10\n",
+        );
+        assert_eq!(index.version, 1);
+        let (library, loudness) = (index.tracks, index.loudness);
+        assert_eq!(
+            library[0],
+            ("Old".to_string(), "/music/old.mp3".to_string())
+        );
+        assert_eq!(library[1].1, "/music/a|b.mp3");
+        let new = loudness.get("/music/a|b.mp3").unwrap();
+        assert_eq!(new.album.as_deref(), Some("Blue"));
+        assert_eq!(new.gain.track_gain, Some(-4.2));
+        assert_eq!(new.gain.album_gain, Some(-3.1));
+        assert!(!loudness.contains_key("/music/old.mp3"));
+    }
+
+    #[test]
+    fn newer_library_indexes_skip_unknown_fields() {
+        let newer = format!(
+            "#crest-library-index v{}\nSong|/music/a|b.mp3|artist=Blue|mood=calm|plays=3\n",
+            LIBRARY_INDEX_VERSION + 1
+        );
+        let index = parse_library_index(&newer);
+        assert_eq!(index.tracks[0].1, "/music/a|b.mp3");
+        assert_eq!(
+            index.metadata["/music/a|b.mp3"].artist.as_deref(),
+            Some("Blue")
+        );
+        assert_eq!(index.stats["/music/a|b.mp3"].plays, 3);
+
+        let current = format!(
+            "#crest-library-index v{LIBRARY_INDEX_VERSION}\nSong|/music/a|b=c.mp3|plays=3\n"
+        );
+        assert_eq!(
+            parse_library_index(&current).tracks[0].1,
+            "/music/a|b=c.mp3"
+        );
+    }
+
+    #[test]
+    fn library_index_round_trips_track_metadata() {
+        let metadata = TrackMetadata {
+            artist: Some("Some | Artist".to_string()),
+            album: Some("Blue".to_string()),
+            year: Some(1987),
+            duration: Some(Duration::from_millis(213_500)),
+            video_id: Some("dQw4w9WgXcQ".to_string()),
+            added_at: Some(1_700_000_000),
+            file_size: Some(4_096),
+            modified_at: Some(1_700_000_100),
+            imported: true,
+        };
+        let stats = TrackStats {
+            favorite: true,
+            rating: 4,
+            plays: 12,
+            skips: 3,
+            last_played: Some(1_700_000_200),
+        };
+        let index = parse_library_index(&format!(
+            "#crest-library-index v{LIBRARY_INDEX_VERSION}\n{}",
+            library_index_line(
+                "Song",
+                "/music/song.mp3",
+                Some(&metadata),
+                None,
+                Some(&stats)
+            )
+        ));
+        assert_eq!(index.version, LIBRARY_INDEX_VERSION);
+        assert_eq!(index.tracks[0].1, "/music/song.mp3");
+        let parsed = &index.metadata["/music/song.mp3"];
+        assert_eq!(parsed.artist.as_deref(), Some("Some _ Artist"));
+        assert_eq!(
+            TrackMetadata {
+                artist: metadata.artist.clone(),
+                ..parsed.clone()
+            },
+            metadata
+        );
+        assert_eq!(index.stats["/music/song.mp3"], stats);
+    }
+
+    #[test]
+    fn only_library_media_and_partials_may_be_deleted() {
+        let root = std::env::temp_dir().join(format!("crest-player-media-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let root = root.canonicalize().unwrap();
+        let roots = [root.clone()];
+        for name in ["Song.opus", "Song.crestvid", "notes.txt", "Song.mp3.part"] {
+            std::fs::write(root.join(name), b"x").unwrap();
+        }
+
+        assert!(validated_library_media_path(root.join("Song.opus"), &roots).is_ok());
+        assert!(validated_library_media_path(root.join("Song.crestvid"), &roots).is_ok());
+        assert!(validated_library_media_path(root.join("notes.txt"), &roots).is_err());
+        assert!(validated_library_media_path(root.join("settings"), &roots).is_err());
+        assert!(validated_library_media_path(root.join("Song.mp3.part"), &roots).is_err());
+        assert!(validated_library_partial_path(root.join("Song.mp3.part"), &roots).is_ok());
+        assert!(validated_library_partial_path(root.join("notes.txt"), &roots).is_err());
+        let _ = std::fs::remove_dir_all(root);
+    }
 }
@@ -1,7 +1,16 @@
+use crate::app::{MAX_RATING, TrackStats};
+use crate::library_filter::LibrarySort;
+use crate::player::RepeatMode;
+use crate::playlists::PlaylistFormat;
+use std::collections::HashMap;
+
 #[derive(Debug, Eq, PartialEq)]
 pub enum DownloadCommand {
     ShuffleQueue,
     ShuffleAll,
+    ShuffleFavorites,
+    /// Songs rated at least this many stars.
+    ShuffleRated(u8),
     Clear,
 }
 
@@ -10,16 +19,37 @@ impl DownloadCommand {
         match input.split_whitespace().collect::<Vec<_>>().as_slice() {
             [":shuffle", "queue"] => Ok(Self::ShuffleQueue),
             [":shuffle", "all"] => Ok(Self::ShuffleAll),
+            [":shuffle", "favorites" | "favourites"] => Ok(Self::ShuffleFavorites),
+            [":shuffle", "rated", stars] => match stars.parse() {
+                Ok(stars) if (1..=MAX_RATING).contains(&stars) => Ok(Self::ShuffleRated(stars)),
+                _ => Err(format!("Ratings run from 1 to {MAX_RATING} stars.")),
+            },
             [":clear"] => Ok(Self::Clear),
-            _ => Err("Commands: :shuffle queue · :shuffle all · :clear".to_string()),
+            _ => Err(
+                "Commands: :shuffle queue|all|favorites · :shuffle rated <n> · :shuffle on|off · :repeat off|track|queue · :clear · :favorite · :rate <n> · :sort · :smart … · :retag · :check · :duplicates · :scan · :playlist …"
+                    .to_string(),
+            ),
         }
     }
 
     pub fn execute(
         self,
         library: &[(String, String)],
+        stats: &HashMap<String, TrackStats>,
         queue: &mut Vec<(String, String)>,
     ) -> String {
+        let shuffle_matching = |queue: &mut Vec<(String, String)>,
+                                keep: &dyn Fn(&TrackStats) -> bool| {
+            let before = queue.len();
+            queue.extend(
+                library
+                    .iter()
+                    .filter(|(_, path)| stats.get(path).is_some_and(keep))
+                    .cloned(),
+            );
+            fastrand::shuffle(queue);
+            queue.len() - before
+        };
         match self {
             Self::ShuffleQueue => {
                 fastrand::shuffle(queue);
@@ -30,6 +60,14 @@ impl DownloadCommand {
                 fastrand::shuffle(queue);
                 format!("Added and shuffled {} library song(s).", library.len())
             }
+            Self::ShuffleFavorites => {
+                let added = shuffle_matching(queue, &|stats| stats.favorite);
+                format!("Added and shuffled {added} favorite song(s).")
+            }
+            Self::ShuffleRated(stars) => {
+                let added = shuffle_matching(queue, &|stats| stats.rating >= stars);
+                format!("Added and shuffled {added} song(s) rated {stars}★ or higher.")
+            }
             Self::Clear => {
                 let removed = queue.len();
                 queue.clear();
@@ -39,9 +77,267 @@ impl DownloadCommand {
     }
 }
 
+/// Marks on the selected library song.
+#[derive(Debug, Eq, PartialEq)]
+pub enum TrackCommand {
+    ToggleFavorite,
+    Rate(u8),
+}
+
+impl TrackCommand {
+    /// `None` when the input is not a favorite or rating command at all.
+    pub fn parse(input: &str) -> Option<Result<Self, String>> {
+        match input.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [":favorite" | ":favourite" | ":fav"] => Some(Ok(Self::ToggleFavorite)),
+            [":rate", stars] => Some(match stars.parse() {
+                Ok(stars) if stars <= MAX_RATING => Ok(Self::Rate(stars)),
+                _ => Err(format!("Use :rate 0-{MAX_RATING}; 0 clears the rating.")),
+            }),
+            [":rate", ..] => Some(Err(format!(
+                "Use :rate 0-{MAX_RATING}; 0 clears the rating."
+            ))),
+            _ => None,
+        }
+    }
+}
+
+/// Persistent playback modes, accepted in every view that has a command bar.
+#[derive(Debug, Eq, PartialEq)]
+pub enum PlaybackModeCommand {
+    /// `None` cycles off → queue → track.
+    Repeat(Option<RepeatMode>),
+    /// `None` toggles.
+    Shuffle(Option<bool>),
+}
+
+impl PlaybackModeCommand {
+    pub fn parse(input: &str) -> Option<Self> {
+        match input.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [":repeat"] => Some(Self::Repeat(None)),
+            [":repeat", mode] => RepeatMode::from_id(mode).map(|mode| Self::Repeat(Some(mode))),
+            [":shuffle"] => Some(Self::Shuffle(None)),
+            [":shuffle", "on"] => Some(Self::Shuffle(Some(true))),
+            [":shuffle", "off"] => Some(Self::Shuffle(Some(false))),
+            _ => None,
+        }
+    }
+}
+
+const PLAYLIST_USAGE: &str = "Playlists: :playlist new|add|play|queue|delete <name> · :playlist remove <name> <n> · :playlist export <name> [m3u8|xspf] · :playlist import <file> · :playlists";
+
+/// Named playlist management. Names may contain spaces, so positional
+/// arguments are taken from the end of the command.
+#[derive(Debug, Eq, PartialEq)]
+pub enum PlaylistCommand {
+    Browse,
+    New(String),
+    /// Adds the selected library song, or the current track while streaming.
+    Add(String),
+    Remove(String, usize),
+    Delete(String),
+    Play(String),
+    Queue(String),
+    Export(String, PlaylistFormat),
+    Import(String),
+}
+
+impl PlaylistCommand {
+    /// `None` when the input is not a playlist command at all.
+    pub fn parse(input: &str) -> Option<Result<Self, String>> {
+        let words = input.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            [":playlists"] => return Some(Ok(Self::Browse)),
+            [":playlist", ..] => {}
+            _ => return None,
+        }
+        let usage = || Err(PLAYLIST_USAGE.to_string());
+        let Some((action, rest)) = words[1..].split_first() else {
+            return Some(usage());
+        };
+        if rest.is_empty() {
+            return Some(usage());
+        }
+        let name = rest.join(" ");
+        Some(match *action {
+            "new" | "create" => Ok(Self::New(name)),
+            "add" => Ok(Self::Add(name)),
+            "delete" => Ok(Self::Delete(name)),
+            "play" => Ok(Self::Play(name)),
+            "queue" => Ok(Self::Queue(name)),
+            // File names may contain runs of spaces, so keep the raw text.
+            "import" => Ok(Self::Import(
+                input
+                    .split_once("import")
+                    .map_or(name, |(_, path)| path.trim().to_string()),
+            )),
+            "remove" => match rest.split_last() {
+                Some((position, name)) if !name.is_empty() => position
+                    .parse()
+                    .map(|position| Self::Remove(name.join(" "), position))
+                    .or_else(|_| usage()),
+                _ => usage(),
+            },
+            "export" => match rest.split_last() {
+                Some((format, base)) if !base.is_empty() => match PlaylistFormat::from_id(format) {
+                    Some(format) => Ok(Self::Export(base.join(" "), format)),
+                    None => Ok(Self::Export(name, PlaylistFormat::M3u8)),
+                },
+                _ => Ok(Self::Export(name, PlaylistFormat::M3u8)),
+            },
+            _ => usage(),
+        })
+    }
+}
+
+const SMART_USAGE: &str = "Smart playlists: :smart new <name> = <rule> · :smart play|queue|show|delete <name> · :smart show · :shuffle smart <name> · :smart";
+
+/// Smart playlist management. Rules keep their raw text; names may contain
+/// spaces.
+#[derive(Debug, Eq, PartialEq)]
+pub enum SmartCommand {
+    Browse,
+    New(String, String),
+    Delete(String),
+    Play(String),
+    Queue(String),
+    Shuffle(String),
+    /// Narrow Downloaded Music Only to the playlist, or `None` to show the
+    /// whole library again.
+    Show(Option<String>),
+}
+
+impl SmartCommand {
+    /// `None` when the input is not a smart playlist command at all.
+    pub fn parse(input: &str) -> Option<Result<Self, String>> {
+        let words = input.split_whitespace().collect::<Vec<_>>();
+        let (action, rest) = match words.as_slice() {
+            [":smart"] => return Some(Ok(Self::Browse)),
+            [":shuffle", "smart", name @ ..] if !name.is_empty() => {
+                return Some(Ok(Self::Shuffle(name.join(" "))));
+            }
+            [":smart", action, rest @ ..] => (*action, rest),
+            _ => return None,
+        };
+        let name = rest.join(" ");
+        Some(match (action, name.is_empty()) {
+            ("new" | "create", false) => input
+                .split_once("=")
+                .map(|(_, rule)| rule.trim())
+                .filter(|rule| !rule.is_empty())
+                .map(|rule| {
+                    let name = name.split_once('=').map_or(name.as_str(), |(name, _)| name);
+                    Self::New(name.trim().to_string(), rule.to_string())
+                })
+                .ok_or_else(|| SMART_USAGE.to_string()),
+            ("delete", false) => Ok(Self::Delete(name)),
+            ("play", false) => Ok(Self::Play(name)),
+            ("queue", false) => Ok(Self::Queue(name)),
+            ("show", _) => Ok(Self::Show(Some(name).filter(|name| !name.is_empty()))),
+            _ => Err(SMART_USAGE.to_string()),
+        })
+    }
+}
+
+/// `:sort <order>` for the Downloaded Music Only list.
+#[derive(Debug, Eq, PartialEq)]
+pub struct SortCommand(pub LibrarySort);
+
+impl SortCommand {
+    /// `None` when the input is not a sort command at all.
+    pub fn parse(input: &str) -> Option<Result<Self, String>> {
+        match input.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [":sort", order] => Some(
+                LibrarySort::from_id(order)
+                    .map(Self)
+                    .ok_or_else(|| SORT_USAGE.to_string()),
+            ),
+            [":sort", ..] => Some(Err(SORT_USAGE.to_string())),
+            _ => None,
+        }
+    }
+}
+
+const SORT_USAGE: &str = "Sort: :sort added|title|artist|plays|duration (Ctrl+t cycles)";
+
+const SCAN_USAGE: &str =
+    "Library folders: :scan · :scan add <folder> · :scan remove <folder> · :scan folders";
+
+/// Folder scanning for music collections outside the download folder.
+#[derive(Debug, Eq, PartialEq)]
+pub enum ScanCommand {
+    Rescan,
+    Folders,
+    AddFolder(String),
+    RemoveFolder(String),
+}
+
+impl ScanCommand {
+    /// `None` when the input is not a scan command at all.
+    pub fn parse(input: &str) -> Option<Result<Self, String>> {
+        let input = input.trim();
+        let rest = input.strip_prefix(":scan")?;
+        if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let rest = rest.trim_start();
+        let (action, folder) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        // Folder paths may contain runs of spaces, so keep the raw text.
+        let folder = folder.trim().to_string();
+        Some(match (action, folder.is_empty()) {
+            ("", _) => Ok(Self::Rescan),
+            ("folders", true) => Ok(Self::Folders),
+            ("add", false) => Ok(Self::AddFolder(folder)),
+            ("remove", false) => Ok(Self::RemoveFolder(folder)),
+            _ => Err(SCAN_USAGE.to_string()),
+        })
+    }
+}
+
+const ROOTS_USAGE: &str = "Library roots: :roots · :roots add <folder> · :roots remove <folder> · :roots download [<folder>]";
+
+/// Storage locations for downloads, such as external disks.
+#[derive(Debug, Eq, PartialEq)]
+pub enum RootCommand {
+    List,
+    Add(String),
+    Remove(String),
+    /// `None` downloads into the Music directory again.
+    Download(Option<String>),
+}
+
+impl RootCommand {
+    /// `None` when the input is not a roots command at all.
+    pub fn parse(input: &str) -> Option<Result<Self, String>> {
+        let input = input.trim();
+        let rest = input.strip_prefix(":roots")?;
+        if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let rest = rest.trim_start();
+        let (action, folder) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let folder = folder.trim().to_string();
+        Some(match (action, folder.is_empty()) {
+            ("", _) => Ok(Self::List),
+            ("add", false) => Ok(Self::Add(folder)),
+            ("remove", false) => Ok(Self::Remove(folder)),
+            ("download", true) => Ok(Self::Download(None)),
+            ("download", false) => Ok(Self::Download(Some(folder))),
+            _ => Err(ROOTS_USAGE.to_string()),
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::DownloadCommand;
+    use super::{
+        DownloadCommand, PlaybackModeCommand, PlaylistCommand, RootCommand, ScanCommand,
+        SmartCommand, SortCommand, TrackCommand,
+    };
+    use crate::app::TrackStats;
+    use crate::library_filter::LibrarySort;
+    use crate::player::RepeatMode;
+    use crate::playlists::PlaylistFormat;
+    use std::collections::HashMap;
 
     #[test]
     fn parses_supported_commands_with_flexible_spacing() {
@@ -59,6 +355,51 @@ mod tests {
         );
     }
 
+    #[test]
+    fn playback_mode_commands_leave_queue_commands_alone() {
+        assert_eq!(
+            PlaybackModeCommand::parse(":repeat one"),
+            Some(PlaybackModeCommand::Repeat(Some(RepeatMode::Track)))
+        );
+        assert_eq!(
+            PlaybackModeCommand::parse(" :shuffle "),
+            Some(PlaybackModeCommand::Shuffle(None))
+        );
+        assert_eq!(PlaybackModeCommand::parse(":shuffle queue"), None);
+        assert_eq!(PlaybackModeCommand::parse(":repeat forever"), None);
+    }
+
+    #[test]
+    fn playlist_names_keep_their_spaces() {
+        assert_eq!(
+            PlaylistCommand::parse(":playlist new Road Trip"),
+            Some(Ok(PlaylistCommand::New("Road Trip".to_string())))
+        );
+        assert_eq!(
+            PlaylistCommand::parse(":playlist remove Road Trip 3"),
+            Some(Ok(PlaylistCommand::Remove("Road Trip".to_string(), 3)))
+        );
+        assert_eq!(
+            PlaylistCommand::parse(":playlist export Road Trip xspf"),
+            Some(Ok(PlaylistCommand::Export(
+                "Road Trip".to_string(),
+                PlaylistFormat::Xspf
+            )))
+        );
+        assert_eq!(
+            PlaylistCommand::parse(":playlist export Mix"),
+            Some(Ok(PlaylistCommand::Export(
+                "Mix".to_string(),
+                PlaylistFormat::M3u8
+            )))
+        );
+        assert!(matches!(
+            PlaylistCommand::parse(":playlist remove Mix"),
+            Some(Err(_))
+        ));
+        assert_eq!(PlaylistCommand::parse(":shuffle queue"), None);
+    }
+
     #[test]
     fn shuffle_all_adds_every_library_track() {
         let library = vec![
@@ -66,7 +407,7 @@ mod tests {
             ("Two".to_string(), "two.mp3".to_string()),
         ];
         let mut queue = vec![("Existing".to_string(), "existing.mp3".to_string())];
-        DownloadCommand::ShuffleAll.execute(&library, &mut queue);
+        DownloadCommand::ShuffleAll.execute(&library, &HashMap::new(), &mut queue);
         queue.sort();
         let mut expected = library;
         expected.push(("Existing".to_string(), "existing.mp3".to_string()));
@@ -74,10 +415,114 @@ mod tests {
         assert_eq!(queue, expected);
     }
 
+    #[test]
+    fn shuffle_favorites_and_ratings_pick_matching_tracks() {
+        let library = vec![
+            ("Loved".to_string(), "loved.mp3".to_string()),
+            ("Good".to_string(), "good.mp3".to_string()),
+            ("Unrated".to_string(), "unrated.mp3".to_string()),
+        ];
+        let stats = HashMap::from([
+            (
+                "loved.mp3".to_string(),
+                TrackStats {
+                    favorite: true,
+                    rating: 5,
+                    ..TrackStats::default()
+                },
+            ),
+            (
+                "good.mp3".to_string(),
+                TrackStats {
+                    rating: 4,
+                    ..TrackStats::default()
+                },
+            ),
+        ]);
+        let mut queue = Vec::new();
+        DownloadCommand::parse(":shuffle favorites")
+            .unwrap()
+            .execute(&library, &stats, &mut queue);
+        assert_eq!(queue, [library[0].clone()]);
+        queue.clear();
+        DownloadCommand::parse(":shuffle rated 4")
+            .unwrap()
+            .execute(&library, &stats, &mut queue);
+        queue.sort();
+        assert_eq!(queue, [library[1].clone(), library[0].clone()]);
+        assert!(DownloadCommand::parse(":shuffle rated 6").is_err());
+        assert_eq!(
+            TrackCommand::parse(":rate 0"),
+            Some(Ok(TrackCommand::Rate(0)))
+        );
+        assert!(matches!(TrackCommand::parse(":rate 9"), Some(Err(_))));
+    }
+
     #[test]
     fn clear_empties_the_queue() {
         let mut queue = vec![("One".to_string(), "one.mp3".to_string())];
-        DownloadCommand::Clear.execute(&[], &mut queue);
+        DownloadCommand::Clear.execute(&[], &HashMap::new(), &mut queue);
         assert!(queue.is_empty());
     }
+
+    #[test]
+    fn scan_folders_keep_their_spaces() {
+        assert_eq!(ScanCommand::parse(" :scan "), Some(Ok(ScanCommand::Rescan)));
+        assert_eq!(
+            ScanCommand::parse(":scan add ~/My  Music"),
+            Some(Ok(ScanCommand::AddFolder("~/My  Music".to_string())))
+        );
+        assert!(matches!(ScanCommand::parse(":scan add"), Some(Err(_))));
+        assert_eq!(ScanCommand::parse(":scanner"), None);
+    }
+
+    #[test]
+    fn roots_download_without_a_folder_means_the_music_directory() {
+        assert_eq!(RootCommand::parse(":roots"), Some(Ok(RootCommand::List)));
+        assert_eq!(
+            RootCommand::parse(":roots download  /mnt/NAS  Music "),
+            Some(Ok(RootCommand::Download(Some(
+                "/mnt/NAS  Music".to_string()
+            ))))
+        );
+        assert_eq!(
+            RootCommand::parse(" :roots download "),
+            Some(Ok(RootCommand::Download(None)))
+        );
+        assert!(matches!(RootCommand::parse(":roots remove"), Some(Err(_))));
+        assert_eq!(RootCommand::parse(":rootsy"), None);
+    }
+
+    #[test]
+    fn sort_accepts_known_orders_only() {
+        assert_eq!(
+            SortCommand::parse(":sort  artist"),
+            Some(Ok(SortCommand(LibrarySort::Artist)))
+        );
+        assert!(matches!(SortCommand::parse(":sort"), Some(Err(_))));
+    }
+
+    #[test]
+    fn smart_rules_keep_their_raw_text() {
+        assert_eq!(
+            SmartCommand::parse(":smart new Short Keepers = rating >= 4 and duration < 5m"),
+            Some(Ok(SmartCommand::New(
+                "Short Keepers".to_string(),
+                "rating >= 4 and duration < 5m".to_string()
+            )))
+        );
+        assert_eq!(
+            SmartCommand::parse(":shuffle smart Short Keepers"),
+            Some(Ok(SmartCommand::Shuffle("Short Keepers".to_string())))
+        );
+        assert_eq!(
+            SmartCommand::parse(":smart show"),
+            Some(Ok(SmartCommand::Show(None)))
+        );
+        assert!(matches!(
+            SmartCommand::parse(":smart new Empty"),
+            Some(Err(_))
+        ));
+        assert_eq!(SmartCommand::parse(":shuffle favorites"), None);
+    }
 }
@@ -0,0 +1,202 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Every extension a library download can be published with. Temporary
+/// audio is written beside the final file as `<name>.<extension>.part`.
+pub const AUDIO_EXTENSIONS: [&str; 5] = ["mp3", "opus", "m4a", "ogg", "flac"];
+
+/// The audio file a permanent download publishes.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum DownloadFormat {
+    /// YouTube's audio stream copied without re-encoding.
+    #[serde(rename = "original")]
+    Original,
+    #[serde(rename = "mp3-v0")]
+    Mp3V0,
+    #[default]
+    #[serde(rename = "mp3-v2")]
+    Mp3V2,
+    #[serde(rename = "mp3-320")]
+    Mp3Cbr320,
+    #[serde(rename = "mp3-192")]
+    Mp3Cbr192,
+    #[serde(rename = "opus-160")]
+    Opus160,
+    #[serde(rename = "opus-128")]
+    Opus128,
+    #[serde(rename = "opus-96")]
+    Opus96,
+    #[serde(rename = "flac")]
+    Flac,
+}
+
+/// How ffmpeg writes the audio of one download.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AudioEncoding {
+    pub extension: &'static str,
+    /// The ffmpeg muxer passed to `-f`.
+    pub muxer: &'static str,
+    pub codec_args: &'static [&'static str],
+}
+
+impl AudioEncoding {
+    const COPY: &'static [&'static str] = &["-c:a", "copy"];
+
+    /// The stream copy that rewrites a published download, by its extension.
+    pub fn of_file(path: &Path) -> Option<Self> {
+        let (extension, muxer) = match path.extension()?.to_str()? {
+            "mp3" => ("mp3", "mp3"),
+            "opus" => ("opus", "opus"),
+            "m4a" => ("m4a", "ipod"),
+            "ogg" => ("ogg", "ogg"),
+            "flac" => ("flac", "flac"),
+            _ => return None,
+        };
+        Some(Self {
+            extension,
+            muxer,
+            codec_args: Self::COPY,
+        })
+    }
+
+    /// Ogg cannot carry an attached picture, so Opus files go without one.
+    pub fn supports_cover_art(self) -> bool {
+        !matches!(self.muxer, "opus" | "ogg")
+    }
+
+    pub fn is_mp3(self) -> bool {
+        self.muxer == "mp3"
+    }
+}
+
+impl DownloadFormat {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Original => Self::Mp3V0,
+            Self::Mp3V0 => Self::Mp3V2,
+            Self::Mp3V2 => Self::Mp3Cbr320,
+            Self::Mp3Cbr320 => Self::Mp3Cbr192,
+            Self::Mp3Cbr192 => Self::Opus160,
+            Self::Opus160 => Self::Opus128,
+            Self::Opus128 => Self::Opus96,
+            Self::Opus96 => Self::Flac,
+            Self::Flac => Self::Original,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Original => "Download Format: ORIGINAL (no re-encode)",
+            Self::Mp3V0 => "Download Format: MP3 VBR V0",
+            Self::Mp3V2 => "Download Format: MP3 VBR V2",
+            Self::Mp3Cbr320 => "Download Format: MP3 CBR 320 kbps",
+            Self::Mp3Cbr192 => "Download Format: MP3 CBR 192 kbps",
+            Self::Opus160 => "Download Format: OPUS 160 kbps",
+            Self::Opus128 => "Download Format: OPUS 128 kbps",
+            Self::Opus96 => "Download Format: OPUS 96 kbps",
+            Self::Flac => "Download Format: FLAC",
+        }
+    }
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::Original => "original",
+            Self::Mp3V0 => "mp3-v0",
+            Self::Mp3V2 => "mp3-v2",
+            Self::Mp3Cbr320 => "mp3-320",
+            Self::Mp3Cbr192 => "mp3-192",
+            Self::Opus160 => "opus-160",
+            Self::Opus128 => "opus-128",
+            Self::Opus96 => "opus-96",
+            Self::Flac => "flac",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "original" => Some(Self::Original),
+            "mp3-v0" => Some(Self::Mp3V0),
+            "mp3-v2" => Some(Self::Mp3V2),
+            "mp3-320" => Some(Self::Mp3Cbr320),
+            "mp3-192" => Some(Self::Mp3Cbr192),
+            "opus-160" => Some(Self::Opus160),
+            "opus-128" => Some(Self::Opus128),
+            "opus-96" => Some(Self::Opus96),
+            "flac" => Some(Self::Flac),
+            _ => None,
+        }
+    }
+
+    /// The extension a queued download is named with. YouTube's best audio
+    /// is almost always Opus, so the original stream is expected to be one;
+    /// [`Self::encoding`] has the final say once the source is known.
+    pub fn planned_extension(self) -> &'static str {
+        self.encoding(Some("opus")).extension
+    }
+
+    /// The encoding for a source whose first audio stream has the ffprobe
+    /// codec name `source_codec`. Original streams that no audio-only
+    /// container here carries are kept losslessly as FLAC instead.
+    pub fn encoding(self, source_codec: Option<&str>) -> AudioEncoding {
+        let encoding = |extension, muxer, codec_args| AudioEncoding {
+            extension,
+            muxer,
+            codec_args,
+        };
+        match self {
+            Self::Original => match source_codec {
+                Some("opus") => encoding("opus", "opus", AudioEncoding::COPY),
+                Some("aac") => encoding("m4a", "ipod", AudioEncoding::COPY),
+                Some("mp3") => encoding("mp3", "mp3", AudioEncoding::COPY),
+                Some("vorbis") => encoding("ogg", "ogg", AudioEncoding::COPY),
+                _ => Self::Flac.encoding(None),
+            },
+            Self::Mp3V0 => encoding("mp3", "mp3", &["-c:a", "libmp3lame", "-q:a", "0"]),
+            Self::Mp3V2 => encoding("mp3", "mp3", &["-c:a", "libmp3lame", "-q:a", "2"]),
+            Self::Mp3Cbr320 => encoding("mp3", "mp3", &["-c:a", "libmp3lame", "-b:a", "320k"]),
+            Self::Mp3Cbr192 => encoding("mp3", "mp3", &["-c:a", "libmp3lame", "-b:a", "192k"]),
+            Self::Opus160 => encoding("opus", "opus", &["-c:a", "libopus", "-b:a", "160k"]),
+            Self::Opus128 => encoding("opus", "opus", &["-c:a", "libopus", "-b:a", "128k"]),
+            Self::Opus96 => encoding("opus", "opus", &["-c:a", "libopus", "-b:a", "96k"]),
+            Self::Flac => encoding("flac", "flac", &["-c:a", "flac"]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AUDIO_EXTENSIONS, AudioEncoding, DownloadFormat};
+    use std::path::Path;
+
+    #[test]
+    fn every_format_round_trips_through_its_id() {
+        let mut format = DownloadFormat::Original;
+        for _ in 0..9 {
+            assert_eq!(DownloadFormat::from_id(format.id()), Some(format));
+            assert!(AUDIO_EXTENSIONS.contains(&format.planned_extension()));
+            format = format.next();
+        }
+        assert_eq!(format, DownloadFormat::Original);
+        assert_eq!(DownloadFormat::from_id("wav"), None);
+        assert_eq!(DownloadFormat::default().planned_extension(), "mp3");
+    }
+
+    #[test]
+    fn original_streams_are_remuxed_into_a_matching_container() {
+        let original = DownloadFormat::Original;
+        let opus = original.encoding(Some("opus"));
+        assert_eq!((opus.extension, opus.muxer), ("opus", "opus"));
+        assert_eq!(opus.codec_args, ["-c:a", "copy"]);
+        assert!(!opus.supports_cover_art());
+        let aac = original.encoding(Some("aac"));
+        assert_eq!((aac.extension, aac.muxer), ("m4a", "ipod"));
+        assert!(aac.supports_cover_art() && !aac.is_mp3());
+        assert_eq!(original.encoding(Some("pcm_s16le")).extension, "flac");
+        assert_eq!(original.encoding(None).extension, "flac");
+        for extension in AUDIO_EXTENSIONS {
+            let path = Path::new("Song").with_extension(extension);
+            assert_eq!(AudioEncoding::of_file(&path).unwrap().extension, extension);
+        }
+        assert_eq!(AudioEncoding::of_file(Path::new("Song.wav")), None);
+    }
+}
@@ -1,14 +1,27 @@
-use crate::search::download_audio;
+use crate::download_format::DownloadFormat;
+use crate::download_progress::{DownloadProgress, DownloadStage};
+use crate::loudness::TrackLoudness;
+use crate::search::{DownloadedAudio, encode_download, fetch_download, remove_partial_download};
+use crate::track_metadata::TrackMetadata;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::panic::AssertUnwindSafe;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
 
+#[derive(Clone)]
 pub struct DownloadRequest {
     pub id: String,
     pub title: String,
     pub url: String,
+    /// Where the download is planned to be published. The extension follows
+    /// `format`; a remuxed original stream may end up with another one.
     pub path: String,
+    /// The download is refused unless `path` is directly inside one of these.
+    pub library_roots: Box<[PathBuf]>,
+    pub format: DownloadFormat,
     pub video_cache_plan: Option<(u16, u16, u16)>,
 }
 
@@ -16,43 +29,349 @@ pub enum DownloadEvent {
     Started {
         id: String,
     },
+    Progress {
+        id: String,
+        progress: DownloadProgress,
+    },
+    /// A running job was stopped by [`DownloadManager::pause`] and is back in
+    /// the queue.
+    Paused {
+        id: String,
+    },
     Finished {
         id: String,
         title: String,
         path: String,
+        loudness: Option<TrackLoudness>,
+        metadata: Option<Box<TrackMetadata>>,
         error: Option<String>,
     },
 }
 
+/// Upper bound for the worker count of either download stage.
+pub const MAX_STAGE_WORKERS: usize = 4;
+
+/// How many permanent downloads may fetch from YouTube at once, and how many
+/// may run the CPU-heavy audio and `.crestvid` encode at once.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DownloadLimits {
+    pub fetch: usize,
+    pub encode: usize,
+}
+
+impl DownloadLimits {
+    pub fn new(fetch: usize, encode: usize) -> Self {
+        Self {
+            fetch: fetch.clamp(1, MAX_STAGE_WORKERS),
+            encode: encode.clamp(1, MAX_STAGE_WORKERS),
+        }
+    }
+
+    /// A job waiting for an encode slot keeps its worker, so this many workers
+    /// keep both stages busy.
+    fn workers(self) -> usize {
+        self.fetch + self.encode
+    }
+}
+
 pub struct DownloadManager {
-    requests: Option<Sender<DownloadRequest>>,
     events: Receiver<DownloadEvent>,
-    cancelled: Arc<AtomicBool>,
-    worker: Option<std::thread::JoinHandle<()>>,
+    event_sender: Sender<DownloadEvent>,
+    shared: Arc<Shared>,
+    workers: Vec<std::thread::JoinHandle<()>>,
+}
+
+/// State every worker thread reads from.
+struct Shared {
+    queue: Mutex<Queue>,
+    /// Signalled when a job is queued or resumed, a fetch slot frees up, or
+    /// the manager shuts down.
+    queue_changed: Condvar,
+    encode: StageSlots,
+}
+
+/// Jobs waiting to start, in the order they will start, plus the controls of
+/// the jobs workers are running. Waiting for a free fetch slot here, rather
+/// than after a worker takes a job, keeps waiting jobs reorderable.
+struct Queue {
+    waiting: VecDeque<QueuedJob>,
+    running: HashMap<String, RunningJob>,
+    fetching: usize,
+    fetch_limit: usize,
+    next_ticket: i64,
+    /// Tickets for jobs moved to the front count down from here.
+    front_ticket: i64,
+    closed: bool,
+}
+
+struct QueuedJob {
+    request: DownloadRequest,
+    /// Orders the job against others at the encode stage.
+    ticket: i64,
+    paused: bool,
+}
+
+/// A job a worker has taken, remembered so it can be stopped and saved.
+struct RunningJob {
+    control: Arc<JobControl>,
+    request: DownloadRequest,
+    ticket: i64,
+}
+
+/// Lets the UI stop one running job.
+#[derive(Default)]
+struct JobControl {
+    /// Kills the job's processes; every command it runs polls this.
+    stop: AtomicBool,
+    /// Set along with `stop` when the job should wait in the queue, paused.
+    pause: AtomicBool,
+}
+
+impl Queue {
+    fn new(fetch_limit: usize) -> Self {
+        Self {
+            waiting: VecDeque::new(),
+            running: HashMap::new(),
+            fetching: 0,
+            fetch_limit,
+            next_ticket: 0,
+            front_ticket: -1,
+            closed: false,
+        }
+    }
+
+    fn push(&mut self, request: DownloadRequest) {
+        self.waiting.push_back(QueuedJob {
+            request,
+            ticket: self.next_ticket,
+            paused: false,
+        });
+        self.next_ticket += 1;
+    }
+
+    fn position(&self, id: &str) -> Option<usize> {
+        self.waiting.iter().position(|job| job.request.id == id)
+    }
+
+    /// The first job that is not paused, if a fetch slot is free.
+    fn take_next(&mut self) -> Option<QueuedJob> {
+        if self.fetching >= self.fetch_limit {
+            return None;
+        }
+        let index = self.waiting.iter().position(|job| !job.paused)?;
+        self.fetching += 1;
+        self.waiting.remove(index)
+    }
+
+    fn move_to_front(&mut self, id: &str) -> bool {
+        let Some(mut job) = self
+            .position(id)
+            .and_then(|index| self.waiting.remove(index))
+        else {
+            return false;
+        };
+        job.ticket = self.front_ticket;
+        self.front_ticket -= 1;
+        self.waiting.push_front(job);
+        true
+    }
+
+    /// Put a stopped job back at its original place in line.
+    fn requeue(&mut self, job: QueuedJob) {
+        let index = self
+            .waiting
+            .iter()
+            .position(|waiting| waiting.ticket > job.ticket)
+            .unwrap_or(self.waiting.len());
+        self.waiting.insert(index, job);
+    }
+}
+
+impl Shared {
+    fn lock_queue(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Wait for the next job a worker should start, holding a fetch slot for
+    /// it. `None` once the manager shuts down.
+    fn next_job(&self) -> Option<(QueuedJob, Arc<JobControl>, FetchSlot<'_>)> {
+        let mut queue = self.lock_queue();
+        loop {
+            if queue.closed {
+                return None;
+            }
+            if let Some(job) = queue.take_next() {
+                let control = Arc::new(JobControl::default());
+                queue.running.insert(
+                    job.request.id.clone(),
+                    RunningJob {
+                        control: Arc::clone(&control),
+                        request: job.request.clone(),
+                        ticket: job.ticket,
+                    },
+                );
+                return Some((job, control, FetchSlot(self)));
+            }
+            queue = self
+                .queue_changed
+                .wait(queue)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// A fetch slot for a retry, or `None` once the job is stopped.
+    fn acquire_fetch(&self, stop: &AtomicBool) -> Option<FetchSlot<'_>> {
+        let mut queue = self.lock_queue();
+        loop {
+            if stop.load(Ordering::Acquire) {
+                return None;
+            }
+            if queue.fetching < queue.fetch_limit {
+                queue.fetching += 1;
+                return Some(FetchSlot(self));
+            }
+            queue = self
+                .queue_changed
+                .wait_timeout(queue, Duration::from_millis(100))
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+    }
+}
+
+/// Releases its fetch slot when dropped, including while unwinding.
+struct FetchSlot<'a>(&'a Shared);
+
+impl Drop for FetchSlot<'_> {
+    fn drop(&mut self) {
+        self.0.lock_queue().fetching -= 1;
+        self.0.queue_changed.notify_all();
+    }
 }
 
 impl DownloadManager {
-    pub fn new() -> Self {
-        let (request_tx, request_rx) = mpsc::channel::<DownloadRequest>();
+    pub fn new(limits: DownloadLimits) -> Self {
         let (event_tx, event_rx) = mpsc::channel::<DownloadEvent>();
-        let cancelled = Arc::new(AtomicBool::new(false));
-        let worker_cancelled = Arc::clone(&cancelled);
-        let worker =
-            std::thread::spawn(move || run_worker(request_rx, event_tx, worker_cancelled.as_ref()));
-        Self {
-            requests: Some(request_tx),
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue::new(limits.fetch)),
+            queue_changed: Condvar::new(),
+            encode: StageSlots::new(limits.encode),
+        });
+        let mut manager = Self {
             events: event_rx,
-            cancelled,
-            worker: Some(worker),
+            event_sender: event_tx,
+            shared,
+            workers: Vec::new(),
+        };
+        manager.spawn_workers(limits.workers());
+        manager
+    }
+
+    /// Apply new stage limits to queued and future jobs. Jobs already inside
+    /// a stage finish there.
+    pub fn set_limits(&mut self, limits: DownloadLimits) {
+        self.shared.lock_queue().fetch_limit = limits.fetch;
+        self.shared.queue_changed.notify_all();
+        self.shared.encode.set_limit(limits.encode);
+        self.spawn_workers(limits.workers());
+    }
+
+    fn spawn_workers(&mut self, count: usize) {
+        while self.workers.len() < count {
+            let shared = Arc::clone(&self.shared);
+            let events = self.event_sender.clone();
+            self.workers
+                .push(std::thread::spawn(move || run_worker(&shared, &events)));
+        }
+    }
+
+    pub fn enqueue(&self, request: DownloadRequest) -> Result<(), Box<DownloadRequest>> {
+        let mut queue = self.shared.lock_queue();
+        if queue.closed {
+            return Err(Box::new(request));
         }
+        queue.push(request);
+        self.shared.queue_changed.notify_all();
+        Ok(())
     }
 
-    pub fn enqueue(&self, request: DownloadRequest) -> Result<(), DownloadRequest> {
-        self.requests
-            .as_ref()
-            .expect("download manager sender is available before drop")
-            .send(request)
-            .map_err(|error| error.0)
+    /// Stop a job. A waiting job finishes at once; a running one finishes
+    /// with an error once its processes are killed. Either way its partial
+    /// files are removed. Returns whether the job was found.
+    pub fn cancel(&self, id: &str) -> bool {
+        let mut queue = self.shared.lock_queue();
+        if let Some(job) = queue
+            .position(id)
+            .and_then(|index| queue.waiting.remove(index))
+        {
+            drop(queue);
+            // A paused or restored job keeps the source it fetched so far.
+            remove_partial_download(Path::new(&job.request.path), &job.request.library_roots);
+            let _ = self.event_sender.send(DownloadEvent::Finished {
+                id: job.request.id,
+                title: job.request.title,
+                path: job.request.path,
+                loudness: None,
+                metadata: None,
+                error: Some("download cancelled".to_string()),
+            });
+            return true;
+        }
+        queue.running.get(id).is_some_and(|job| {
+            job.control.stop.store(true, Ordering::Release);
+            true
+        })
+    }
+
+    /// Hold a job in the queue. A running job is stopped; its partial source
+    /// is kept so it continues when resumed. Returns whether the job was found.
+    pub fn pause(&self, id: &str) -> bool {
+        let mut queue = self.shared.lock_queue();
+        if let Some(index) = queue.position(id) {
+            queue.waiting[index].paused = true;
+            return true;
+        }
+        queue.running.get(id).is_some_and(|job| {
+            job.control.pause.store(true, Ordering::Release);
+            job.control.stop.store(true, Ordering::Release);
+            true
+        })
+    }
+
+    pub fn resume(&self, id: &str) -> bool {
+        let mut queue = self.shared.lock_queue();
+        let Some(index) = queue.position(id) else {
+            return false;
+        };
+        queue.waiting[index].paused = false;
+        self.shared.queue_changed.notify_all();
+        true
+    }
+
+    /// Start a waiting job before every other waiting job.
+    pub fn move_to_front(&self, id: &str) -> bool {
+        self.shared.lock_queue().move_to_front(id)
+    }
+
+    /// Every unfinished job in start order, running jobs first, with whether
+    /// it is paused.
+    pub fn pending(&self) -> Vec<(DownloadRequest, bool)> {
+        let queue = self.shared.lock_queue();
+        let mut running: Vec<_> = queue.running.values().collect();
+        running.sort_by_key(|job| job.ticket);
+        running
+            .into_iter()
+            .map(|job| {
+                let paused = job.control.pause.load(Ordering::Acquire);
+                (job.request.clone(), paused)
+            })
+            .chain(
+                queue
+                    .waiting
+                    .iter()
+                    .map(|job| (job.request.clone(), job.paused)),
+            )
+            .collect()
     }
 
     pub fn try_recv(&self) -> Result<DownloadEvent, mpsc::TryRecvError> {
@@ -62,41 +381,141 @@ impl DownloadManager {
 
 impl Drop for DownloadManager {
     fn drop(&mut self) {
-        self.cancelled.store(true, Ordering::Release);
-        self.requests.take();
-        if let Some(worker) = self.worker.take() {
+        {
+            let mut queue = self.shared.lock_queue();
+            queue.closed = true;
+            for job in queue.running.values() {
+                job.control.stop.store(true, Ordering::Release);
+            }
+        }
+        self.shared.queue_changed.notify_all();
+        for worker in self.workers.drain(..) {
             let _ = worker.join();
         }
     }
 }
 
-fn run_worker(
-    requests: Receiver<DownloadRequest>,
-    events: Sender<DownloadEvent>,
-    cancelled: &AtomicBool,
-) {
-    while let Ok(request) = requests.recv() {
-        if cancelled.load(Ordering::Acquire) {
-            return;
+/// A counting semaphore for one download stage. Free slots go to the waiting
+/// job that was queued first, so a job that reaches the encode stage early
+/// cannot overtake an older one, and the panel's order stays the work order.
+struct StageSlots {
+    state: Mutex<SlotState>,
+    changed: Condvar,
+}
+
+struct SlotState {
+    limit: usize,
+    busy: usize,
+    waiting: BTreeSet<i64>,
+}
+
+/// Releases its stage slot when dropped, including while unwinding.
+struct StageSlot<'a>(&'a StageSlots);
+
+impl StageSlots {
+    fn new(limit: usize) -> Self {
+        Self {
+            state: Mutex::new(SlotState {
+                limit,
+                busy: 0,
+                waiting: BTreeSet::new(),
+            }),
+            changed: Condvar::new(),
         }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SlotState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn set_limit(&self, limit: usize) {
+        self.lock().limit = limit;
+        self.changed.notify_all();
+    }
+
+    /// Wait for a slot, or give up with `None` once `cancelled` is set.
+    fn acquire(&self, ticket: i64, cancelled: &AtomicBool) -> Option<StageSlot<'_>> {
+        let mut state = self.lock();
+        state.waiting.insert(ticket);
+        loop {
+            if cancelled.load(Ordering::Acquire) {
+                state.waiting.remove(&ticket);
+                self.changed.notify_all();
+                return None;
+            }
+            if state.busy < state.limit && state.waiting.first() == Some(&ticket) {
+                state.waiting.remove(&ticket);
+                state.busy += 1;
+                self.changed.notify_all();
+                return Some(StageSlot(self));
+            }
+            // The timeout only bounds how long cancellation goes unnoticed.
+            state = self
+                .changed
+                .wait_timeout(state, Duration::from_millis(100))
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+    }
+}
+
+impl Drop for StageSlot<'_> {
+    fn drop(&mut self) {
+        self.0.lock().busy -= 1;
+        self.0.changed.notify_all();
+    }
+}
+
+fn run_worker(shared: &Shared, events: &Sender<DownloadEvent>) {
+    while let Some((job, control, fetch_slot)) = shared.next_job() {
         if events
             .send(DownloadEvent::Started {
-                id: request.id.clone(),
+                id: job.request.id.clone(),
             })
             .is_err()
         {
             return;
         }
-        let result = retry_download(&request, 3, cancelled);
-        let (path, error) = match result {
-            Ok(path) => (path.to_string_lossy().into_owned(), None),
-            Err(error) => (request.path, Some(error)),
+        let result = retry_download(&job, 3, shared, &control, fetch_slot, events);
+        let mut queue = shared.lock_queue();
+        queue.running.remove(&job.request.id);
+        // Partial files are kept at shutdown so the next launch can resume.
+        if result.is_err() && queue.closed {
+            return;
+        }
+        if result.is_err() && control.pause.load(Ordering::Acquire) {
+            let id = job.request.id.clone();
+            queue.requeue(QueuedJob {
+                paused: true,
+                ..job
+            });
+            drop(queue);
+            if events.send(DownloadEvent::Paused { id }).is_err() {
+                return;
+            }
+            continue;
+        }
+        drop(queue);
+        if result.is_err() {
+            remove_partial_download(Path::new(&job.request.path), &job.request.library_roots);
+        }
+        let request = job.request;
+        let (path, loudness, metadata, error) = match result {
+            Ok(download) => (
+                download.path.to_string_lossy().into_owned(),
+                download.loudness,
+                Some(Box::new(download.metadata)),
+                None,
+            ),
+            Err(error) => (request.path, None, None, Some(error)),
         };
         if events
             .send(DownloadEvent::Finished {
                 id: request.id,
                 title: request.title,
                 path,
+                loudness,
+                metadata,
                 error,
             })
             .is_err()
@@ -107,29 +526,248 @@ fn run_worker(
 }
 
 fn retry_download(
-    request: &DownloadRequest,
+    job: &QueuedJob,
     attempts: usize,
-    cancelled: &AtomicBool,
-) -> Result<PathBuf, String> {
+    shared: &Shared,
+    control: &JobControl,
+    fetch_slot: FetchSlot<'_>,
+    events: &Sender<DownloadEvent>,
+) -> Result<DownloadedAudio, String> {
+    let request = &job.request;
+    let stop = &control.stop;
+    let mut fetch_slot = Some(fetch_slot);
+    let mut progress = ProgressReporter {
+        id: &request.id,
+        events,
+        last: None,
+    };
     let mut errors = Vec::new();
     for attempt in 1..=attempts.max(1) {
-        if cancelled.load(Ordering::Acquire) {
-            return Err("download cancelled".to_string());
-        }
-        let result = std::panic::catch_unwind(|| {
-            download_audio(
-                &request.url,
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            let fetched = {
+                let _slot = match fetch_slot.take() {
+                    Some(slot) => slot,
+                    None => shared
+                        .acquire_fetch(stop)
+                        .ok_or_else(|| "download cancelled".to_string())?,
+                };
+                fetch_download(
+                    &request.url,
+                    &request.title,
+                    Path::new(&request.path),
+                    &request.library_roots,
+                    stop,
+                    &mut |report| progress.send(report),
+                )?
+            };
+            let _slot = shared
+                .encode
+                .acquire(job.ticket, stop)
+                .ok_or_else(|| "download cancelled".to_string())?;
+            encode_download(
+                &fetched,
                 &request.title,
-                Path::new(&request.path),
+                request.format,
                 request.video_cache_plan,
-                cancelled,
+                stop,
+                &mut |report| progress.send(report),
             )
-        })
+        }))
         .unwrap_or_else(|_| Err("the download process stopped unexpectedly".to_string()));
         match result {
-            Ok(path) => return Ok(path),
-            Err(error) => errors.push(format!("attempt {attempt}: {error}")),
+            Ok(download) => return Ok(download),
+            Err(_) if stop.load(Ordering::Acquire) => {
+                return Err("download cancelled".to_string());
+            }
+            Err(error) => {
+                errors.push(format!("attempt {attempt}: {error}"));
+                // A damaged partial source must not poison the next attempt.
+                remove_partial_download(Path::new(&request.path), &request.library_roots);
+            }
         }
     }
     Err(errors.join("; "))
 }
+
+/// Forwards a job's progress to the UI. yt-dlp reports every chunk, so
+/// updates within a stage are limited to a few per second.
+struct ProgressReporter<'a> {
+    id: &'a str,
+    events: &'a Sender<DownloadEvent>,
+    last: Option<(DownloadStage, Instant)>,
+}
+
+impl ProgressReporter<'_> {
+    const INTERVAL: Duration = Duration::from_millis(250);
+
+    fn send(&mut self, progress: DownloadProgress) {
+        let due = match self.last {
+            Some((stage, sent)) => {
+                stage != progress.stage
+                    || progress.fraction == Some(1.0)
+                    || sent.elapsed() >= Self::INTERVAL
+            }
+            None => true,
+        };
+        if due {
+            self.last = Some((progress.stage, Instant::now()));
+            let _ = self.events.send(DownloadEvent::Progress {
+                id: self.id.to_string(),
+                progress,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        DownloadEvent, DownloadLimits, DownloadManager, DownloadRequest, MAX_STAGE_WORKERS, Queue,
+        QueuedJob, Shared, StageSlots,
+    };
+    use crate::download_format::DownloadFormat;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::mpsc;
+    use std::sync::{Arc, Condvar, Mutex};
+    use std::time::Duration;
+
+    #[test]
+    fn stage_slots_go_to_the_oldest_waiting_job() {
+        let slots = Arc::new(StageSlots::new(1));
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let first = slots.acquire(1, &cancelled).unwrap();
+        let waiters: Vec<_> = [4, 2, 3]
+            .into_iter()
+            .map(|ticket| {
+                let (slots, order, cancelled) = (
+                    Arc::clone(&slots),
+                    Arc::clone(&order),
+                    Arc::clone(&cancelled),
+                );
+                std::thread::spawn(move || {
+                    let _slot = slots.acquire(ticket, &cancelled).unwrap();
+                    order.lock().unwrap().push(ticket);
+                    std::thread::sleep(Duration::from_millis(5));
+                })
+            })
+            .collect();
+        while slots.lock().waiting.len() < 3 {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        drop(first);
+        for waiter in waiters {
+            waiter.join().unwrap();
+        }
+        assert_eq!(*order.lock().unwrap(), vec![2, 3, 4]);
+        assert_eq!(slots.lock().busy, 0);
+    }
+
+    #[test]
+    fn limits_stay_within_the_worker_bounds_and_cancel_waiters() {
+        assert_eq!(
+            DownloadLimits::new(0, 9),
+            DownloadLimits::new(1, MAX_STAGE_WORKERS)
+        );
+        let slots = StageSlots::new(2);
+        let cancelled = AtomicBool::new(false);
+        let _a = slots.acquire(1, &cancelled).unwrap();
+        let _b = slots.acquire(2, &cancelled).unwrap();
+        cancelled.store(true, std::sync::atomic::Ordering::Release);
+        assert!(slots.acquire(3, &cancelled).is_none());
+        assert!(slots.lock().waiting.is_empty());
+    }
+
+    fn request(id: &str) -> DownloadRequest {
+        DownloadRequest {
+            id: id.to_string(),
+            title: id.to_string(),
+            url: String::new(),
+            path: id.to_string(),
+            library_roots: Box::new([]),
+            format: DownloadFormat::default(),
+            video_cache_plan: None,
+        }
+    }
+
+    #[test]
+    fn waiting_jobs_can_be_paused_and_moved_to_the_front() {
+        let mut queue = Queue::new(2);
+        for id in ["a", "b", "c", "d"] {
+            queue.push(request(id));
+        }
+        assert!(queue.move_to_front("c"));
+        assert!(!queue.move_to_front("missing"));
+        queue.waiting[1].paused = true;
+
+        let first = queue.take_next().unwrap();
+        let second = queue.take_next().unwrap();
+        assert_eq!(
+            (first.request.id.as_str(), second.request.id.as_str()),
+            ("c", "b")
+        );
+        assert!(first.ticket < second.ticket);
+        // Both fetch slots are busy.
+        assert!(queue.take_next().is_none());
+
+        queue.fetching = 0;
+        queue.requeue(QueuedJob {
+            paused: true,
+            ..second
+        });
+        let ids: Vec<_> = queue
+            .waiting
+            .iter()
+            .map(|job| job.request.id.as_str())
+            .collect();
+        assert_eq!(ids, ["a", "b", "d"]);
+        assert_eq!(queue.take_next().unwrap().request.id, "d");
+    }
+
+    #[test]
+    fn cancelling_a_paused_job_removes_its_partial_source() {
+        let root =
+            std::env::temp_dir().join(format!("crest-player-cancel-paused-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let root = root.canonicalize().unwrap();
+        let partials = [
+            "Song.download.mkv",
+            "Song.download.f251.webm.part",
+            "Song.mp3.part",
+        ];
+        for name in partials {
+            std::fs::write(root.join(name), b"partial").unwrap();
+        }
+        // Without workers the job stays queued while it is paused.
+        let (event_sender, events) = mpsc::channel();
+        let manager = DownloadManager {
+            events,
+            event_sender,
+            shared: Arc::new(Shared {
+                queue: Mutex::new(Queue::new(1)),
+                queue_changed: Condvar::new(),
+                encode: StageSlots::new(1),
+            }),
+            workers: Vec::new(),
+        };
+        let song = root.join("Song.mp3").to_string_lossy().into_owned();
+        manager
+            .enqueue(DownloadRequest {
+                path: song.clone(),
+                library_roots: Box::new([root.clone()]),
+                ..request("song")
+            })
+            .unwrap_or_else(|_| panic!("the queue is open"));
+        assert!(manager.pause("song"));
+        assert!(manager.cancel("song"));
+
+        for name in partials {
+            assert!(!root.join(name).exists(), "{name} was left behind");
+        }
+        assert!(matches!(
+            manager.events.try_recv(),
+            Ok(DownloadEvent::Finished { path, error: Some(_), .. }) if path == song
+        ));
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,199 @@
+use crate::storage::format_bytes;
+use std::time::Duration;
+
+/// yt-dlp `--progress-template` producing lines [`parse_yt_dlp_line`] reads.
+/// Missing fields are printed as `NA`.
+pub const YT_DLP_PROGRESS_TEMPLATE: &str = "download:crest-progress %(progress.downloaded_bytes)s %(progress.total_bytes,progress.total_bytes_estimate)s %(progress.speed)s %(progress.eta)s";
+
+const YT_DLP_PROGRESS_PREFIX: &str = "crest-progress ";
+
+/// The step a permanent download is working on.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DownloadStage {
+    Fetching,
+    Converting,
+    EncodingVideo,
+    EmbeddingLyrics,
+}
+
+impl DownloadStage {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Fetching => "Fetching",
+            Self::Converting => "Converting",
+            Self::EncodingVideo => "Encoding video",
+            Self::EmbeddingLyrics => "Embedding lyrics",
+        }
+    }
+}
+
+/// One progress report for a permanent download.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DownloadProgress {
+    pub stage: DownloadStage,
+    /// Completed share of the stage from 0 to 1, when the total is known.
+    pub fraction: Option<f64>,
+    /// A rate such as `1.20 MiB/s`, or `3.5x` realtime for ffmpeg.
+    pub speed: Option<String>,
+    pub eta: Option<Duration>,
+}
+
+impl DownloadProgress {
+    /// A stage that has started but not reported any numbers yet.
+    pub fn stage(stage: DownloadStage) -> Self {
+        Self {
+            stage,
+            fraction: None,
+            speed: None,
+            eta: None,
+        }
+    }
+
+    /// `42%  1.20 MiB/s  ETA 0:31`, leaving out what is unknown.
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(fraction) = self.fraction {
+            parts.push(format!("{:.0}%", fraction * 100.0));
+        }
+        if let Some(speed) = &self.speed {
+            parts.push(speed.clone());
+        }
+        if let Some(eta) = self.eta {
+            let seconds = eta.as_secs();
+            parts.push(format!("ETA {}:{:02}", seconds / 60, seconds % 60));
+        }
+        parts.join("  ")
+    }
+}
+
+/// Read a line printed through [`YT_DLP_PROGRESS_TEMPLATE`]. yt-dlp fetches
+/// video and audio separately, so the fraction restarts once per stream.
+pub fn parse_yt_dlp_line(line: &str) -> Option<DownloadProgress> {
+    let mut fields = line
+        .trim()
+        .strip_prefix(YT_DLP_PROGRESS_PREFIX)?
+        .split_whitespace()
+        .map(|field| {
+            field
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite() && *value >= 0.0)
+        });
+    let (downloaded, total, speed, eta) = (
+        fields.next()?,
+        fields.next()?,
+        fields.next()?,
+        fields.next()?,
+    );
+    Some(DownloadProgress {
+        stage: DownloadStage::Fetching,
+        fraction: downloaded
+            .zip(total)
+            .filter(|(_, total)| *total > 0.0)
+            .map(|(downloaded, total)| (downloaded / total).min(1.0)),
+        speed: speed.map(|speed| format!("{}/s", format_bytes(speed as u64))),
+        eta: eta.map(Duration::from_secs_f64),
+    })
+}
+
+/// Collects ffmpeg's `-progress` output, which arrives as `key=value` lines
+/// in blocks closed by a `progress=` line.
+pub struct FfmpegProgress {
+    stage: DownloadStage,
+    /// Media duration, needed for a percentage.
+    total: Option<Duration>,
+    out_time: Option<Duration>,
+    speed: Option<f64>,
+}
+
+impl FfmpegProgress {
+    pub fn new(stage: DownloadStage, total: Option<Duration>) -> Self {
+        Self {
+            stage,
+            total,
+            out_time: None,
+            speed: None,
+        }
+    }
+
+    /// Feed one line; a report comes back at the end of each block.
+    pub fn line(&mut self, line: &str) -> Option<DownloadProgress> {
+        let (key, value) = line.trim().split_once('=')?;
+        match key {
+            "out_time_us" | "out_time_ms" => {
+                // Despite its name, `out_time_ms` is also in microseconds.
+                self.out_time = value.parse::<u64>().ok().map(Duration::from_micros);
+                None
+            }
+            "speed" => {
+                self.speed = value
+                    .trim_end_matches('x')
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|speed| speed.is_finite() && *speed > 0.0);
+                None
+            }
+            "progress" => Some(self.report(value == "end")),
+            _ => None,
+        }
+    }
+
+    fn report(&self, finished: bool) -> DownloadProgress {
+        let fraction = if finished {
+            Some(1.0)
+        } else {
+            self.out_time
+                .zip(self.total.filter(|total| !total.is_zero()))
+                .map(|(done, total)| (done.as_secs_f64() / total.as_secs_f64()).min(1.0))
+        };
+        let eta = self
+            .total
+            .zip(self.out_time)
+            .zip(self.speed)
+            .filter(|_| !finished)
+            .map(|((total, done), speed)| total.saturating_sub(done).div_f64(speed));
+        DownloadProgress {
+            stage: self.stage,
+            fraction,
+            speed: self.speed.map(|speed| format!("{speed:.1}x")),
+            eta,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DownloadStage, FfmpegProgress, parse_yt_dlp_line};
+    use std::time::Duration;
+
+    #[test]
+    fn reads_yt_dlp_progress_template_lines() {
+        let progress = parse_yt_dlp_line("crest-progress 524288 2097152 1048576.0 3").unwrap();
+        assert_eq!(progress.stage, DownloadStage::Fetching);
+        assert_eq!(progress.fraction, Some(0.25));
+        assert_eq!(progress.speed.as_deref(), Some("1.00 MiB/s"));
+        assert_eq!(progress.eta, Some(Duration::from_secs(3)));
+        assert_eq!(progress.summary(), "25%  1.00 MiB/s  ETA 0:03");
+
+        let unknown = parse_yt_dlp_line("crest-progress 1024 NA NA NA").unwrap();
+        assert_eq!(unknown.fraction, None);
+        assert_eq!(unknown.summary(), "");
+        assert!(parse_yt_dlp_line("[download] Destination: song.mkv").is_none());
+    }
+
+    #[test]
+    fn ffmpeg_progress_blocks_report_share_and_eta() {
+        let mut progress =
+            FfmpegProgress::new(DownloadStage::EncodingVideo, Some(Duration::from_secs(200)));
+        assert!(progress.line("frame=1500").is_none());
+        assert!(progress.line("out_time_us=50000000").is_none());
+        assert!(progress.line("speed=2.5x").is_none());
+        let report = progress.line("progress=continue").unwrap();
+        assert_eq!(report.fraction, Some(0.25));
+        assert_eq!(report.speed.as_deref(), Some("2.5x"));
+        assert_eq!(report.eta, Some(Duration::from_secs(60)));
+
+        let end = progress.line("progress=end").unwrap();
+        assert_eq!((end.fraction, end.eta), (Some(1.0), None));
+    }
+}
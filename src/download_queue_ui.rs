@@ -1,37 +1,83 @@
 use crate::app::{App, DownloadState};
+use crate::download_progress::DownloadProgress;
 use ratatui::{
     Frame,
     layout::Rect,
     style::{Color, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem},
+    widgets::{Block, Borders, List, ListItem, ListState},
 };
 
 pub fn render_download_queue(frame: &mut Frame, area: Rect, app: &App) {
+    let width = usize::from(area.width.saturating_sub(2));
     let items: Vec<ListItem> = app
         .downloads
         .iter()
         .map(|download| {
-            let (label, color) = match download.state {
-                DownloadState::Queued => ("Queued", Color::DarkGray),
-                DownloadState::Downloading => ("Downloading…", Color::Yellow),
+            let (label, color) = match (download.state, &download.progress) {
+                (DownloadState::Queued, _) => ("Queued", Color::DarkGray),
+                (DownloadState::Paused, _) => ("Paused", Color::Blue),
+                (DownloadState::Downloading, Some(progress)) => {
+                    (progress.stage.label(), Color::Yellow)
+                }
+                (DownloadState::Downloading, None) => ("Downloading…", Color::Yellow),
             };
-            Line::from(vec![
+            let mut lines = vec![Line::from(vec![
                 Span::raw(shorten(&download.title, 22)),
                 Span::raw("  "),
                 Span::styled(label, Style::default().fg(color)),
-            ])
-            .into()
+            ])];
+            if download.state == DownloadState::Downloading
+                && let Some(progress) = &download.progress
+            {
+                lines.push(progress_line(progress, width));
+            }
+            ListItem::new(lines)
         })
         .collect();
-    frame.render_widget(
-        List::new(items).block(
+    let focused = app.downloads_focused;
+    let list = List::new(items)
+        .block(
             Block::default()
                 .borders(Borders::ALL)
-                .title("Download Queue"),
-        ),
-        area,
-    );
+                .border_style(if focused {
+                    Style::default().fg(Color::Yellow)
+                } else {
+                    Style::default()
+                })
+                .title(if focused {
+                    "Download Queue (x cancel, p pause/resume, f front, Tab done)"
+                } else {
+                    "Download Queue"
+                }),
+        )
+        .highlight_style(Style::default().bg(Color::Green).fg(Color::Black));
+    let mut state = ListState::default();
+    if focused && !app.downloads.is_empty() {
+        state.select(Some(app.download_selected.min(app.downloads.len() - 1)));
+    }
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+/// An indented bar filling the row, followed by percentage, speed, and ETA.
+fn progress_line(progress: &DownloadProgress, width: usize) -> Line<'static> {
+    let summary = progress.summary();
+    let mut spans = vec![Span::raw("  ")];
+    if let Some(fraction) = progress.fraction {
+        let bar = width.saturating_sub(summary.chars().count() + 3).min(40);
+        let filled = ((bar as f64) * fraction.clamp(0.0, 1.0)).round() as usize;
+        spans.push(Span::styled(
+            "█".repeat(filled),
+            Style::default().fg(Color::Green),
+        ));
+        spans.push(Span::styled(
+            "░".repeat(bar - filled),
+            Style::default().fg(Color::DarkGray),
+        ));
+        spans.push(Span::raw(" "));
+    }
+    spans.push(Span::styled(summary, Style::default().fg(Color::Gray)));
+    Line::from(spans)
 }
 
 fn shorten(title: &str, max_len: usize) -> String {
@@ -1,4 +1,7 @@
+use crate::download_format::DownloadFormat;
+use crate::download_manager::DownloadLimits;
 use crate::idle_mode::{ColorPrecision, VideoRenderMode, draw_video_frame};
+use crate::loudness::NormalizationMode;
 use crate::wallpaper::HomeWallpaper;
 use ratatui::layout::{Alignment, Constraint, Direction, Layout};
 use ratatui::style::{Color, Style};
@@ -6,7 +9,7 @@ use ratatui::text::{Line, Span};
 use ratatui::widgets::{Block, Borders, Clear, Paragraph};
 
 pub const HOME_OPTION_COUNT: usize = 4;
-pub const SETTINGS_OPTION_COUNT: usize = 14;
+pub const SETTINGS_OPTION_COUNT: usize = 19;
 pub const DELETE_MEDIA_SETTING: usize = SETTINGS_OPTION_COUNT - 3;
 pub const RESET_WALLPAPER_SETTING: usize = SETTINGS_OPTION_COUNT - 2;
 pub const REMOVE_APPLICATION_SETTING: usize = SETTINGS_OPTION_COUNT - 1;
@@ -17,6 +20,10 @@ pub struct StartupScreenState<'a> {
     pub lyric_settings: (bool, bool, bool),
     pub video_settings: (bool, VideoRenderMode, ColorPrecision, u16, bool),
     pub autoplay_enabled: bool,
+    pub normalization_mode: NormalizationMode,
+    pub crossfade_seconds: u8,
+    pub download_limits: DownloadLimits,
+    pub download_format: DownloadFormat,
     pub discord_presence_enabled: bool,
     pub discord_presence_configured: bool,
     pub library_track_count: usize,
@@ -31,6 +38,10 @@ pub fn draw_startup_screen(f: &mut ratatui::Frame, state: StartupScreenState<'_>
         lyric_settings,
         video_settings,
         autoplay_enabled,
+        normalization_mode,
+        crossfade_seconds,
+        download_limits,
+        download_format,
         discord_presence_enabled,
         discord_presence_configured,
         library_track_count,
@@ -86,6 +97,13 @@ pub fn draw_startup_screen(f: &mut ratatui::Frame, state: StartupScreenState<'_>
         .map(|&l| Line::from(Span::styled(l, Style::default().fg(Color::Red))))
         .collect();
 
+    let crossfade_label = if crossfade_seconds == 0 {
+        "Crossfade: OFF (gapless)".to_string()
+    } else {
+        format!("Crossfade: {crossfade_seconds}s")
+    };
+    let fetch_label = format!("Parallel Downloads: {}", download_limits.fetch);
+    let encode_label = format!("Parallel Encodes: {}", download_limits.encode);
     let options = if settings_page {
         vec![
             (
@@ -158,6 +176,26 @@ pub fn draw_startup_screen(f: &mut ratatui::Frame, state: StartupScreenState<'_>
                 },
                 "Prefetch a YouTube Mix recommendation whenever your queue is empty.",
             ),
+            (
+                normalization_mode.label(),
+                "Cycle off, per-track, and per-album ReplayGain levels measured with EBU R128.",
+            ),
+            (
+                crossfade_label.as_str(),
+                "Blend queued tracks over 0 to 12 seconds; 0 switches between them without a gap.",
+            ),
+            (
+                fetch_label.as_str(),
+                "Cycle how many library downloads fetch from YouTube at the same time.",
+            ),
+            (
+                encode_label.as_str(),
+                "Cycle how many downloads convert audio and build video caches at the same time.",
+            ),
+            (
+                download_format.label(),
+                "Keep YouTube's original audio stream, or convert new downloads to MP3, Opus, or FLAC.",
+            ),
             (
                 if !discord_presence_configured {
                     "Discord Rich Presence: NOT CONFIGURED"
@@ -0,0 +1,127 @@
+use crate::app::App;
+use crate::history_ui::format_age;
+use crate::storage::format_bytes;
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Paragraph, Wrap},
+};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Copies shown next to each other; larger groups scroll with the selection.
+const MAX_COLUMNS: usize = 4;
+
+/// One duplicate group with its copies side by side. The selected copy is the
+/// one kept; the others are deleted with their video caches.
+pub fn render_duplicates(frame: &mut Frame, area: Rect, app: &App) {
+    let Some(view) = &app.duplicates else {
+        return;
+    };
+    let Some(group) = view.current() else {
+        return;
+    };
+    let reasons = group
+        .reasons
+        .iter()
+        .map(|reason| reason.label())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let block = Block::default().borders(Borders::ALL).title(format!(
+        "Duplicates · group {}/{} · {reasons} (←/→ choose, ↑/↓ group, Enter keep and delete the rest, Esc close)",
+        view.group + 1,
+        view.groups.len()
+    ));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let columns = group.tracks.len().min(MAX_COLUMNS);
+    let first = (view.track + 1).saturating_sub(columns);
+    let areas = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![Constraint::Ratio(1, columns as u32); columns])
+        .split(inner);
+    for (column, (index, track)) in group
+        .tracks
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(columns)
+        .enumerate()
+    {
+        let metadata = app.track_metadata(&track.path);
+        let stats = app.track_stats(&track.path);
+        let detail = |label: &str, value: Option<String>| {
+            Line::styled(
+                format!("{label}: {}", value.unwrap_or_else(|| "—".to_string())),
+                Style::default().fg(Color::Gray),
+            )
+        };
+        let source = if app.is_imported_track(&track.path) {
+            Some("imported, kept on disk".to_string())
+        } else {
+            metadata.and_then(|metadata| metadata.video_id.clone())
+        };
+        let lines = vec![
+            Line::styled(
+                track.title.clone(),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            Line::default(),
+            detail(
+                "Artist",
+                metadata.and_then(|metadata| metadata.artist.clone()),
+            ),
+            detail(
+                "Album",
+                metadata.and_then(|metadata| metadata.album.clone()),
+            ),
+            detail(
+                "Length",
+                metadata.and_then(|metadata| metadata.duration_label()),
+            ),
+            detail("Size", Some(format_bytes(track.bytes))),
+            detail(
+                "Added",
+                metadata
+                    .and_then(|metadata| metadata.added_at)
+                    .map(|added| format_age(now.saturating_sub(added))),
+            ),
+            detail(
+                "Plays",
+                Some(stats.map_or(0, |stats| stats.plays).to_string()),
+            ),
+            detail(
+                "Rating",
+                stats
+                    .map(|stats| stats.badge())
+                    .filter(|badge| !badge.is_empty()),
+            ),
+            detail("Source", source),
+            detail(
+                "File",
+                Path::new(&track.path)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned()),
+            ),
+        ];
+        let (label, color) = if index == view.track {
+            ("Keep", Color::Green)
+        } else {
+            ("Delete", Color::Red)
+        };
+        let card = Paragraph::new(lines).wrap(Wrap { trim: true }).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(color))
+                .title(format!("{label} · {}/{}", index + 1, group.tracks.len())),
+        );
+        frame.render_widget(card, areas[column]);
+    }
+}
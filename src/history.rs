@@ -0,0 +1,161 @@
+use crate::security::{read_file_limited, sanitize_display_text, valid_youtube_id};
+use dirs::audio_dir;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const MAX_HISTORY_ENTRIES: usize = 500;
+const MAX_HISTORY_FILE_BYTES: usize = 4 * 1024 * 1024;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct HistoryEntry {
+    pub title: String,
+    /// Library file, or empty when the track was only streamed.
+    #[serde(default)]
+    pub path: String,
+    #[serde(default)]
+    pub video_id: Option<String>,
+    /// Seconds since the Unix epoch when playback ended.
+    pub played_at: u64,
+    pub listened_seconds: u64,
+}
+
+impl HistoryEntry {
+    pub fn new(title: &str, path: &str, video_id: Option<String>, listened: Duration) -> Self {
+        Self {
+            title: sanitize_display_text(title),
+            // Streaming files are deleted after playback and cannot be replayed.
+            path: if path.contains("ytmusic_play_") {
+                String::new()
+            } else {
+                path.to_string()
+            },
+            video_id: video_id.filter(|id| valid_youtube_id(id)),
+            played_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default(),
+            listened_seconds: listened.as_secs(),
+        }
+    }
+
+    pub fn is_replayable(&self) -> bool {
+        !self.path.is_empty() || self.video_id.is_some()
+    }
+}
+
+/// Recently played tracks, newest first.
+#[derive(Default)]
+pub struct PlayHistory {
+    entries: VecDeque<HistoryEntry>,
+}
+
+impl PlayHistory {
+    pub fn load() -> Self {
+        let entries = history_path()
+            .and_then(|path| read_file_limited(path, MAX_HISTORY_FILE_BYTES).ok())
+            .and_then(|contents| serde_json::from_slice::<Vec<HistoryEntry>>(&contents).ok())
+            .unwrap_or_default();
+        Self::from_entries(entries)
+    }
+
+    fn from_entries(entries: Vec<HistoryEntry>) -> Self {
+        Self {
+            entries: entries
+                .into_iter()
+                .map(|mut entry| {
+                    entry.title = sanitize_display_text(&entry.title);
+                    entry.video_id = entry.video_id.filter(|id| valid_youtube_id(id));
+                    entry
+                })
+                .filter(HistoryEntry::is_replayable)
+                .take(MAX_HISTORY_ENTRIES)
+                .collect(),
+        }
+    }
+
+    pub fn save(&self) {
+        let Some(path) = history_path() else { return };
+        if let Ok(json) = serde_json::to_string(&self.entries) {
+            let _ = std::fs::write(path, json);
+        }
+    }
+
+    pub fn record(&mut self, entry: HistoryEntry) {
+        if !entry.is_replayable() {
+            return;
+        }
+        self.entries.push_front(entry);
+        self.entries.truncate(MAX_HISTORY_ENTRIES);
+    }
+
+    /// Stepping back removes the entry so repeated presses walk further into
+    /// the past; it is recorded again once it finishes playing.
+    pub fn remove(&mut self, index: usize) -> Option<HistoryEntry> {
+        self.entries.remove(index)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&HistoryEntry> {
+        self.entries.get(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.entries.iter()
+    }
+}
+
+fn history_path() -> Option<std::path::PathBuf> {
+    audio_dir().map(|directory| directory.join("ytmusic_history.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HistoryEntry, MAX_HISTORY_ENTRIES, PlayHistory};
+    use std::time::Duration;
+
+    #[test]
+    fn history_is_newest_first_and_bounded() {
+        let mut history = PlayHistory::default();
+        for index in 0..MAX_HISTORY_ENTRIES + 5 {
+            history.record(HistoryEntry::new(
+                &format!("Song {index}"),
+                &format!("song-{index}.mp3"),
+                None,
+                Duration::from_secs(30),
+            ));
+        }
+        assert_eq!(history.len(), MAX_HISTORY_ENTRIES);
+        let newest = format!("Song {}", MAX_HISTORY_ENTRIES + 4);
+        assert_eq!(history.get(0).unwrap().title, newest);
+        assert_eq!(history.remove(0).unwrap().title, newest);
+    }
+
+    #[test]
+    fn streamed_tracks_keep_only_their_video_id() {
+        let mut history = PlayHistory::default();
+        history.record(HistoryEntry::new(
+            "Streamed",
+            "/tmp/ytmusic_play_1_2.mp3",
+            Some("dQw4w9WgXcQ".to_string()),
+            Duration::from_secs(12),
+        ));
+        history.record(HistoryEntry::new(
+            "Gone",
+            "/tmp/ytmusic_play_1_3.mp3",
+            Some("not an id".to_string()),
+            Duration::from_secs(12),
+        ));
+        assert_eq!(history.len(), 1);
+        let entry = history.get(0).unwrap();
+        assert!(entry.path.is_empty());
+        assert_eq!(entry.video_id.as_deref(), Some("dQw4w9WgXcQ"));
+    }
+}
@@ -0,0 +1,86 @@
+use crate::app::App;
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState},
+};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// `streaming` marks whether entries that were only streamed can be fetched again.
+pub fn render_history(frame: &mut Frame, area: Rect, app: &App, streaming: bool) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let items: Vec<ListItem> = app
+        .history
+        .iter()
+        .map(|entry| {
+            let (status, color) = if app.is_library_file_available(&entry.path) {
+                ("●", Color::Green)
+            } else if app.is_library_track_offline(&entry.path) {
+                ("○", Color::DarkGray)
+            } else if streaming && entry.video_id.is_some() {
+                ("☁", Color::Yellow)
+            } else {
+                ("❌", Color::Red)
+            };
+            Line::from(vec![
+                Span::raw(shorten(&entry.title, 24)),
+                Span::raw(" "),
+                Span::styled(status, Style::default().fg(color)),
+                Span::styled(
+                    format!(
+                        "  {} · {}",
+                        format_listened(entry.listened_seconds),
+                        format_age(now.saturating_sub(entry.played_at))
+                    ),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ])
+            .into()
+        })
+        .collect();
+    let mut state = ListState::default();
+    if !app.history.is_empty() {
+        state.select(Some(app.history_selected.min(app.history.len() - 1)));
+    }
+    frame.render_stateful_widget(
+        List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Recently Played (Enter play, Ctrl+e queue, Ctrl+r close)"),
+            )
+            .highlight_style(Style::default().bg(Color::Green).fg(Color::Black)),
+        area,
+        &mut state,
+    );
+}
+
+fn format_listened(seconds: u64) -> String {
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+pub fn format_age(seconds: u64) -> String {
+    match seconds {
+        0..60 => "just now".to_string(),
+        60..3_600 => format!("{}m ago", seconds / 60),
+        3_600..86_400 => format!("{}h ago", seconds / 3_600),
+        _ => format!("{}d ago", seconds / 86_400),
+    }
+}
+
+fn shorten(title: &str, max_len: usize) -> String {
+    if title.chars().count() <= max_len {
+        return title.to_string();
+    }
+    let mut shortened = title
+        .chars()
+        .take(max_len.saturating_sub(1))
+        .collect::<String>();
+    shortened.push('…');
+    shortened
+}
@@ -1,9 +1,10 @@
 use std::time::{Duration, Instant};
 
+use crate::album_art::fit_rect;
 use crate::video_screensaver::VideoFrame;
 use ratatui::{
     Frame,
-    layout::{Alignment, Rect},
+    layout::{Alignment, Position, Rect},
     style::{Color, Style},
     text::Line,
     widgets::{Block, Paragraph},
@@ -147,6 +148,8 @@ pub struct IdleRenderState<'a> {
     pub title: Option<&'a str>,
     pub position: Duration,
     pub video_frame: Option<&'a VideoFrame>,
+    /// Shown centered over the animated backdrop when there is no video.
+    pub cover_art: Option<&'a VideoFrame>,
     pub render_mode: VideoRenderMode,
     pub color_precision: ColorPrecision,
     pub synced_lyrics: Option<(String, Option<String>)>,
@@ -158,6 +161,7 @@ pub fn draw_idle_mode(frame: &mut Frame, state: IdleRenderState<'_>) {
         title,
         position,
         video_frame,
+        cover_art,
         render_mode,
         color_precision,
         synced_lyrics,
@@ -174,23 +178,40 @@ pub fn draw_idle_mode(frame: &mut Frame, state: IdleRenderState<'_>) {
     let inner = area;
 
     let seconds = position.as_secs_f32();
+    let cover = cover_art
+        .filter(|_| video_frame.is_none())
+        .map(|cover| (cover, fit_rect(inner, cover.width, cover.height)));
     for y in 0..inner.height {
         for x in 0..inner.width {
-            let top = video_frame
-                .and_then(|video| video_color(video, inner, x, y.saturating_mul(2)))
-                .unwrap_or_else(|| pixel_color(x, y.saturating_mul(2), inner, seconds));
-            let bottom = video_frame
-                .and_then(|video| {
-                    video_color(video, inner, x, y.saturating_mul(2).saturating_add(1))
-                })
-                .unwrap_or_else(|| {
-                    pixel_color(x, y.saturating_mul(2).saturating_add(1), inner, seconds)
-                });
+            let on_cover =
+                cover.filter(|(_, rect)| rect.contains(Position::new(inner.x + x, inner.y + y)));
+            let (top, bottom) = if let Some((cover, rect)) = on_cover {
+                let (x, y) = (inner.x + x - rect.x, inner.y + y - rect.y);
+                (
+                    video_color(cover, rect, x, y.saturating_mul(2)).unwrap_or(Color::Black),
+                    video_color(cover, rect, x, y.saturating_mul(2).saturating_add(1))
+                        .unwrap_or(Color::Black),
+                )
+            } else {
+                (
+                    video_frame
+                        .and_then(|video| video_color(video, inner, x, y.saturating_mul(2)))
+                        .unwrap_or_else(|| pixel_color(x, y.saturating_mul(2), inner, seconds)),
+                    video_frame
+                        .and_then(|video| {
+                            video_color(video, inner, x, y.saturating_mul(2).saturating_add(1))
+                        })
+                        .unwrap_or_else(|| {
+                            pixel_color(x, y.saturating_mul(2).saturating_add(1), inner, seconds)
+                        }),
+                )
+            };
             let top = color_precision.apply(top);
             let bottom = color_precision.apply(bottom);
-            let (symbol, style) = match (render_mode, video_frame) {
-                (VideoRenderMode::AsciiFast, Some(_)) => ascii_cell(top, bottom, x, y, false),
-                (VideoRenderMode::AsciiDetailed, Some(_)) => ascii_cell(top, bottom, x, y, true),
+            let picture = video_frame.is_some() || on_cover.is_some();
+            let (symbol, style) = match (render_mode, picture) {
+                (VideoRenderMode::AsciiFast, true) => ascii_cell(top, bottom, x, y, false),
+                (VideoRenderMode::AsciiDetailed, true) => ascii_cell(top, bottom, x, y, true),
                 _ => ("▀", Style::default().fg(top).bg(bottom)),
             };
             if let Some(cell) = frame.buffer_mut().cell_mut((inner.x + x, inner.y + y)) {
@@ -0,0 +1,473 @@
+use crate::app::{load_library_index, load_library_roots, merge_library_index};
+use crate::download_format::AUDIO_EXTENSIONS;
+use crate::security::{containing_root, sanitize_display_text};
+use crate::storage::format_bytes;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Always the first entry, so an archive is rejected before anything is
+/// written.
+const MANIFEST_NAME: &str = "crest-archive.json";
+const ARCHIVE_FORMAT: u32 = 1;
+
+/// Files under the `crest-player` configuration directory.
+const CONFIG_FILES: [&str; 4] = [
+    "settings.json",
+    "playlists.json",
+    "smart_playlists.json",
+    crate::wallpaper::FILE_NAME,
+];
+const LIBRARY_INDEX: &str = "ytmusic_library.csv";
+const HISTORY: &str = "ytmusic_history.json";
+
+/// Upper bound for every archived file except media, which is streamed.
+const MAX_DATA_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    format: u32,
+    /// The exported library roots as written in the index, and canonical.
+    music_dirs: Vec<String>,
+    created_at: u64,
+    media: bool,
+}
+
+/// `--export-library <file> [--with-media]`: write the library index,
+/// history, playlists, settings and wallpaper, and optionally the downloaded
+/// songs with their video caches, to a zstd-compressed tar archive.
+/// Imported folders are only referenced by the index, never copied.
+pub fn export_library(file: &Path, with_media: bool) -> Result<(), String> {
+    let music_dir = dirs::audio_dir().ok_or("could not locate the Music directory")?;
+    let config_dir = config_dir()?;
+    let roots = load_library_roots().online();
+    let mut music_dirs = Vec::new();
+    for root in &roots {
+        let canonical = root.canonicalize().unwrap_or(root.clone());
+        for root in [root, &canonical] {
+            let root = root.to_string_lossy().into_owned();
+            if !music_dirs.contains(&root) {
+                music_dirs.push(root);
+            }
+        }
+    }
+    let manifest = Manifest {
+        format: ARCHIVE_FORMAT,
+        music_dirs,
+        created_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default(),
+        media: with_media,
+    };
+    let manifest = serde_json::to_vec_pretty(&manifest).map_err(|error| error.to_string())?;
+
+    let partial = partial_path(file);
+    let result = (|| -> io::Result<(usize, u64)> {
+        let encoder = zstd::Encoder::new(File::create(&partial)?, 3)?;
+        let mut archive = tar::Builder::new(encoder);
+        append_bytes(&mut archive, MANIFEST_NAME, &manifest)?;
+        for name in CONFIG_FILES {
+            append_if_present(
+                &mut archive,
+                &config_dir.join(name),
+                &format!("config/{name}"),
+            )?;
+        }
+        for name in [LIBRARY_INDEX, HISTORY] {
+            append_if_present(
+                &mut archive,
+                &music_dir.join(name),
+                &format!("music/{name}"),
+            )?;
+        }
+        let (mut songs, mut bytes) = (0, 0);
+        if with_media {
+            for path in downloaded_media(&roots) {
+                let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                    continue;
+                };
+                bytes += path.metadata()?.len();
+                archive.append_path_with_name(&path, format!("music/{name}"))?;
+                if is_downloaded_audio(name) {
+                    songs += 1;
+                }
+            }
+        }
+        let mut output = archive.into_inner()?.finish()?;
+        output.flush()?;
+        output.sync_all()?;
+        Ok((songs, bytes))
+    })();
+    let (songs, bytes) = match result.and_then(|summary| {
+        std::fs::rename(&partial, file)?;
+        Ok(summary)
+    }) {
+        Ok(summary) => summary,
+        Err(error) => {
+            let _ = std::fs::remove_file(&partial);
+            return Err(format!(
+                "could not write {}: {error}",
+                sanitize_display_text(&file.to_string_lossy())
+            ));
+        }
+    };
+    let size = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+    if with_media {
+        println!(
+            "Exported the library with {songs} downloaded song(s) ({}) to {} ({}).",
+            format_bytes(bytes),
+            sanitize_display_text(&file.to_string_lossy()),
+            format_bytes(size)
+        );
+    } else {
+        println!(
+            "Exported the library to {} ({}). Add --with-media to include downloaded songs.",
+            sanitize_display_text(&file.to_string_lossy()),
+            format_bytes(size)
+        );
+    }
+    Ok(())
+}
+
+/// `--import-library <file>`: restore an archive made by [`export_library`]
+/// into this machine's Music and configuration directories. Index, playlist
+/// and history paths under the exported library roots are rewritten to
+/// point at the local Music directory. Existing configuration files are kept as `.bak`,
+/// existing songs are never overwritten, and library entries already present
+/// stay as they are.
+pub fn import_library(file: &Path) -> Result<(), String> {
+    let music_dir = dirs::audio_dir().ok_or("could not locate the Music directory")?;
+    let config_dir = config_dir()?;
+    let input = File::open(file).map_err(|error| {
+        format!(
+            "could not open {}: {error}",
+            sanitize_display_text(&file.to_string_lossy())
+        )
+    })?;
+    let decoder = zstd::Decoder::new(input).map_err(|error| error.to_string())?;
+    let mut archive = tar::Archive::new(decoder);
+    let mut entries = archive.entries().map_err(invalid_archive)?;
+
+    let manifest: Manifest = {
+        let mut entry = entries
+            .next()
+            .ok_or("the archive is empty")?
+            .map_err(invalid_archive)?;
+        if entry.path().map_err(invalid_archive)? != Path::new(MANIFEST_NAME) {
+            return Err("not a Crest Player library archive".to_string());
+        }
+        serde_json::from_slice(&read_limited(&mut entry)?)
+            .map_err(|error| format!("invalid archive manifest: {error}"))?
+    };
+    if manifest.format != ARCHIVE_FORMAT {
+        return Err(format!(
+            "unsupported archive format {}; update Crest Player to import it",
+            manifest.format
+        ));
+    }
+    let old_dirs: Vec<PathBuf> = manifest.music_dirs.iter().map(PathBuf::from).collect();
+    let rebase = |path: &str| rebase_path(path, &old_dirs, &music_dir);
+
+    std::fs::create_dir_all(&music_dir).map_err(|error| error.to_string())?;
+    std::fs::create_dir_all(&config_dir).map_err(|error| error.to_string())?;
+    let mut config = Vec::new();
+    let (mut index, mut history) = (None, None);
+    let (mut restored, mut present, mut ignored) = (0, 0, 0);
+    for entry in entries {
+        let mut entry = entry.map_err(invalid_archive)?;
+        let name = entry.path().map_err(invalid_archive)?.into_owned();
+        let Some((section, name)) = archive_member(&name)
+            .filter(|_| entry.header().entry_type() == tar::EntryType::Regular)
+        else {
+            ignored += 1;
+            continue;
+        };
+        match (section, name.as_str()) {
+            ("config", name) if CONFIG_FILES.contains(&name) => {
+                config.push((name.to_string(), read_limited(&mut entry)?));
+            }
+            ("music", LIBRARY_INDEX) => index = Some(read_limited(&mut entry)?),
+            ("music", HISTORY) => history = Some(read_limited(&mut entry)?),
+            ("music", name) if is_downloaded_audio(name) || name.ends_with(".crestvid") => {
+                let target = music_dir.join(name);
+                if target.exists() {
+                    present += 1;
+                    continue;
+                }
+                let partial = partial_path(&target);
+                let result = File::create(&partial)
+                    .and_then(|mut output| io::copy(&mut entry, &mut output))
+                    .and_then(|_| std::fs::rename(&partial, &target));
+                if let Err(error) = result {
+                    let _ = std::fs::remove_file(&partial);
+                    return Err(format!(
+                        "could not restore {}: {error}",
+                        sanitize_display_text(name)
+                    ));
+                }
+                restored += 1;
+            }
+            _ => ignored += 1,
+        }
+    }
+
+    let mut backed_up = false;
+    for (name, data) in config {
+        let data = if name == "playlists.json" {
+            rebase_json(&data, &rebase)?
+        } else {
+            data
+        };
+        let target = config_dir.join(&name);
+        if target.exists() {
+            std::fs::copy(&target, config_dir.join(format!("{name}.bak")))
+                .map_err(|error| format!("could not back up {name}: {error}"))?;
+            backed_up = true;
+        }
+        std::fs::write(&target, data)
+            .map_err(|error| format!("could not write {name}: {error}"))?;
+    }
+    // Local history wins: it is newer than anything in the archive.
+    let history_path = music_dir.join(HISTORY);
+    if let Some(data) = history.filter(|_| !history_path.exists()) {
+        std::fs::write(&history_path, rebase_json(&data, &rebase)?)
+            .map_err(|error| format!("could not write the play history: {error}"))?;
+    }
+    let mut added = 0;
+    if let Some(data) = index {
+        let index_path = music_dir.join(LIBRARY_INDEX);
+        let existing = match std::fs::read(&index_path) {
+            Ok(existing) => String::from_utf8_lossy(&existing).into_owned(),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(format!("could not read the library index: {error}")),
+        };
+        let (merged, count) =
+            merge_library_index(&existing, &String::from_utf8_lossy(&data), rebase);
+        std::fs::write(&index_path, merged)
+            .map_err(|error| format!("could not write the library index: {error}"))?;
+        added = count;
+    }
+
+    println!(
+        "Imported {added} song(s) into {}.",
+        sanitize_display_text(&music_dir.to_string_lossy())
+    );
+    if manifest.media {
+        println!("Restored {restored} media file(s); {present} already existed.");
+    }
+    if backed_up {
+        println!("Previous configuration files were kept with a .bak extension.");
+    }
+    if ignored > 0 {
+        println!("Skipped {ignored} unrecognized file(s) in the archive.");
+    }
+    Ok(())
+}
+
+fn config_dir() -> Result<PathBuf, String> {
+    dirs::config_dir()
+        .map(|directory| directory.join("crest-player"))
+        .ok_or_else(|| "could not locate the configuration directory".to_string())
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".part");
+    PathBuf::from(name)
+}
+
+fn invalid_archive(error: io::Error) -> String {
+    format!("invalid library archive: {error}")
+}
+
+/// Whether a file name is a song in one of the download formats.
+fn is_downloaded_audio(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| AUDIO_EXTENSIONS.contains(&extension))
+}
+
+/// Downloaded songs and their video caches on the mounted library roots,
+/// skipping imported tracks.
+fn downloaded_media(roots: &[PathBuf]) -> Vec<PathBuf> {
+    let index = load_library_index();
+    let mut media = Vec::new();
+    let mut names = HashSet::new();
+    for (_, path) in &index.tracks {
+        if index
+            .metadata
+            .get(path)
+            .is_some_and(|metadata| metadata.imported)
+        {
+            continue;
+        }
+        let song = Path::new(path);
+        if containing_root(song, roots).is_none() || !song.is_file() {
+            continue;
+        }
+        // The archive is flat, so a name already taken from another root is
+        // left out rather than overwritten on import.
+        if !song
+            .file_name()
+            .is_some_and(|name| names.insert(name.to_owned()))
+        {
+            continue;
+        }
+        let cache = song.with_extension("crestvid");
+        media.push(song.to_path_buf());
+        if cache.is_file() {
+            media.push(cache);
+        }
+    }
+    media
+}
+
+fn append_bytes<W: Write>(
+    archive: &mut tar::Builder<W>,
+    name: &str,
+    data: &[u8],
+) -> io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default(),
+    );
+    header.set_cksum();
+    archive.append_data(&mut header, name, data)
+}
+
+fn append_if_present<W: Write>(
+    archive: &mut tar::Builder<W>,
+    path: &Path,
+    name: &str,
+) -> io::Result<()> {
+    match std::fs::read(path) {
+        Ok(data) => append_bytes(archive, name, &data),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(error),
+    }
+}
+
+fn read_limited(entry: &mut impl Read) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    entry
+        .take(MAX_DATA_BYTES + 1)
+        .read_to_end(&mut data)
+        .map_err(invalid_archive)?;
+    if data.len() as u64 > MAX_DATA_BYTES {
+        return Err("invalid library archive: an entry is too large".to_string());
+    }
+    Ok(data)
+}
+
+/// Split `config/<name>` or `music/<name>` into its parts. Anything nested,
+/// hidden or escaping the directory is refused.
+fn archive_member(path: &Path) -> Option<(&'static str, String)> {
+    let mut components = path.components();
+    let section = match components.next()? {
+        Component::Normal(section) if section == "config" => "config",
+        Component::Normal(section) if section == "music" => "music",
+        _ => return None,
+    };
+    let Component::Normal(name) = components.next()? else {
+        return None;
+    };
+    let name = name.to_str()?;
+    if components.next().is_some() || name.starts_with('.') {
+        return None;
+    }
+    Some((section, name.to_string()))
+}
+
+/// Move a file directly inside one of the exported library roots into
+/// `music_dir`. Other paths, such as imported folders, are left alone.
+fn rebase_path(path: &str, old_dirs: &[PathBuf], music_dir: &Path) -> String {
+    let original = Path::new(path);
+    match (original.parent(), original.file_name()) {
+        (Some(parent), Some(name)) if old_dirs.iter().any(|old| old == parent) => {
+            music_dir.join(name).to_string_lossy().into_owned()
+        }
+        _ => path.to_string(),
+    }
+}
+
+/// Rewrite every `"path"` string in a playlists or history file.
+fn rebase_json(data: &[u8], rebase: &impl Fn(&str) -> String) -> Result<Vec<u8>, String> {
+    fn walk(value: &mut Value, rebase: &impl Fn(&str) -> String) {
+        match value {
+            Value::Array(items) => items.iter_mut().for_each(|item| walk(item, rebase)),
+            Value::Object(fields) => {
+                for (key, field) in fields.iter_mut() {
+                    match field {
+                        Value::String(path) if key == "path" => *path = rebase(path),
+                        _ => walk(field, rebase),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    let mut value: Value = serde_json::from_slice(data)
+        .map_err(|error| format!("invalid library archive: {error}"))?;
+    walk(&mut value, rebase);
+    serde_json::to_vec(&value).map_err(|error| error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{archive_member, is_downloaded_audio, rebase_json, rebase_path};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn paths_move_into_the_new_music_directory() {
+        let old = [PathBuf::from("/home/a/Music"), PathBuf::from("/data/music")];
+        let new = Path::new("/home/b/Musik");
+        let rebase = |path: &str| rebase_path(path, &old, new);
+        assert_eq!(rebase("/home/a/Music/Song.mp3"), "/home/b/Musik/Song.mp3");
+        assert_eq!(rebase("/data/music/Song.mp3"), "/home/b/Musik/Song.mp3");
+        assert_eq!(
+            rebase("/home/a/Music/Albums/Song.mp3"),
+            "/home/a/Music/Albums/Song.mp3"
+        );
+        assert_eq!(rebase("/mnt/other/Song.mp3"), "/mnt/other/Song.mp3");
+
+        let playlists = br#"[{"name":"Mix","entries":[{"title":"/home/a/Music/x","path":"/home/a/Music/Song.mp3"}]}]"#;
+        let rewritten = String::from_utf8(rebase_json(playlists, &rebase).unwrap()).unwrap();
+        assert!(rewritten.contains(r#""path":"/home/b/Musik/Song.mp3""#));
+        assert!(rewritten.contains(r#""title":"/home/a/Music/x""#));
+    }
+
+    #[test]
+    fn only_flat_known_sections_are_extracted() {
+        assert_eq!(
+            archive_member(Path::new("music/Song.mp3")),
+            Some(("music", "Song.mp3".to_string()))
+        );
+        assert_eq!(
+            archive_member(Path::new("config/settings.json")),
+            Some(("config", "settings.json".to_string()))
+        );
+        for path in [
+            "music/../Song.mp3",
+            "music/sub/Song.mp3",
+            "music/.hidden",
+            "/music/Song.mp3",
+            "other/Song.mp3",
+            "music",
+        ] {
+            assert_eq!(archive_member(Path::new(path)), None, "{path}");
+        }
+        assert!(is_downloaded_audio("Song.opus") && is_downloaded_audio("Song.mp3"));
+        assert!(!is_downloaded_audio("Song.crestvid") && !is_downloaded_audio("opus"));
+    }
+}
@@ -0,0 +1,415 @@
+use crate::app::{App, validated_library_media_path, validated_library_partial_path};
+use crate::download_format::AUDIO_EXTENSIONS;
+use crate::pending_downloads::PendingDownloads;
+use crate::search::rebuild_video_cache;
+use crate::security::{sanitize_display_text, valid_youtube_id};
+use crate::tagging::video_id_from_filename;
+use crate::video_cache::is_readable_cache;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, SystemTime};
+
+/// Temporary files a download writes next to its audio before publishing it,
+/// besides the `<name>.<extension>.part` audio itself.
+const PARTIAL_SUFFIXES: [&str; 4] = [
+    ".download.mkv",
+    ".download.info.json",
+    ".crestvid.part",
+    ".crestvid.part.lyrics.vtt",
+];
+
+/// Partial files this recent may belong to a download another Crest Player
+/// window is still running, so they are left alone.
+const MIN_PARTIAL_AGE: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CacheProblem {
+    Missing,
+    Unreadable,
+}
+
+/// A downloaded track whose `.crestvid` cache cannot be played.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BrokenCache {
+    pub title: String,
+    pub path: String,
+    /// Without it the video cannot be downloaded again.
+    pub video_id: Option<String>,
+    pub problem: CacheProblem,
+}
+
+/// Where the library index and the library roots disagree.
+#[derive(Debug, Default)]
+pub struct LibraryReport {
+    pub checked: usize,
+    /// Songs on a disk that is not mounted; they are not problems.
+    pub offline: usize,
+    /// Index entries whose file is gone, as (title, path).
+    pub dead: Vec<(String, String)>,
+    pub broken_caches: Vec<BrokenCache>,
+    /// Leftovers of interrupted downloads in the library roots.
+    pub partials: Vec<PathBuf>,
+}
+
+impl LibraryReport {
+    pub fn is_clean(&self) -> bool {
+        self.dead.is_empty() && self.broken_caches.is_empty() && self.partials.is_empty()
+    }
+
+    /// One line per problem found, for the command line.
+    pub fn details(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (title, path) in &self.dead {
+            lines.push(format!(
+                "missing file: {} ({})",
+                sanitize_display_text(title),
+                sanitize_display_text(path)
+            ));
+        }
+        for cache in &self.broken_caches {
+            let problem = match cache.problem {
+                CacheProblem::Missing => "missing video cache",
+                CacheProblem::Unreadable => "unreadable video cache",
+            };
+            let note = if cache.video_id.is_none() {
+                " (no YouTube ID to rebuild from)"
+            } else {
+                ""
+            };
+            lines.push(format!(
+                "{problem}: {}{note}",
+                sanitize_display_text(&cache.title)
+            ));
+        }
+        for partial in &self.partials {
+            lines.push(format!(
+                "leftover partial download: {}",
+                sanitize_display_text(&partial.to_string_lossy())
+            ));
+        }
+        lines
+    }
+
+    pub fn summary(&self) -> String {
+        let offline = if self.offline > 0 {
+            format!(" {} song(s) on offline disks were skipped.", self.offline)
+        } else {
+            String::new()
+        };
+        if self.is_clean() {
+            return format!(
+                "Checked {} song(s); the library is healthy.{offline}",
+                self.checked - self.offline
+            );
+        }
+        format!(
+            "Checked {} song(s): {} missing file(s), {} broken video cache(s), {} leftover partial file(s).{offline}",
+            self.checked - self.offline,
+            self.dead.len(),
+            self.broken_caches.len(),
+            self.partials.len()
+        )
+    }
+
+    /// Broken caches that can be downloaded again.
+    pub fn rebuildable_caches(&self) -> Vec<BrokenCache> {
+        self.broken_caches
+            .iter()
+            .filter(|cache| cache.video_id.is_some())
+            .cloned()
+            .collect()
+    }
+}
+
+/// Compare the library index with the files on disk. Imported songs are only
+/// checked for existence; they have no video cache. Songs on unmounted
+/// disks are counted but never reported as missing.
+pub fn check_library(app: &App) -> LibraryReport {
+    let mut report = LibraryReport {
+        checked: app.library.len(),
+        ..LibraryReport::default()
+    };
+    let roots = app.library_roots.paths();
+    for (title, path) in &app.library {
+        let file = Path::new(path);
+        if app.is_library_track_offline(path) {
+            report.offline += 1;
+            continue;
+        }
+        if !file.is_file() {
+            report.dead.push((title.clone(), path.clone()));
+            continue;
+        }
+        if app.is_imported_track(path) {
+            continue;
+        }
+        // Only caches the repair could safely replace are reported.
+        let Ok(cache) = validated_library_media_path(file.with_extension("crestvid"), &roots)
+        else {
+            continue;
+        };
+        let problem = if !cache.exists() {
+            CacheProblem::Missing
+        } else if !is_readable_cache(&cache) {
+            CacheProblem::Unreadable
+        } else {
+            continue;
+        };
+        let video_id = app
+            .track_metadata(path)
+            .and_then(|metadata| metadata.video_id.clone())
+            .filter(|video_id| valid_youtube_id(video_id))
+            .or_else(|| video_id_from_filename(file));
+        report.broken_caches.push(BrokenCache {
+            title: title.clone(),
+            path: path.clone(),
+            video_id,
+            problem,
+        });
+    }
+    report.partials = orphan_partials(app);
+    report
+}
+
+fn orphan_partials(app: &App) -> Vec<PathBuf> {
+    let roots = app.library_roots.paths();
+    // Downloads saved for the next launch resume from their partial files.
+    let pending = PendingDownloads::load().unwrap_or_default();
+    let owners: HashSet<PathBuf> = app
+        .downloads
+        .iter()
+        .map(|job| job.path.as_str())
+        .chain(
+            pending
+                .downloads
+                .iter()
+                .map(|download| download.path.as_str()),
+        )
+        .map(|path| Path::new(path).with_extension(""))
+        .collect();
+    let now = SystemTime::now();
+    let mut partials: Vec<PathBuf> = roots
+        .iter()
+        .filter(|root| !app.library_roots.is_offline(root))
+        .filter_map(|root| std::fs::read_dir(root).ok())
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let Some(owner) = partial_owner(path) else {
+                return false;
+            };
+            let settled = path
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| {
+                    now.duration_since(modified)
+                        .is_ok_and(|age| age >= MIN_PARTIAL_AGE)
+                });
+            settled
+                && !owners.contains(&owner)
+                && validated_library_partial_path(path, &roots).is_ok_and(|path| path.is_file())
+        })
+        .collect();
+    partials.sort();
+    partials
+}
+
+/// The download a partial file belongs to, without the audio extension,
+/// which depends on the format the download was queued with.
+pub fn partial_owner(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    let stem = PARTIAL_SUFFIXES
+        .iter()
+        .find_map(|suffix| name.strip_suffix(suffix))
+        .or_else(|| {
+            let audio = name.strip_suffix(".part")?;
+            AUDIO_EXTENSIONS
+                .iter()
+                .find_map(|extension| audio.strip_suffix(extension)?.strip_suffix('.'))
+        })
+        .filter(|stem| !stem.is_empty())?;
+    Some(path.with_file_name(stem))
+}
+
+/// What `repair_library` changed.
+#[derive(Debug, Default)]
+pub struct RepairOutcome {
+    pub removed_entries: usize,
+    pub deleted_partials: usize,
+    pub errors: Vec<String>,
+}
+
+impl RepairOutcome {
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "Removed {} missing song(s) and {} partial file(s)",
+            self.removed_entries, self.deleted_partials
+        );
+        if !self.errors.is_empty() {
+            summary.push_str(&format!("; {} could not be removed", self.errors.len()));
+        }
+        summary + "."
+    }
+}
+
+/// Drop dead index entries and delete leftover partial files. Deletions go
+/// through the same library-root containment check as removing a song.
+/// Broken caches are rebuilt separately with [`CacheRebuildJob`].
+pub fn repair_library(app: &mut App, report: &LibraryReport) -> RepairOutcome {
+    let mut outcome = RepairOutcome::default();
+    let roots = app.library_roots.paths();
+    for (_, path) in &report.dead {
+        // A dead download may still have a cache beside it, which
+        // `remove_library_track` deletes. Entries it refuses to touch, such as
+        // paths outside the library roots, are only dropped from the index.
+        if app.remove_library_track(path).is_err() {
+            app.forget_library_track(path);
+        }
+        outcome.removed_entries += 1;
+    }
+    for partial in &report.partials {
+        match validated_library_partial_path(partial, &roots).and_then(std::fs::remove_file) {
+            Ok(()) => outcome.deleted_partials += 1,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+            Err(error) => outcome.errors.push(format!(
+                "{}: {error}",
+                sanitize_display_text(&partial.to_string_lossy())
+            )),
+        }
+    }
+    outcome
+}
+
+/// Downloads the videos of broken caches again, one at a time, in the
+/// background.
+pub struct CacheRebuildJob {
+    pub receiver: Receiver<(String, Result<(), String>)>,
+    pub total: usize,
+    pub rebuilt: usize,
+    pub failed: usize,
+}
+
+impl CacheRebuildJob {
+    /// `plan` is the width, height and frame rate of the new caches.
+    pub fn start(caches: Vec<BrokenCache>, plan: (u16, u16, u16)) -> Self {
+        let total = caches.len();
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let cancelled = AtomicBool::new(false);
+            for cache in caches {
+                let result = match cache.video_id.as_deref() {
+                    Some(video_id) => rebuild_video_cache(
+                        &format!("https://www.youtube.com/watch?v={video_id}"),
+                        &cache.title,
+                        Path::new(&cache.path),
+                        plan,
+                        &cancelled,
+                    ),
+                    None => Err("the song has no YouTube ID".to_string()),
+                };
+                if sender.send((cache.title, result)).is_err() {
+                    return;
+                }
+            }
+        });
+        Self {
+            receiver,
+            total,
+            rebuilt: 0,
+            failed: 0,
+        }
+    }
+}
+
+/// `--check-library [--fix]`: print the report and, with `fix`, repair the
+/// library and rebuild broken caches before returning.
+pub fn check_from_command_line(
+    mut app: App,
+    fix: bool,
+    plan: (u16, u16, u16),
+) -> Result<(), String> {
+    if dirs::audio_dir().is_none() {
+        return Err("could not locate the Music directory".to_string());
+    }
+    let report = check_library(&app);
+    println!("Crest Player library check");
+    println!();
+    for line in report.details() {
+        println!("  {line}");
+    }
+    if !report.is_clean() {
+        println!();
+    }
+    println!("{}", report.summary());
+    if report.is_clean() {
+        return Ok(());
+    }
+    if !fix {
+        println!("Run 'crest-player --check-library --fix' to repair the library.");
+        return Ok(());
+    }
+    let outcome = repair_library(&mut app, &report);
+    crate::app::save_library(&app);
+    for error in &outcome.errors {
+        eprintln!("  {error}");
+    }
+    println!("{}", outcome.summary());
+    let caches = report.rebuildable_caches();
+    if caches.is_empty() {
+        return Ok(());
+    }
+    println!("Rebuilding {} video cache(s)...", caches.len());
+    let mut job = CacheRebuildJob::start(caches, plan);
+    for (title, result) in job.receiver.iter() {
+        let title = sanitize_display_text(&title);
+        match result {
+            Ok(()) => {
+                job.rebuilt += 1;
+                println!(
+                    "  [{}/{}] rebuilt {title}",
+                    job.rebuilt + job.failed,
+                    job.total
+                );
+            }
+            Err(error) => {
+                job.failed += 1;
+                println!(
+                    "  [{}/{}] could not rebuild {title}: {error}",
+                    job.rebuilt + job.failed,
+                    job.total
+                );
+            }
+        }
+    }
+    println!("Rebuilt {} of {} video cache(s).", job.rebuilt, job.total);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::partial_owner;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn partial_files_map_back_to_their_download() {
+        let owner = Some(PathBuf::from("/music/Song [dQw4w9WgXcQ]_ytmusic"));
+        for name in [
+            "Song [dQw4w9WgXcQ]_ytmusic.mp3.part",
+            "Song [dQw4w9WgXcQ]_ytmusic.opus.part",
+            "Song [dQw4w9WgXcQ]_ytmusic.flac.part",
+            "Song [dQw4w9WgXcQ]_ytmusic.download.mkv",
+            "Song [dQw4w9WgXcQ]_ytmusic.download.info.json",
+            "Song [dQw4w9WgXcQ]_ytmusic.crestvid.part",
+            "Song [dQw4w9WgXcQ]_ytmusic.crestvid.part.lyrics.vtt",
+        ] {
+            assert_eq!(partial_owner(&Path::new("/music").join(name)), owner);
+        }
+        assert_eq!(partial_owner(Path::new("/music/Song.mp3")), None);
+        assert_eq!(partial_owner(Path::new("/music/Song.crestvid")), None);
+        assert_eq!(partial_owner(Path::new("/music/.mp3.part")), None);
+        assert_eq!(partial_owner(Path::new("/music/Song.wav.part")), None);
+    }
+}
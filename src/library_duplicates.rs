@@ -0,0 +1,392 @@
+use crate::app::App;
+use crate::security::{bounded_output, external_command};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+/// Two uploads of one song rarely differ by more than a few seconds.
+const DURATION_TOLERANCE: Duration = Duration::from_secs(3);
+/// Only the opening of each song is fingerprinted.
+const FINGERPRINT_SECONDS: &str = "120";
+const MAX_FINGERPRINT_BYTES: usize = 64 * 1024;
+/// Chromaprint covers about eight frames per second; small offsets absorb
+/// different amounts of leading silence.
+const MAX_FINGERPRINT_OFFSET: usize = 16;
+/// Share of matching fingerprint bits above which two recordings are taken to
+/// be the same. Unrelated songs agree on about half of them.
+const MIN_FINGERPRINT_SIMILARITY: f64 = 0.85;
+
+/// Bracketed title parts that describe the upload rather than the song.
+const TITLE_NOISE: [&str; 13] = [
+    "official",
+    "video",
+    "audio",
+    "lyric",
+    "lyrics",
+    "hd",
+    "hq",
+    "4k",
+    "visualizer",
+    "visualiser",
+    "mv",
+    "m/v",
+    "explicit",
+];
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum MatchReason {
+    VideoId,
+    Title,
+    Audio,
+}
+
+impl MatchReason {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::VideoId => "same video",
+            Self::Title => "same title and length",
+            Self::Audio => "same audio",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DuplicateTrack {
+    pub title: String,
+    pub path: String,
+    /// The song and its video cache together.
+    pub bytes: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DuplicateGroup {
+    pub reasons: Vec<MatchReason>,
+    pub tracks: Vec<DuplicateTrack>,
+}
+
+/// The duplicate comparison view: one group at a time, with a track chosen
+/// to keep.
+pub struct DuplicateView {
+    pub groups: Vec<DuplicateGroup>,
+    pub group: usize,
+    pub track: usize,
+}
+
+impl DuplicateView {
+    pub fn current(&self) -> Option<&DuplicateGroup> {
+        self.groups.get(self.group)
+    }
+}
+
+/// Fold case, drop bracketed upload notes such as "(Official Video)", and
+/// keep only letters and digits.
+pub fn normalized_title(title: &str) -> String {
+    let mut kept = String::new();
+    let mut rest = title;
+    while let Some(start) = rest.find(['(', '[', '{']) {
+        kept.push_str(&rest[..start]);
+        let close = match rest.as_bytes()[start] {
+            b'(' => ')',
+            b'[' => ']',
+            _ => '}',
+        };
+        let Some(length) = rest[start + 1..].find(close) else {
+            rest = &rest[start + 1..];
+            continue;
+        };
+        let inside = rest[start + 1..start + 1 + length].to_lowercase();
+        let noise = inside
+            .split(|character: char| character.is_whitespace() || character == '-')
+            .any(|word| TITLE_NOISE.contains(&word));
+        if !noise {
+            kept.push(' ');
+            kept.push_str(&inside);
+        }
+        rest = &rest[start + 2 + length..];
+    }
+    kept.push_str(rest);
+    kept.to_lowercase()
+        .split(|character: char| !character.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Disjoint sets over library indices, remembering why sets were joined.
+struct Groups {
+    parent: Vec<usize>,
+    reasons: Vec<(usize, usize, MatchReason)>,
+}
+
+impl Groups {
+    fn new(count: usize) -> Self {
+        Self {
+            parent: (0..count).collect(),
+            reasons: Vec::new(),
+        }
+    }
+
+    fn root(&mut self, mut index: usize) -> usize {
+        while self.parent[index] != index {
+            self.parent[index] = self.parent[self.parent[index]];
+            index = self.parent[index];
+        }
+        index
+    }
+
+    fn join(&mut self, left: usize, right: usize, reason: MatchReason) {
+        let (left_root, right_root) = (self.root(left), self.root(right));
+        if left_root != right_root {
+            self.parent[right_root] = left_root;
+        }
+        self.reasons.push((left, right, reason));
+    }
+}
+
+fn close_durations(left: Option<Duration>, right: Option<Duration>) -> bool {
+    match (left, right) {
+        (Some(left), Some(right)) => left.abs_diff(right) <= DURATION_TOLERANCE,
+        // Without a length the title alone has to decide.
+        _ => true,
+    }
+}
+
+/// Library tracks that look like copies of each other. `audio_matches` holds
+/// pairs of paths whose fingerprints agree.
+pub fn find_duplicates(app: &App, audio_matches: &[(String, String)]) -> Vec<DuplicateGroup> {
+    let mut groups = Groups::new(app.library.len());
+    let index_of: HashMap<&str, usize> = app
+        .library
+        .iter()
+        .enumerate()
+        .map(|(index, (_, path))| (path.as_str(), index))
+        .collect();
+    let mut by_video: HashMap<&str, usize> = HashMap::new();
+    let mut by_title: HashMap<String, Vec<usize>> = HashMap::new();
+    for (index, (title, path)) in app.library.iter().enumerate() {
+        let metadata = app.track_metadata(path);
+        if let Some(video_id) = metadata.and_then(|metadata| metadata.video_id.as_deref()) {
+            match by_video.get(video_id) {
+                Some(&first) => groups.join(first, index, MatchReason::VideoId),
+                None => {
+                    by_video.insert(video_id, index);
+                }
+            }
+        }
+        let normalized = normalized_title(title);
+        if !normalized.is_empty() {
+            by_title.entry(normalized).or_default().push(index);
+        }
+    }
+    let duration = |index: usize| {
+        app.track_metadata(&app.library[index].1)
+            .and_then(|metadata| metadata.duration)
+    };
+    for indices in by_title.values() {
+        for (position, &left) in indices.iter().enumerate() {
+            for &right in &indices[position + 1..] {
+                if close_durations(duration(left), duration(right)) {
+                    groups.join(left, right, MatchReason::Title);
+                }
+            }
+        }
+    }
+    for (left, right) in audio_matches {
+        if let (Some(&left), Some(&right)) =
+            (index_of.get(left.as_str()), index_of.get(right.as_str()))
+        {
+            groups.join(left, right, MatchReason::Audio);
+        }
+    }
+
+    let mut members: HashMap<usize, Vec<usize>> = HashMap::new();
+    for index in 0..app.library.len() {
+        let root = groups.root(index);
+        members.entry(root).or_default().push(index);
+    }
+    let mut reasons: HashMap<usize, Vec<MatchReason>> = HashMap::new();
+    for (left, _, reason) in std::mem::take(&mut groups.reasons) {
+        let root = groups.root(left);
+        let entry = reasons.entry(root).or_default();
+        if !entry.contains(&reason) {
+            entry.push(reason);
+        }
+    }
+    let mut duplicates: Vec<DuplicateGroup> = members
+        .into_iter()
+        .filter(|(_, indices)| indices.len() > 1)
+        .map(|(root, indices)| {
+            let mut reasons = reasons.remove(&root).unwrap_or_default();
+            reasons.sort();
+            DuplicateGroup {
+                reasons,
+                tracks: indices
+                    .into_iter()
+                    .map(|index| {
+                        let (title, path) = &app.library[index];
+                        DuplicateTrack {
+                            title: title.clone(),
+                            path: path.clone(),
+                            bytes: track_bytes(app, path),
+                        }
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+    // Library order keeps the oldest copy first in every group.
+    duplicates.sort_by_key(|group| index_of.get(group.tracks[0].path.as_str()).copied());
+    duplicates
+}
+
+fn track_bytes(app: &App, path: &str) -> u64 {
+    let size = |path: &Path| path.metadata().map_or(0, |metadata| metadata.len());
+    let file = Path::new(path);
+    let cache = if app.is_imported_track(path) {
+        0
+    } else {
+        size(&file.with_extension("crestvid"))
+    };
+    size(file).saturating_add(cache)
+}
+
+/// Available tracks worth fingerprinting: those with another track of about
+/// the same length.
+pub fn fingerprint_candidates(app: &App) -> Vec<(String, Duration)> {
+    let mut tracks: Vec<(String, Duration)> = app
+        .library
+        .iter()
+        .filter(|(_, path)| app.is_library_file_available(path))
+        .filter_map(|(_, path)| {
+            let duration = app.track_metadata(path)?.duration?;
+            Some((path.clone(), duration))
+        })
+        .collect();
+    tracks.sort_by_key(|(_, duration)| *duration);
+    let close =
+        |left: usize, right: usize| tracks[left].1.abs_diff(tracks[right].1) <= DURATION_TOLERANCE;
+    (0..tracks.len())
+        .filter(|&index| {
+            (index > 0 && close(index - 1, index))
+                || (index + 1 < tracks.len() && close(index, index + 1))
+        })
+        .map(|index| tracks[index].clone())
+        .collect()
+}
+
+/// Fingerprint `candidates` with FFmpeg's Chromaprint muxer and report the
+/// pairs of paths that sound the same.
+pub fn fingerprint_in_background(
+    candidates: Vec<(String, Duration)>,
+) -> Receiver<Result<Vec<(String, String)>, String>> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut fingerprints = Vec::new();
+        for (path, duration) in candidates {
+            match fingerprint(Path::new(&path)) {
+                Ok(points) => fingerprints.push((path, duration, points)),
+                Err(error) if fingerprints.is_empty() => {
+                    let _ = sender.send(Err(error));
+                    return;
+                }
+                // One unreadable file does not stop the others.
+                Err(_) => {}
+            }
+        }
+        let mut matches = Vec::new();
+        for (position, (left, left_duration, left_points)) in fingerprints.iter().enumerate() {
+            for (right, right_duration, right_points) in &fingerprints[position + 1..] {
+                if right_duration.abs_diff(*left_duration) > DURATION_TOLERANCE {
+                    break;
+                }
+                if fingerprint_similarity(left_points, right_points) >= MIN_FINGERPRINT_SIMILARITY {
+                    matches.push((left.clone(), right.clone()));
+                }
+            }
+        }
+        let _ = sender.send(Ok(matches));
+    });
+    receiver
+}
+
+fn fingerprint(path: &Path) -> Result<Vec<u32>, String> {
+    let mut command = external_command("ffmpeg");
+    command.args([
+        "-nostdin",
+        "-loglevel",
+        "error",
+        "-t",
+        FINGERPRINT_SECONDS,
+        "-i",
+    ]);
+    command.arg(path);
+    command.args(["-vn", "-f", "chromaprint", "-fp_format", "raw", "-"]);
+    let output = bounded_output(command, MAX_FINGERPRINT_BYTES)
+        .map_err(|error| format!("could not start FFmpeg: {error}"))?;
+    if !output.status.success() || output.stdout.len() < 4 {
+        return Err("audio fingerprints need FFmpeg built with Chromaprint".to_string());
+    }
+    Ok(output
+        .stdout
+        .chunks_exact(4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect())
+}
+
+/// The best share of equal bits over small alignments of two fingerprints.
+fn fingerprint_similarity(left: &[u32], right: &[u32]) -> f64 {
+    let compare = |left: &[u32], right: &[u32]| {
+        let length = left.len().min(right.len());
+        if length == 0 {
+            return 0.0;
+        }
+        let differing: u32 = left
+            .iter()
+            .zip(right)
+            .map(|(left, right)| (left ^ right).count_ones())
+            .sum();
+        1.0 - f64::from(differing) / (length as f64 * 32.0)
+    };
+    (0..=MAX_FINGERPRINT_OFFSET)
+        .flat_map(|offset| {
+            [
+                compare(left.get(offset..).unwrap_or_default(), right),
+                compare(left, right.get(offset..).unwrap_or_default()),
+            ]
+        })
+        .fold(0.0, f64::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fingerprint_similarity, normalized_title};
+
+    #[test]
+    fn titles_ignore_upload_notes_case_and_punctuation() {
+        assert_eq!(
+            normalized_title("Rick Astley - Never Gonna Give You Up (Official Music Video)"),
+            "rick astley never gonna give you up"
+        );
+        assert_eq!(
+            normalized_title("rick astley: never gonna give you up [4K Remaster] (Lyrics)"),
+            "rick astley never gonna give you up"
+        );
+        assert_eq!(
+            normalized_title("Song (Live at Wembley)"),
+            "song live at wembley"
+        );
+        assert_eq!(normalized_title("Broken (bracket"), "broken bracket");
+    }
+
+    #[test]
+    fn fingerprints_match_despite_a_small_offset() {
+        let song: Vec<u32> = (0..200u32)
+            .map(|index| index.wrapping_mul(2_654_435_761))
+            .collect();
+        let shifted = [vec![0; 5], song.clone()].concat();
+        assert!(fingerprint_similarity(&song, &shifted) > 0.99);
+        let other: Vec<u32> = song.iter().map(|point| point.rotate_left(7)).collect();
+        assert!(fingerprint_similarity(&song, &other) < 0.85);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::app::App;
+use std::cmp::Ordering;
+
+/// Orders for the Downloaded Music Only list.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LibrarySort {
+    /// Oldest first, which is also the order of the library index.
+    #[default]
+    Added,
+    Title,
+    Artist,
+    /// Most played first, counting tracks played to their end.
+    Plays,
+    /// Shortest first.
+    Duration,
+}
+
+impl LibrarySort {
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Title => "title",
+            Self::Artist => "artist",
+            Self::Plays => "plays",
+            Self::Duration => "duration",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "added" | "date" => Some(Self::Added),
+            "title" | "name" => Some(Self::Title),
+            "artist" => Some(Self::Artist),
+            "plays" | "played" => Some(Self::Plays),
+            "duration" | "length" => Some(Self::Duration),
+            _ => None,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::Added => Self::Title,
+            Self::Title => Self::Artist,
+            Self::Artist => Self::Plays,
+            Self::Plays => Self::Duration,
+            Self::Duration => Self::Added,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Added => "date added",
+            Self::Title => "title",
+            Self::Artist => "artist",
+            Self::Plays => "play count",
+            Self::Duration => "duration",
+        }
+    }
+}
+
+/// The command bar holds a filter unless it starts a `:` command.
+pub fn filter_text(input: &str) -> &str {
+    if input.starts_with(':') { "" } else { input }
+}
+
+/// A case-insensitive fuzzy query. Every whitespace-separated word must
+/// appear, in order but not necessarily contiguously, in the title, artist
+/// or album.
+pub struct LibraryQuery {
+    words: Vec<Vec<char>>,
+}
+
+impl LibraryQuery {
+    pub fn new(text: &str) -> Self {
+        Self {
+            words: text
+                .split_whitespace()
+                .map(|word| word.chars().map(fold).collect())
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn matches(&self, app: &App, title: &str, path: &str) -> bool {
+        let metadata = app.track_metadata(path);
+        let artist = metadata.and_then(|metadata| metadata.artist.as_deref());
+        let album = metadata.and_then(|metadata| metadata.album.as_deref());
+        self.words.iter().all(|word| {
+            [Some(title), artist, album]
+                .into_iter()
+                .flatten()
+                .any(|field| is_subsequence(word, field))
+        })
+    }
+
+    /// Character positions in `text` to highlight, one flag per `char`.
+    pub fn highlights(&self, text: &str) -> Vec<bool> {
+        let mut marked = vec![false; text.chars().count()];
+        for word in &self.words {
+            let mut wanted = word.iter().peekable();
+            let mut positions = Vec::with_capacity(word.len());
+            for (index, character) in text.chars().enumerate() {
+                if wanted.peek() == Some(&&fold(character)) {
+                    wanted.next();
+                    positions.push(index);
+                }
+            }
+            if wanted.peek().is_none() {
+                for index in positions {
+                    marked[index] = true;
+                }
+            }
+        }
+        marked
+    }
+}
+
+fn fold(character: char) -> char {
+    character.to_lowercase().next().unwrap_or(character)
+}
+
+fn is_subsequence(word: &[char], text: &str) -> bool {
+    let mut wanted = word.iter();
+    let mut next = wanted.next();
+    for character in text.chars() {
+        match next {
+            Some(expected) if *expected == fold(character) => next = wanted.next(),
+            Some(_) => {}
+            None => break,
+        }
+    }
+    next.is_none()
+}
+
+/// The library, or the smart playlist being shown, filtered by `query` and
+/// ordered by `sort`.
+pub fn library_view(app: &App, query: &LibraryQuery, sort: LibrarySort) -> Vec<(String, String)> {
+    let smart = app
+        .smart_view
+        .as_deref()
+        .and_then(|name| app.smart_playlists.find(name));
+    let mut tracks: Vec<(String, String)> = match smart {
+        Some(playlist) => {
+            let mut tracks = playlist.tracks(app);
+            narrow_view(app, query, &mut tracks);
+            tracks
+        }
+        None => app
+            .library
+            .iter()
+            .filter(|(title, path)| query.is_empty() || query.matches(app, title, path))
+            .cloned()
+            .collect(),
+    };
+    sort_tracks(app, &mut tracks, sort);
+    tracks
+}
+
+/// Typing another character only narrows the query, so the already sorted
+/// results are filtered again instead of the whole library.
+pub fn narrow_view(app: &App, query: &LibraryQuery, tracks: &mut Vec<(String, String)>) {
+    tracks.retain(|(title, path)| query.matches(app, title, path));
+}
+
+fn sort_tracks(app: &App, tracks: &mut [(String, String)], sort: LibrarySort) {
+    let metadata = |path: &str| app.track_metadata(path);
+    match sort {
+        LibrarySort::Added => {
+            tracks.sort_by_key(|(_, path)| metadata(path).and_then(|metadata| metadata.added_at))
+        }
+        LibrarySort::Title => tracks.sort_by_cached_key(|(title, _)| title.to_lowercase()),
+        LibrarySort::Artist => tracks.sort_by_cached_key(|(title, path)| {
+            let artist = metadata(path)
+                .and_then(|metadata| metadata.artist.as_deref())
+                .map(str::to_lowercase);
+            // Songs without an artist go last.
+            (artist.is_none(), artist, title.to_lowercase())
+        }),
+        LibrarySort::Plays => tracks.sort_by_key(|(_, path)| {
+            std::cmp::Reverse(app.track_stats(path).map_or(0, |stats| stats.plays))
+        }),
+        LibrarySort::Duration => tracks.sort_by(|(_, left), (_, right)| {
+            let duration = |path: &str| metadata(path).and_then(|metadata| metadata.duration);
+            match (duration(left), duration(right)) {
+                (Some(left), Some(right)) => left.cmp(&right),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LibraryQuery, LibrarySort, is_subsequence};
+
+    #[test]
+    fn words_match_as_case_insensitive_subsequences() {
+        let query = LibraryQuery::new("NvGnA  rick");
+        assert!(is_subsequence(&query.words[0], "Never Gonna Give You Up"));
+        assert!(is_subsequence(&query.words[1], "Rick Astley"));
+        assert!(!is_subsequence(&query.words[1], "Astley"));
+        let marked = LibraryQuery::new("ngn").highlights("Never Gonna");
+        let positions: Vec<_> = (0..marked.len()).filter(|index| marked[*index]).collect();
+        assert_eq!(positions, [0, 6, 8]);
+        assert!(
+            LibraryQuery::new("xyz")
+                .highlights("Never")
+                .iter()
+                .all(|marked| !marked)
+        );
+    }
+
+    #[test]
+    fn sort_orders_round_trip_and_cycle() {
+        let mut sort = LibrarySort::default();
+        for _ in 0..5 {
+            assert_eq!(LibrarySort::from_id(sort.id()), Some(sort));
+            sort = sort.next();
+        }
+        assert_eq!(sort, LibrarySort::default());
+    }
+}
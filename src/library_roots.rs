@@ -0,0 +1,269 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Operating system folders that never hold a music library. Media can be
+/// deleted from a root, so neither they nor anything inside them is accepted.
+#[cfg(unix)]
+const SYSTEM_FOLDERS: [&str; 12] = [
+    "/bin", "/boot", "/dev", "/etc", "/lib", "/lib64", "/proc", "/run", "/sbin", "/sys", "/usr",
+    "/System",
+];
+#[cfg(windows)]
+const SYSTEM_FOLDERS: [&str; 3] = [
+    r"C:\Windows",
+    r"C:\Program Files",
+    r"C:\Program Files (x86)",
+];
+#[cfg(not(any(unix, windows)))]
+const SYSTEM_FOLDERS: [&str; 0] = [];
+
+/// Directories Crest Player downloads into and may delete media from. The
+/// Music directory is always the first root and keeps the library index;
+/// further roots are typically external disks or network mounts.
+#[derive(Clone, Debug, Default)]
+pub struct LibraryRoots {
+    extra: Vec<PathBuf>,
+    /// `None` downloads into the Music directory.
+    download: Option<PathBuf>,
+    offline: HashSet<PathBuf>,
+}
+
+impl LibraryRoots {
+    pub fn new(extra: &[String], download: Option<&str>) -> Self {
+        let extra: Vec<PathBuf> = extra.iter().map(PathBuf::from).collect();
+        let download = download
+            .map(PathBuf::from)
+            .filter(|download| extra.contains(download));
+        let mut roots = Self {
+            extra,
+            download,
+            offline: HashSet::new(),
+        };
+        roots.refresh();
+        roots
+    }
+
+    /// Every root, the Music directory first, whether or not it is mounted.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        dirs::audio_dir()
+            .into_iter()
+            .chain(self.extra.iter().cloned())
+            .collect()
+    }
+
+    /// The roots that are currently mounted.
+    pub fn online(&self) -> Vec<PathBuf> {
+        self.paths()
+            .into_iter()
+            .filter(|root| !self.offline.contains(root))
+            .collect()
+    }
+
+    /// Roots other than the Music directory, as stored in the settings.
+    pub fn extra(&self) -> Vec<String> {
+        self.extra
+            .iter()
+            .map(|root| root.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    pub fn download_setting(&self) -> Option<String> {
+        self.download
+            .as_ref()
+            .map(|root| root.to_string_lossy().into_owned())
+    }
+
+    /// Where new downloads go. An offline download root is an error rather
+    /// than a silent fallback, so songs do not end up on the wrong disk.
+    pub fn download_dir(&self) -> Result<PathBuf, String> {
+        match &self.download {
+            Some(root) if self.offline.contains(root) || !root.is_dir() => Err(format!(
+                "The download location {} is offline.",
+                root.display()
+            )),
+            Some(root) => Ok(root.clone()),
+            None => {
+                dirs::audio_dir().ok_or_else(|| "The Music directory is unavailable.".to_string())
+            }
+        }
+    }
+
+    pub fn is_download_root(&self, root: &Path) -> bool {
+        match &self.download {
+            Some(download) => download == root,
+            None => dirs::audio_dir().as_deref() == Some(root),
+        }
+    }
+
+    pub fn is_offline(&self, root: &Path) -> bool {
+        self.offline.contains(root)
+    }
+
+    /// Recheck which extra roots are mounted. Returns whether any changed.
+    pub fn refresh(&mut self) -> bool {
+        let offline: HashSet<PathBuf> = self
+            .extra
+            .iter()
+            .filter(|root| !root.is_dir())
+            .cloned()
+            .collect();
+        let changed = offline != self.offline;
+        self.offline = offline;
+        changed
+    }
+
+    /// The root a library path was downloaded into, judged by its text so it
+    /// also works while the root is unmounted.
+    pub fn root_of(&self, path: &str) -> Option<PathBuf> {
+        let parent = Path::new(path).parent()?;
+        self.paths().into_iter().find(|root| {
+            root == parent
+                || root
+                    .canonicalize()
+                    .is_ok_and(|canonical| canonical == parent)
+        })
+    }
+
+    /// Whether a song is missing only because its disk is not mounted.
+    pub fn is_offline_track(&self, path: &str) -> bool {
+        !self.offline.is_empty()
+            && self
+                .root_of(path)
+                .is_some_and(|root| self.offline.contains(&root))
+    }
+
+    pub fn add(&mut self, folder: &Path) -> Result<PathBuf, String> {
+        let root = folder
+            .canonicalize()
+            .ok()
+            .filter(|root| root.is_dir())
+            .ok_or_else(|| format!("{} is not a folder.", folder.display()))?;
+        if is_protected_folder(&root) {
+            return Err(format!(
+                "{} is a system or home folder and cannot be a library root.",
+                root.display()
+            ));
+        }
+        if self
+            .paths()
+            .iter()
+            .any(|known| *known == root || known.canonicalize().is_ok_and(|known| known == root))
+        {
+            return Err(format!("{} is already a library root.", root.display()));
+        }
+        self.extra.push(root.clone());
+        Ok(root)
+    }
+
+    /// Offline roots can be removed by the path they were added with.
+    pub fn remove(&mut self, folder: &Path) -> Result<PathBuf, String> {
+        let index = self.position(folder).ok_or_else(|| {
+            if dirs::audio_dir().as_deref() == Some(folder) {
+                "The Music directory is always a library root.".to_string()
+            } else {
+                format!("{} is not a library root.", folder.display())
+            }
+        })?;
+        let root = self.extra.remove(index);
+        self.offline.remove(&root);
+        if self.download.as_ref() == Some(&root) {
+            self.download = None;
+        }
+        Ok(root)
+    }
+
+    /// `None` moves downloads back to the Music directory.
+    pub fn set_download(&mut self, folder: Option<&Path>) -> Result<(), String> {
+        self.download = match folder {
+            Some(folder) => {
+                let index = self
+                    .position(folder)
+                    .ok_or_else(|| format!("{} is not a library root.", folder.display()))?;
+                Some(self.extra[index].clone())
+            }
+            None => None,
+        };
+        Ok(())
+    }
+
+    fn position(&self, folder: &Path) -> Option<usize> {
+        let canonical = folder.canonicalize().ok();
+        self.extra
+            .iter()
+            .position(|root| root == folder || canonical.as_ref() == Some(root))
+    }
+}
+
+/// The filesystem root, the home directory and the folders holding it, and
+/// the operating system's own folders.
+fn is_protected_folder(root: &Path) -> bool {
+    let contains_home = dirs::home_dir()
+        .and_then(|home| home.canonicalize().ok())
+        .is_some_and(|home| home.starts_with(root));
+    root.parent().is_none()
+        || contains_home
+        || SYSTEM_FOLDERS
+            .iter()
+            .filter_map(|folder| Path::new(folder).canonicalize().ok())
+            .any(|folder| root.starts_with(folder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LibraryRoots;
+    use std::path::Path;
+
+    #[test]
+    fn unmounted_roots_keep_their_tracks_offline() {
+        let base =
+            std::env::temp_dir().join(format!("crest-player-library-roots-{}", std::process::id()));
+        let disk = base.join("Disk");
+        std::fs::create_dir_all(&disk).unwrap();
+        let disk = disk.canonicalize().unwrap();
+        let nas = base.canonicalize().unwrap().join("Nas");
+        let mut roots = LibraryRoots::new(
+            &[
+                disk.to_string_lossy().into_owned(),
+                nas.to_string_lossy().into_owned(),
+            ],
+            Some(&nas.to_string_lossy()),
+        );
+
+        assert!(roots.is_offline(&nas));
+        assert!(!roots.is_offline(&disk));
+        assert!(roots.download_dir().is_err());
+        let song = nas.join("Song_ytmusic.mp3");
+        assert!(roots.is_offline_track(&song.to_string_lossy()));
+        assert!(!roots.is_offline_track(&disk.join("Song.mp3").to_string_lossy()));
+        assert!(!roots.is_offline_track(&nas.join("Sub/Song.mp3").to_string_lossy()));
+
+        std::fs::create_dir_all(&nas).unwrap();
+        assert!(roots.refresh());
+        assert_eq!(roots.download_dir(), Ok(nas.clone()));
+        assert!(!roots.is_offline_track(&song.to_string_lossy()));
+
+        assert!(roots.add(&disk).is_err());
+        assert!(roots.add(Path::new("/nonexistent/crest-player")).is_err());
+        assert_eq!(roots.remove(&nas), Ok(nas.clone()));
+        assert_eq!(roots.download_setting(), None);
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[test]
+    fn system_and_home_folders_are_refused_as_roots() {
+        let mut roots = LibraryRoots::new(&[], None);
+        if let Some(home) = dirs::home_dir().filter(|home| home.is_dir()) {
+            assert!(roots.add(&home).is_err());
+            if let Some(parent) = home.parent() {
+                assert!(roots.add(parent).is_err());
+            }
+        }
+        #[cfg(unix)]
+        {
+            assert!(roots.add(Path::new("/")).is_err());
+            assert!(roots.add(Path::new("/etc")).is_err());
+            assert!(roots.add(Path::new("/usr/share")).is_err());
+        }
+        assert!(roots.extra().is_empty());
+    }
+}
@@ -0,0 +1,378 @@
+use crate::security::{
+    MAX_METADATA_BYTES, bounded_output, external_command, sanitize_display_text_limited,
+};
+use crate::track_metadata::TrackMetadata;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+const SCAN_EXTENSIONS: [&str; 7] = ["flac", "ogg", "oga", "opus", "m4a", "wav", "mp3"];
+// Bounds a runaway walk, e.g. a folder setting that points at `/`.
+const MAX_SCANNED_FILES: usize = 200_000;
+
+pub struct ScannedTrack {
+    pub title: String,
+    pub path: String,
+    pub metadata: TrackMetadata,
+}
+
+/// A file the index already knows, with the size and modification time seen
+/// when it was last scanned.
+pub struct KnownFile {
+    pub file_size: Option<u64>,
+    pub modified_at: Option<u64>,
+}
+
+#[derive(Default)]
+pub struct ScanResult {
+    pub added: Vec<ScannedTrack>,
+    /// Files whose tags or contents changed since the last scan.
+    pub updated: Vec<ScannedTrack>,
+    /// `(old path, new path)` for files that were renamed or moved.
+    pub moved: Vec<(String, String)>,
+    pub removed: Vec<String>,
+    pub unreadable: usize,
+}
+
+impl ScanResult {
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "Library scan: {} added, {} updated, {} moved, {} removed",
+            self.added.len(),
+            self.updated.len(),
+            self.moved.len(),
+            self.removed.len()
+        );
+        if self.unreadable > 0 {
+            summary.push_str(&format!(", {} unreadable", self.unreadable));
+        }
+        summary + "."
+    }
+}
+
+/// Walk `folders` on a background thread. `known` holds the imported tracks
+/// already in the index; `skip` holds paths that belong to Crest downloads
+/// and imported files the user removed from the library.
+pub fn scan_in_background(
+    folders: Vec<PathBuf>,
+    known: HashMap<String, KnownFile>,
+    skip: HashSet<String>,
+) -> Receiver<ScanResult> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(scan(&folders, &known, &skip, probe_track));
+    });
+    receiver
+}
+
+/// Probe individual files the watcher saw appear, on a background thread.
+pub fn probe_in_background(paths: Vec<PathBuf>) -> Receiver<ScanResult> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut result = ScanResult::default();
+        for path in paths {
+            match path.canonicalize().ok().and_then(|path| probe_track(&path)) {
+                Some(track) => result.added.push(track),
+                None => result.unreadable += 1,
+            }
+        }
+        let _ = sender.send(result);
+    });
+    receiver
+}
+
+fn scan(
+    folders: &[PathBuf],
+    known: &HashMap<String, KnownFile>,
+    skip: &HashSet<String>,
+    probe: impl Fn(&Path) -> Option<ScannedTrack>,
+) -> ScanResult {
+    let mut files = Vec::new();
+    let mut visited = HashSet::new();
+    for folder in folders {
+        walk(folder, &mut files, &mut visited);
+    }
+    let mut result = ScanResult::default();
+    let mut seen = HashSet::new();
+    let mut candidates = Vec::new();
+    for path in files {
+        let path_string = path.to_string_lossy().into_owned();
+        if skip.contains(&path_string) || !seen.insert(path_string.clone()) {
+            continue;
+        }
+        let current = file_stamp(&path);
+        match known.get(&path_string) {
+            Some(file) if (file.file_size, file.modified_at) == current => {}
+            Some(_) => match probe(&path) {
+                Some(track) => result.updated.push(track),
+                None => result.unreadable += 1,
+            },
+            None => candidates.push((path, current)),
+        }
+    }
+    // A folder on an unplugged disk keeps its songs in the index; they are
+    // shown as offline until the disk returns.
+    let offline: Vec<&PathBuf> = folders.iter().filter(|folder| !folder.is_dir()).collect();
+    let mut missing: Vec<&String> = known
+        .keys()
+        .filter(|path| {
+            !seen.contains(*path)
+                && !offline
+                    .iter()
+                    .any(|folder| Path::new(path.as_str()).starts_with(folder))
+        })
+        .collect();
+    missing.sort();
+    for (path, stamp) in candidates {
+        // A moved file keeps its size and modification time, so its index
+        // entry, play statistics and loudness follow it instead of a re-import.
+        if let Some(index) = missing.iter().position(|old| {
+            let file = &known[*old];
+            stamp.0.is_some() && (file.file_size, file.modified_at) == stamp
+        }) {
+            let old = missing.remove(index);
+            result
+                .moved
+                .push((old.clone(), path.to_string_lossy().into_owned()));
+            continue;
+        }
+        match probe(&path) {
+            Some(track) => result.added.push(track),
+            None => result.unreadable += 1,
+        }
+    }
+    result.removed = missing.into_iter().cloned().collect();
+    result
+}
+
+fn walk(directory: &Path, files: &mut Vec<PathBuf>, visited: &mut HashSet<PathBuf>) {
+    // Canonical paths keep symlink loops and overlapping folders from being
+    // walked twice.
+    let Ok(directory) = directory.canonicalize() else {
+        return;
+    };
+    if !visited.insert(directory.clone()) {
+        return;
+    }
+    let Ok(entries) = std::fs::read_dir(&directory) else {
+        return;
+    };
+    let mut entries: Vec<_> = entries.flatten().map(|entry| entry.path()).collect();
+    entries.sort();
+    for path in entries {
+        if files.len() >= MAX_SCANNED_FILES {
+            return;
+        }
+        let hidden = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_none_or(|name| name.starts_with('.'));
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            walk(&path, files, visited);
+        } else if is_scannable(&path)
+            && let Ok(path) = path.canonicalize()
+        {
+            files.push(path);
+        }
+    }
+}
+
+pub fn is_scannable(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    // Streaming temp files and in-progress downloads are not collection files.
+    if name.contains("ytmusic_play_") {
+        return false;
+    }
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            SCAN_EXTENSIONS
+                .iter()
+                .any(|known| extension.eq_ignore_ascii_case(known))
+        })
+}
+
+fn file_stamp(path: &Path) -> (Option<u64>, Option<u64>) {
+    let mut metadata = TrackMetadata::default();
+    metadata.fill_from_file(path);
+    (metadata.file_size, metadata.modified_at)
+}
+
+fn probe_track(path: &Path) -> Option<ScannedTrack> {
+    let mut command = external_command("ffprobe");
+    command
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "a:0",
+            "-show_entries",
+            "format=duration:format_tags:stream=codec_type:stream_tags",
+            "-of",
+            "json",
+        ])
+        .arg(path);
+    let output = bounded_output(command, MAX_METADATA_BYTES).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let json = serde_json::from_slice::<serde_json::Value>(&output.stdout).ok()?;
+    track_from_probe(path, &json)
+}
+
+/// FLAC and MP3 keep tags on the container, while Ogg and Opus keep them on
+/// the audio stream; tag names are matched without regard to case.
+fn track_from_probe(path: &Path, json: &serde_json::Value) -> Option<ScannedTrack> {
+    let stream = json.get("streams")?.as_array()?.first()?;
+    if stream.get("codec_type")?.as_str()? != "audio" {
+        return None;
+    }
+    let format = json.get("format");
+    let tag = |name: &str| {
+        [
+            format.and_then(|format| format.get("tags")),
+            stream.get("tags"),
+        ]
+        .into_iter()
+        .flatten()
+        .filter_map(serde_json::Value::as_object)
+        .flat_map(|tags| tags.iter())
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .and_then(|(_, value)| value.as_str())
+        .map(|value| sanitize_display_text_limited(value.trim(), 256))
+        .filter(|value| !value.is_empty())
+    };
+    let title = tag("title").unwrap_or_else(|| {
+        path.file_stem()
+            .map(|stem| sanitize_display_text_limited(&stem.to_string_lossy(), 256))
+            .unwrap_or_default()
+    });
+    let mut metadata = TrackMetadata {
+        artist: tag("artist").or_else(|| tag("album_artist")),
+        album: tag("album"),
+        year: tag("date")
+            .or_else(|| tag("year"))
+            .and_then(|date| date.get(..4)?.parse().ok()),
+        duration: format
+            .and_then(|format| format.get("duration")?.as_str()?.parse::<f64>().ok())
+            .filter(|seconds| seconds.is_finite() && *seconds > 0.0)
+            .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok()),
+        imported: true,
+        ..TrackMetadata::default()
+    };
+    metadata.fill_from_file(path);
+    Some(ScannedTrack {
+        title,
+        path: path.to_string_lossy().into_owned(),
+        metadata,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KnownFile, ScannedTrack, file_stamp, scan, track_from_probe};
+    use crate::track_metadata::TrackMetadata;
+    use std::collections::{HashMap, HashSet};
+    use std::path::Path;
+
+    #[test]
+    fn reads_stream_tags_and_falls_back_to_the_file_name() {
+        let track = track_from_probe(
+            Path::new("/music/01 Intro.opus"),
+            &serde_json::json!({
+                "streams": [{"codec_type": "audio", "tags": {"ARTIST": "Band", "DATE": "2019-04-01"}}],
+                "format": {"duration": "61.500000", "tags": {"Album": "First"}},
+            }),
+        )
+        .unwrap();
+        assert_eq!(track.title, "01 Intro");
+        assert_eq!(track.metadata.artist.as_deref(), Some("Band"));
+        assert_eq!(track.metadata.album.as_deref(), Some("First"));
+        assert_eq!(track.metadata.year, Some(2019));
+        assert_eq!(track.metadata.duration.unwrap().as_millis(), 61_500);
+        assert!(track.metadata.imported);
+        assert!(
+            track_from_probe(
+                Path::new("/music/cover.m4a"),
+                &serde_json::json!({"streams": [{"codec_type": "video"}]}),
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn rescans_detect_added_moved_changed_and_removed_files() {
+        let root = std::env::temp_dir().join(format!("crest-player-scan-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("Album")).unwrap();
+        std::fs::create_dir_all(root.join(".hidden")).unwrap();
+        for (name, contents) in [
+            ("Album/kept.flac", "kept"),
+            ("Album/changed.ogg", "changed!"),
+            ("moved.m4a", "moved"),
+            ("new.wav", "brand new"),
+            ("notes.txt", "ignored"),
+            (".hidden/secret.mp3", "ignored"),
+        ] {
+            std::fs::write(root.join(name), contents).unwrap();
+        }
+        let root = root.canonicalize().unwrap();
+        let path = |name: &str| root.join(name).to_string_lossy().into_owned();
+        let stamp = |name: &str| {
+            let (file_size, modified_at) = file_stamp(&root.join(name));
+            KnownFile {
+                file_size,
+                modified_at,
+            }
+        };
+        let known = HashMap::from([
+            (path("Album/kept.flac"), stamp("Album/kept.flac")),
+            (
+                path("Album/changed.ogg"),
+                KnownFile {
+                    file_size: Some(1),
+                    modified_at: Some(1),
+                },
+            ),
+            (path("Old/moved.m4a"), stamp("moved.m4a")),
+            (
+                path("deleted.flac"),
+                KnownFile {
+                    file_size: Some(2),
+                    modified_at: Some(2),
+                },
+            ),
+            (
+                path("Unplugged/offline.flac"),
+                KnownFile {
+                    file_size: None,
+                    modified_at: None,
+                },
+            ),
+        ]);
+        let probe = |path: &Path| {
+            Some(ScannedTrack {
+                title: path.file_stem()?.to_string_lossy().into_owned(),
+                path: path.to_string_lossy().into_owned(),
+                metadata: TrackMetadata::default(),
+            })
+        };
+        let folders = [root.clone(), root.join("Unplugged")];
+        let result = scan(&folders, &known, &HashSet::new(), probe);
+        let titles = |tracks: &[ScannedTrack]| {
+            tracks
+                .iter()
+                .map(|track| track.title.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(titles(&result.added), ["new"]);
+        assert_eq!(titles(&result.updated), ["changed"]);
+        assert_eq!(result.moved, [(path("Old/moved.m4a"), path("moved.m4a"))]);
+        assert_eq!(result.removed, [path("deleted.flac")]);
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
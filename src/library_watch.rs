@@ -0,0 +1,231 @@
+use notify::event::{AccessKind, AccessMode, EventKind};
+use notify::{RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+/// A bulk copy emits an event per file and write; changes are handed over
+/// only after the directories have been quiet for this long.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(750);
+
+/// Another program writing for minutes would otherwise hold every change back
+/// until it finishes.
+const WATCH_MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// Mounting or unplugging a disk sends no event for the root itself, so
+/// library roots are polled this often.
+const ROOT_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Paths that changed during one burst of filesystem activity.
+#[derive(Debug, Default, PartialEq)]
+pub struct WatchBatch {
+    pub paths: HashSet<PathBuf>,
+    /// The kernel dropped events, so any file may have changed.
+    pub rescan: bool,
+}
+
+#[derive(Default)]
+struct Debounce {
+    pending: WatchBatch,
+    first_event: Option<Instant>,
+    last_event: Option<Instant>,
+}
+
+impl Debounce {
+    fn push(&mut self, paths: impl IntoIterator<Item = PathBuf>, rescan: bool, now: Instant) {
+        self.pending.paths.extend(paths);
+        self.pending.rescan |= rescan;
+        self.first_event.get_or_insert(now);
+        self.last_event = Some(now);
+    }
+
+    fn take_ready(&mut self, now: Instant) -> Option<WatchBatch> {
+        let quiet = now.saturating_duration_since(self.last_event?) >= WATCH_DEBOUNCE;
+        let overdue = self
+            .first_event
+            .is_some_and(|first| now.saturating_duration_since(first) >= WATCH_MAX_DELAY);
+        (quiet || overdue).then(|| {
+            self.first_event = None;
+            self.last_event = None;
+            std::mem::take(&mut self.pending)
+        })
+    }
+}
+
+/// Watches the library roots and the scanner's library folders (inotify on
+/// Linux) so files changed by other programs are noticed while Crest runs.
+pub struct LibraryWatcher {
+    _watcher: notify::RecommendedWatcher,
+    receiver: Receiver<notify::Result<notify::Event>>,
+    roots: Vec<PathBuf>,
+    folders: Vec<String>,
+    debounce: Debounce,
+    last_root_check: Instant,
+}
+
+impl LibraryWatcher {
+    /// Downloads sit directly in the mounted library `roots`; library folders
+    /// are watched recursively. Folders that cannot be watched are skipped.
+    pub fn start(roots: &[PathBuf], folders: &[String]) -> Option<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = sender.send(event);
+        })
+        .ok()?;
+        for root in roots {
+            let _ = watcher.watch(root, RecursiveMode::NonRecursive);
+        }
+        for folder in folders {
+            let _ = watcher.watch(PathBuf::from(folder).as_path(), RecursiveMode::Recursive);
+        }
+        Some(Self {
+            _watcher: watcher,
+            receiver,
+            roots: roots.to_vec(),
+            folders: folders.to_vec(),
+            debounce: Debounce::default(),
+            last_root_check: Instant::now(),
+        })
+    }
+
+    /// Whether this watcher was started for `roots` and `folders`.
+    pub fn watches(&self, roots: &[PathBuf], folders: &[String]) -> bool {
+        self.roots == roots && self.folders == folders
+    }
+
+    /// Whether it is time to check which library roots are mounted.
+    pub fn roots_due(&mut self) -> bool {
+        let due = self.last_root_check.elapsed() >= ROOT_CHECK_INTERVAL;
+        if due {
+            self.last_root_check = Instant::now();
+        }
+        due
+    }
+
+    /// The next debounced batch, once its burst has settled.
+    pub fn poll(&mut self) -> Option<WatchBatch> {
+        let now = Instant::now();
+        while let Ok(event) = self.receiver.try_recv() {
+            match event {
+                Ok(event) if event.need_rescan() => self.debounce.push([], true, now),
+                // Playback reads library files constantly; only finished
+                // writes matter among access events.
+                Ok(event) if is_ignored_access(&event.kind) => {}
+                Ok(event) => {
+                    // Downloads rewrite their temporary files for minutes and
+                    // would keep the burst from ever settling.
+                    let paths: Vec<_> = event
+                        .paths
+                        .into_iter()
+                        .filter(|path| !is_crest_temp_file(path))
+                        .collect();
+                    if !paths.is_empty() {
+                        self.debounce.push(paths, false, now);
+                    }
+                }
+                Err(_) => self.debounce.push([], true, now),
+            }
+        }
+        self.debounce.take_ready(now)
+    }
+
+    /// Hold a batch back until the caller can handle it.
+    pub fn requeue(&mut self, batch: WatchBatch) {
+        self.debounce
+            .push(batch.paths, batch.rescan, Instant::now());
+    }
+}
+
+fn is_ignored_access(kind: &EventKind) -> bool {
+    matches!(kind, EventKind::Access(access) if *access != AccessKind::Close(AccessMode::Write))
+}
+
+/// Partial downloads, the per-stream files yt-dlp writes beside a
+/// `.download.mkv` source, and streamed playback files.
+fn is_crest_temp_file(path: &Path) -> bool {
+    crate::library_check::partial_owner(path).is_some()
+        || path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| {
+                name.contains(".download.")
+                    || name.contains(".crestvid.part")
+                    || name.starts_with("ytmusic_play_")
+            })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Debounce, WATCH_DEBOUNCE, WATCH_MAX_DELAY, WatchBatch, is_crest_temp_file,
+        is_ignored_access,
+    };
+    use notify::event::{AccessKind, AccessMode, CreateKind, EventKind};
+    use std::collections::HashSet;
+    use std::path::Path;
+    use std::path::PathBuf;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn bursts_are_delivered_once_they_settle() {
+        let start = Instant::now();
+        let mut debounce = Debounce::default();
+        assert_eq!(debounce.take_ready(start), None);
+        debounce.push([PathBuf::from("a.flac")], false, start);
+        let later = start + WATCH_DEBOUNCE / 2;
+        debounce.push([PathBuf::from("b.flac")], false, later);
+        assert_eq!(debounce.take_ready(start + WATCH_DEBOUNCE), None);
+        assert_eq!(
+            debounce.take_ready(later + WATCH_DEBOUNCE + Duration::from_millis(1)),
+            Some(WatchBatch {
+                paths: HashSet::from([PathBuf::from("a.flac"), PathBuf::from("b.flac")]),
+                rescan: false,
+            })
+        );
+        assert_eq!(debounce.take_ready(later + WATCH_DEBOUNCE * 4), None);
+    }
+
+    #[test]
+    fn playback_reads_do_not_wake_the_library() {
+        assert!(is_ignored_access(&EventKind::Access(AccessKind::Close(
+            AccessMode::Read
+        ))));
+        assert!(!is_ignored_access(&EventKind::Access(AccessKind::Close(
+            AccessMode::Write
+        ))));
+        assert!(!is_ignored_access(&EventKind::Create(CreateKind::File)));
+    }
+
+    #[test]
+    fn downloads_in_progress_do_not_hold_changes_back() {
+        for name in [
+            "Song.download.mkv",
+            "Song.download.f251.webm.part",
+            "Song.crestvid.part",
+            "Song.opus.part",
+            "ytmusic_play_1_2.mp3",
+        ] {
+            assert!(
+                is_crest_temp_file(Path::new("/music").join(name).as_path()),
+                "{name}"
+            );
+        }
+        assert!(!is_crest_temp_file(Path::new("/music/Song.opus")));
+
+        let start = Instant::now();
+        let mut debounce = Debounce::default();
+        let mut now = start;
+        debounce.push([PathBuf::from("a.flac")], false, now);
+        while now < start + WATCH_MAX_DELAY {
+            assert_eq!(debounce.take_ready(now), None);
+            now += WATCH_DEBOUNCE / 2;
+            debounce.push([PathBuf::from("busy.flac")], false, now);
+        }
+        assert!(
+            debounce
+                .take_ready(now)
+                .is_some_and(|batch| batch.paths.len() == 2)
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use crate::security::{bounded_output, external_command, sanitize_display_text_limited};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+
+/// ReplayGain 2.0 reference level. EBU R128 measurements are converted to a
+/// gain relative to this target so quiet and loud tracks meet in the middle.
+pub const REFERENCE_LOUDNESS_LUFS: f64 = -18.0;
+const MINIMUM_GAIN_DB: f64 = -24.0;
+// Large boosts mostly amplify noise floors and clip on peaks.
+const MAXIMUM_GAIN_DB: f64 = 10.0;
+const MAX_ANALYSIS_OUTPUT_BYTES: usize = 8 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum NormalizationMode {
+    #[default]
+    Off,
+    Track,
+    Album,
+}
+
+impl NormalizationMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Track,
+            Self::Track => Self::Album,
+            Self::Album => Self::Off,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Off => "Loudness Normalization: OFF",
+            Self::Track => "Loudness Normalization: TRACK",
+            Self::Album => "Loudness Normalization: ALBUM",
+        }
+    }
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Track => "track",
+            Self::Album => "album",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Self {
+        match id {
+            "track" => Self::Track,
+            "album" => Self::Album,
+            _ => Self::Off,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ReplayGain {
+    pub track_gain: Option<f64>,
+    pub album_gain: Option<f64>,
+}
+
+impl ReplayGain {
+    /// Album mode falls back to the track value for singles and streams.
+    pub fn gain_db(self, mode: NormalizationMode) -> f64 {
+        match mode {
+            NormalizationMode::Off => 0.0,
+            NormalizationMode::Track => self.track_gain.unwrap_or(0.0),
+            NormalizationMode::Album => self.album_gain.or(self.track_gain).unwrap_or(0.0),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrackLoudness {
+    pub integrated_lufs: f64,
+    pub album: Option<String>,
+}
+
+impl TrackLoudness {
+    pub fn track_gain(&self) -> f64 {
+        gain_for_loudness(self.integrated_lufs)
+    }
+}
+
+pub fn gain_for_loudness(integrated_lufs: f64) -> f64 {
+    (REFERENCE_LOUDNESS_LUFS - integrated_lufs).clamp(MINIMUM_GAIN_DB, MAXIMUM_GAIN_DB)
+}
+
+/// Combine track measurements in the energy domain, as R128 does for programmes.
+pub fn album_gain(track_gains: &[f64]) -> Option<f64> {
+    if track_gains.is_empty() {
+        return None;
+    }
+    let mean_energy = track_gains
+        .iter()
+        .map(|gain| 10f64.powf((REFERENCE_LOUDNESS_LUFS - gain) / 10.0))
+        .sum::<f64>()
+        / track_gains.len() as f64;
+    Some(gain_for_loudness(10.0 * mean_energy.log10()))
+}
+
+pub fn analyze_track_loudness(path: &Path) -> Result<TrackLoudness, String> {
+    let path_text = path
+        .to_str()
+        .ok_or_else(|| "the audio path is not valid UTF-8".to_string())?;
+    let mut command = external_command("ffmpeg");
+    command.args([
+        "-nostdin",
+        "-v",
+        "error",
+        "-i",
+        path_text,
+        "-vn",
+        "-af",
+        "ebur128=metadata=1,ametadata=mode=print:key=lavfi.r128.I:file=-",
+        "-f",
+        "null",
+        "-",
+    ]);
+    let output = bounded_output(command, MAX_ANALYSIS_OUTPUT_BYTES)
+        .map_err(|error| format!("could not start loudness analysis: {error}"))?;
+    if !output.status.success() {
+        return Err(format!("loudness analysis exited with {}", output.status));
+    }
+    let integrated_lufs = parse_integrated_loudness(&String::from_utf8_lossy(&output.stdout))
+        .ok_or_else(|| "loudness analysis produced no measurement".to_string())?;
+    Ok(TrackLoudness {
+        integrated_lufs,
+        album: album_tag(path_text),
+    })
+}
+
+/// Measure tracks one at a time so a large library never competes with
+/// playback for more than one decoder.
+pub fn analyze_in_background(
+    paths: Vec<String>,
+) -> Receiver<(String, Result<TrackLoudness, String>)> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        for path in paths {
+            let result = analyze_track_loudness(Path::new(&path));
+            if sender.send((path, result)).is_err() {
+                return;
+            }
+        }
+    });
+    receiver
+}
+
+fn album_tag(path: &str) -> Option<String> {
+    let mut command = external_command("ffprobe");
+    command.args([
+        "-v",
+        "error",
+        "-show_entries",
+        "format_tags=album",
+        "-of",
+        "default=noprint_wrappers=1:nokey=1",
+        path,
+    ]);
+    let output = bounded_output(command, 4096).ok()?;
+    let album = sanitize_display_text_limited(String::from_utf8_lossy(&output.stdout).trim(), 256);
+    (output.status.success() && !album.is_empty()).then_some(album)
+}
+
+/// The running integrated value is printed after every analysis block; the
+/// final one covers the whole programme.
+fn parse_integrated_loudness(output: &str) -> Option<f64> {
+    output
+        .lines()
+        .rev()
+        .find_map(|line| line.trim().strip_prefix("lavfi.r128.I="))
+        .and_then(|value| value.parse::<f64>().ok())
+        .filter(|value| value.is_finite() && *value > -70.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NormalizationMode, ReplayGain, album_gain, gain_for_loudness};
+
+    #[test]
+    fn reads_the_final_integrated_loudness() {
+        let output = "frame:0 pts:0 pts_time:0\nlavfi.r128.I=-70.000\nframe:1 pts:4800 pts_time:0.1\nlavfi.r128.I=-11.250\n";
+        assert_eq!(super::parse_integrated_loudness(output), Some(-11.25));
+        assert_eq!(
+            super::parse_integrated_loudness("lavfi.r128.I=-inf\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn gains_are_relative_to_the_reference_and_bounded() {
+        assert_eq!(gain_for_loudness(-8.0), -10.0);
+        assert_eq!(gain_for_loudness(-60.0), 10.0);
+        let album = album_gain(&[-4.0, -4.0]).unwrap();
+        assert!((album + 4.0).abs() < 1e-9);
+        // A louder track dominates the energy average.
+        assert!(album_gain(&[-10.0, 0.0]).unwrap() < -5.0);
+    }
+
+    #[test]
+    fn album_mode_falls_back_to_track_gain() {
+        let gain = ReplayGain {
+            track_gain: Some(-3.0),
+            album_gain: None,
+        };
+        assert_eq!(gain.gain_db(NormalizationMode::Off), 0.0);
+        assert_eq!(gain.gain_db(NormalizationMode::Album), -3.0);
+        assert_eq!(NormalizationMode::Album.next(), NormalizationMode::Off);
+    }
+}
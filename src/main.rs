@@ -1,20 +1,41 @@
+mod album_art;
 mod app;
 #[cfg(feature = "casting")]
 mod casting;
 mod desktop_integration;
 mod discord_presence;
 mod download_commands;
+mod download_format;
 mod download_manager;
+mod download_progress;
 mod download_queue_ui;
 mod draw_startup_screen;
+mod duplicates_ui;
+mod history;
+mod history_ui;
 mod idle_mode;
+mod library_archive;
+mod library_check;
+mod library_duplicates;
+mod library_filter;
+mod library_roots;
+mod library_scan;
+mod library_watch;
+mod loudness;
 mod lyrics;
 mod party_server;
+mod pending_downloads;
 mod player;
+mod playlists;
+mod playlists_ui;
 mod recommendations;
 mod search;
 mod security;
+mod session;
+mod smart_playlists;
 mod storage;
+mod tagging;
+mod track_metadata;
 mod ui_downloaded_only;
 mod ui_with_player;
 mod uninstall;
@@ -22,7 +43,8 @@ mod video_cache;
 mod video_screensaver;
 mod wallpaper;
 
-use app::{App, save_library, save_settings};
+use album_art::{AlbumArtLoader, ArtRequest};
+use app::{App, DownloadState, save_library, save_settings};
 #[cfg(feature = "casting")]
 use casting::CastCommand;
 use crossterm::{
@@ -37,22 +59,42 @@ use crossterm::{
     },
 };
 use discord_presence::DiscordPresence;
-use download_commands::DownloadCommand;
-use download_manager::{DownloadEvent as LibraryDownloadEvent, DownloadManager, DownloadRequest};
+use download_commands::{
+    DownloadCommand, PlaybackModeCommand, PlaylistCommand, RootCommand, ScanCommand, SmartCommand,
+    SortCommand, TrackCommand,
+};
+use download_format::AUDIO_EXTENSIONS;
+use download_manager::{
+    DownloadEvent as LibraryDownloadEvent, DownloadLimits, DownloadManager, DownloadRequest,
+    MAX_STAGE_WORKERS,
+};
 use draw_startup_screen::{
     DELETE_MEDIA_SETTING, HOME_OPTION_COUNT, REMOVE_APPLICATION_SETTING, RESET_WALLPAPER_SETTING,
     SETTINGS_OPTION_COUNT, StartupScreenState, draw_startup_screen,
 };
+use history::HistoryEntry;
 use idle_mode::{IdleMode, IdleRenderState, draw_idle_mode};
+use library_check::{CacheRebuildJob, check_library, repair_library};
+use library_duplicates::{
+    DuplicateView, find_duplicates, fingerprint_candidates, fingerprint_in_background,
+};
+use library_filter::{LibraryQuery, filter_text, library_view, narrow_view};
+use library_scan::{KnownFile, ScanResult};
+use library_watch::LibraryWatcher;
+use loudness::TrackLoudness;
 use lyrics::{Lyrics, fetch_lyrics_with_caption_fallback};
-use player::Player;
+use pending_downloads::{PendingDownload, PendingDownloads};
+use player::{Player, RepeatMode};
+use playlists::{PlaylistEntry, export_playlist, import_playlist};
 use ratatui::Terminal;
 use ratatui::prelude::CrosstermBackend;
 use recommendations::{Recommendation, youtube_mix_recommendation};
-use search::{playable_audio_file, search_youtube};
+use search::{playable_audio_file, remove_partial_download, search_youtube};
 use security::{contained_media_path, external_command, valid_youtube_id};
+use session::{SESSION_SAVE_INTERVAL, Session};
 use std::io::{self, BufWriter, Write};
 use std::time::{Duration, Instant};
+use tagging::{RetagJob, video_id_from_filename};
 use ui_with_player::ui_with_player;
 use video_screensaver::VideoScreensaver;
 use wallpaper::HomeWallpaper;
@@ -80,6 +122,803 @@ fn handle_cast_command(input: &str, player: &mut Player) -> Option<String> {
     }
 }
 
+/// Handle the local output level shortcuts shared by every playback view.
+fn handle_volume_key(key: &crossterm::event::KeyEvent, app: &mut App, player: &mut Player) -> bool {
+    if !key.modifiers.contains(crossterm::event::KeyModifiers::ALT) {
+        return false;
+    }
+    let message = match key.code {
+        KeyCode::Up => player.adjust_volume(5),
+        KeyCode::Down => player.adjust_volume(-5),
+        KeyCode::Char('m') | KeyCode::Char('M') => player.toggle_mute(),
+        _ => return false,
+    };
+    app.volume = player.volume();
+    app.muted = player.is_muted();
+    app.error = Some(message);
+    save_settings(app);
+    true
+}
+
+/// Alt+R cycles repeat modes and Alt+S toggles shuffle in every playback view.
+fn handle_playback_mode_key(
+    key: &crossterm::event::KeyEvent,
+    app: &mut App,
+    player: &mut Player,
+) -> bool {
+    if !key.modifiers.contains(crossterm::event::KeyModifiers::ALT) {
+        return false;
+    }
+    let command = match key.code {
+        KeyCode::Char('r') | KeyCode::Char('R') => PlaybackModeCommand::Repeat(None),
+        KeyCode::Char('s') | KeyCode::Char('S') => PlaybackModeCommand::Shuffle(None),
+        _ => return false,
+    };
+    app.error = Some(apply_playback_mode(command, app, player));
+    true
+}
+
+fn handle_playback_mode_command(input: &str, app: &mut App, player: &mut Player) -> Option<String> {
+    let command = PlaybackModeCommand::parse(input)?;
+    Some(apply_playback_mode(command, app, player))
+}
+
+fn apply_playback_mode(command: PlaybackModeCommand, app: &mut App, player: &mut Player) -> String {
+    match command {
+        PlaybackModeCommand::Repeat(mode) => {
+            app.repeat_mode = mode.unwrap_or_else(|| app.repeat_mode.next());
+        }
+        PlaybackModeCommand::Shuffle(enabled) => {
+            app.shuffle_enabled = enabled.unwrap_or(!app.shuffle_enabled);
+        }
+    }
+    player.set_playback_modes(app.repeat_mode, app.shuffle_enabled);
+    save_settings(app);
+    format!(
+        "{} · Shuffle {}",
+        app.repeat_mode.label(),
+        if app.shuffle_enabled { "on" } else { "off" }
+    )
+}
+
+// Ctrl+B restarts the current track after this point instead of going back.
+const PREVIOUS_RESTARTS_AFTER: Duration = Duration::from_secs(3);
+
+fn record_play_history(app: &mut App, player: &mut Player) -> bool {
+    let played = player.take_played();
+    if played.is_empty() {
+        return false;
+    }
+    let mut stats_changed = false;
+    for track in played {
+        stats_changed |= app.record_track_outcome(&track.path, track.outcome);
+        app.history.record(HistoryEntry::new(
+            &track.title,
+            &track.path,
+            track.video_id,
+            track.listened,
+        ));
+    }
+    app.history.save();
+    if stats_changed {
+        save_library(app);
+    }
+    true
+}
+
+/// Streamed entries can only be fetched again where streaming is allowed.
+fn history_entry_playable(
+    app: &App,
+    sender: Option<&std::sync::mpsc::Sender<DownloadFinished>>,
+    entry: &HistoryEntry,
+) -> bool {
+    app.is_library_file_available(&entry.path) || (sender.is_some() && entry.video_id.is_some())
+}
+
+fn play_history_entry(
+    app: &mut App,
+    player: &mut Player,
+    sender: Option<&std::sync::mpsc::Sender<DownloadFinished>>,
+    entry: &HistoryEntry,
+    now: bool,
+) -> bool {
+    if app.is_library_file_available(&entry.path) {
+        if now {
+            player.stop();
+            player.play(&entry.path, &entry.title);
+        } else {
+            player.queue.push((entry.title.clone(), entry.path.clone()));
+        }
+        return true;
+    }
+    let (Some(sender), Some(video_id)) = (sender, entry.video_id.as_deref()) else {
+        app.error = Some(format!("{} is not in the downloaded library.", entry.title));
+        return false;
+    };
+    if now {
+        player.stop();
+    }
+    let queued = player.queue.len();
+    queue_youtube_download(app, player, sender, &entry.title, video_id);
+    if now
+        && player.queue.len() > queued
+        && let Some(pending) = player.queue.pop()
+    {
+        player.queue.insert(0, pending);
+    }
+    true
+}
+
+fn play_previous(
+    app: &mut App,
+    player: &mut Player,
+    sender: Option<&std::sync::mpsc::Sender<DownloadFinished>>,
+) {
+    if player.title.is_some()
+        && player.status != "Downloading..."
+        && player.position() >= PREVIOUS_RESTARTS_AFTER
+    {
+        player.seek_by(-(player.position().as_secs() as i64) - 1);
+        return;
+    }
+    let Some(index) = app
+        .history
+        .iter()
+        .position(|entry| history_entry_playable(app, sender, entry))
+    else {
+        app.error = Some("No earlier track in the play history.".to_string());
+        return;
+    };
+    let Some(entry) = app.history.remove(index) else {
+        return;
+    };
+    app.history.save();
+    app.history_selected = app
+        .history_selected
+        .min(app.history.len().saturating_sub(1));
+    player.requeue_current();
+    play_history_entry(app, player, sender, &entry, true);
+}
+
+/// Keys for the Recently Played panel and previous-track navigation, shared by
+/// both list views.
+fn handle_history_key(
+    key: &crossterm::event::KeyEvent,
+    app: &mut App,
+    player: &mut Player,
+    sender: Option<&std::sync::mpsc::Sender<DownloadFinished>>,
+) -> bool {
+    let control = key
+        .modifiers
+        .contains(crossterm::event::KeyModifiers::CONTROL);
+    match key.code {
+        KeyCode::Char('b') if control => {
+            play_previous(app, player, sender);
+            app.lyrics_active = None;
+        }
+        KeyCode::Char('r') if control => {
+            app.show_history = !app.show_history;
+            app.history_selected = 0;
+            app.show_playlists = false;
+            app.queue_focused = false;
+        }
+        KeyCode::Down if key.modifiers.is_empty() && app.show_history => {
+            app.history_selected =
+                (app.history_selected + 1).min(app.history.len().saturating_sub(1));
+        }
+        KeyCode::Up if key.modifiers.is_empty() && app.show_history => {
+            app.history_selected = app.history_selected.saturating_sub(1);
+        }
+        KeyCode::Enter
+            if key.modifiers.is_empty() && app.show_history && app.input.trim().is_empty() =>
+        {
+            if let Some(entry) = app.history.get(app.history_selected).cloned() {
+                play_history_entry(app, player, sender, &entry, true);
+                app.lyrics_active = None;
+            }
+        }
+        KeyCode::Char('e') if control && app.show_history => {
+            if let Some(entry) = app.history.get(app.history_selected).cloned()
+                && play_history_entry(app, player, sender, &entry, false)
+            {
+                app.error = Some(format!("Queued {}.", entry.title));
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Keys without modifiers, other than Enter, in the streaming view's search
+/// box and lists. A key whose arm's condition does not hold is ignored.
+fn handle_search_view_key(code: KeyCode, app: &mut App) {
+    match code {
+        KeyCode::PageDown => {
+            app.lyrics_scroll = app.lyrics_scroll.saturating_add(5);
+        }
+        KeyCode::PageUp => {
+            app.lyrics_scroll = app.lyrics_scroll.saturating_sub(5);
+        }
+        KeyCode::Backspace if app.results.is_empty() && !app.searching => {
+            app.input.pop();
+            app.error = None;
+        }
+        // Special case: if user types exactly :library, show library in results
+        KeyCode::Char(c) if !app.searching && app.results.is_empty() => {
+            app.input.push(c);
+            if app.input == ":library" {
+                app.results = app.library.clone();
+                app.selected = 0;
+                app.show_library = false;
+                app.input.clear();
+            }
+        }
+        KeyCode::Delete if app.show_library && !app.library.is_empty() => {
+            let path = app.library[app.selected].1.clone();
+            if let Err(error) = app.remove_library_track(&path) {
+                app.error = Some(format!("Could not delete song: {error}"));
+            } else {
+                app.selected = app.selected.min(app.library.len().saturating_sub(1));
+                save_library(app);
+            }
+        }
+        // j/k navigation removed
+        KeyCode::Down => {
+            if app.show_library {
+                if !app.library.is_empty() {
+                    app.selected = (app.selected + 1).min(app.library.len() - 1);
+                }
+            } else if !app.results.is_empty() {
+                app.selected = (app.selected + 1).min(app.results.len() - 1);
+            }
+        }
+        KeyCode::Up => {
+            if app.show_library {
+                if !app.library.is_empty() && app.selected > 0 {
+                    app.selected -= 1;
+                }
+            } else if !app.results.is_empty() && app.selected > 0 {
+                app.selected -= 1;
+            }
+        }
+        KeyCode::Esc if !app.results.is_empty() => {
+            app.results.clear();
+            app.input.clear();
+            app.selected = 0;
+        }
+        _ => {}
+    }
+}
+
+/// Tab focuses the queue panel; while focused, keys edit the queue instead of
+/// the search or library list.
+fn handle_queue_key(key: &crossterm::event::KeyEvent, app: &mut App, player: &mut Player) -> bool {
+    if key.code == KeyCode::Tab && key.modifiers.is_empty() {
+        if app.queue_focused && app.has_active_downloads() {
+            app.downloads_focused = true;
+            app.download_selected = 0;
+        } else if app.downloads_focused {
+            app.downloads_focused = false;
+            return true;
+        }
+        app.queue_focused = !app.queue_focused;
+        app.show_history = false;
+        app.show_playlists = false;
+        app.show_library = false;
+        app.queue_selected = app.queue_selected.min(player.queue.len().saturating_sub(1));
+        return true;
+    }
+    if !app.queue_focused {
+        return false;
+    }
+    let shift = key
+        .modifiers
+        .contains(crossterm::event::KeyModifiers::SHIFT);
+    let selected = app.queue_selected;
+    match key.code {
+        KeyCode::Esc => app.queue_focused = false,
+        KeyCode::Up if shift => {
+            if let Some(index) = player.move_queue_entry(selected, -1) {
+                app.queue_selected = index;
+            }
+        }
+        KeyCode::Down if shift => {
+            if let Some(index) = player.move_queue_entry(selected, 1) {
+                app.queue_selected = index;
+            }
+        }
+        KeyCode::Up => app.queue_selected = selected.saturating_sub(1),
+        KeyCode::Down => {
+            app.queue_selected = (selected + 1).min(player.queue.len().saturating_sub(1));
+        }
+        KeyCode::Char('n') | KeyCode::Char('N')
+            if !key
+                .modifiers
+                .contains(crossterm::event::KeyModifiers::CONTROL) =>
+        {
+            if player.play_queue_entry_next(selected) {
+                app.queue_selected = 0;
+            }
+        }
+        KeyCode::Enter => {
+            if player.jump_to_queue_entry(selected) {
+                app.lyrics_active = None;
+                app.queue_selected = 0;
+            }
+        }
+        KeyCode::Delete | KeyCode::Backspace => {
+            if let Some((title, path)) = player.remove_queue_entry(selected) {
+                if title.ends_with(" (Downloading...)") {
+                    app.cancel_download(&path);
+                }
+                app.queue_selected = selected.min(player.queue.len().saturating_sub(1));
+            }
+        }
+        // Typing would otherwise leak into the hidden search field.
+        KeyCode::Char(_) if key.modifiers.is_empty() => {}
+        // Playback shortcuts keep working while the queue has focus.
+        _ => return false,
+    }
+    true
+}
+
+/// Keys for the focused Download Queue panel. They act on permanent
+/// downloads; temporary stream downloads are cancelled from the queue.
+fn handle_download_queue_key(
+    key: &crossterm::event::KeyEvent,
+    app: &mut App,
+    manager: &DownloadManager,
+) -> bool {
+    if !app.downloads_focused {
+        return false;
+    }
+    if !app.has_active_downloads() {
+        app.downloads_focused = false;
+        return false;
+    }
+    // Playback shortcuts keep working while the panel has focus.
+    if key
+        .modifiers
+        .intersects(crossterm::event::KeyModifiers::CONTROL | crossterm::event::KeyModifiers::ALT)
+    {
+        return false;
+    }
+    app.download_selected = app.download_selected.min(app.downloads.len() - 1);
+    let job = &app.downloads[app.download_selected];
+    let (path, title, state) = (job.path.clone(), job.title.clone(), job.state);
+    let permanent = job.permanent;
+    let acts_on_job = matches!(
+        key.code,
+        KeyCode::Delete | KeyCode::Backspace | KeyCode::Char('x' | 'X' | 'p' | 'P' | 'f' | 'F')
+    );
+    if acts_on_job && !permanent {
+        app.error = Some("Remove a streaming download from the queue to cancel it.".to_string());
+        return true;
+    }
+    match key.code {
+        KeyCode::Esc => app.downloads_focused = false,
+        KeyCode::Up => app.download_selected = app.download_selected.saturating_sub(1),
+        KeyCode::Down => {
+            app.download_selected = (app.download_selected + 1).min(app.downloads.len() - 1);
+        }
+        KeyCode::Delete | KeyCode::Backspace | KeyCode::Char('x' | 'X') => {
+            if manager.cancel(&path) {
+                app.cancel_download(&path);
+                app.download_selected = app
+                    .download_selected
+                    .min(app.downloads.len().saturating_sub(1));
+                app.error = Some(format!("Cancelled {title}."));
+            }
+        }
+        KeyCode::Char('p' | 'P') if state == DownloadState::Paused => {
+            if manager.resume(&path) {
+                app.set_download_state(&path, DownloadState::Queued);
+                app.error = Some(format!("Resumed {title}."));
+            }
+        }
+        KeyCode::Char('p' | 'P') => {
+            if manager.pause(&path) {
+                app.set_download_state(&path, DownloadState::Paused);
+                app.error = Some(format!("Paused {title}."));
+            }
+        }
+        KeyCode::Char('f' | 'F') => {
+            if state == DownloadState::Downloading || !manager.move_to_front(&path) {
+                app.error = Some(format!("{title} has already started."));
+            } else {
+                app.move_download_to_front(&path);
+                app.download_selected = app
+                    .downloads
+                    .iter()
+                    .position(|job| job.path == path)
+                    .unwrap_or(0);
+            }
+        }
+        // Typing would otherwise leak into the hidden search field.
+        KeyCode::Char(_) => {}
+        _ => return false,
+    }
+    true
+}
+
+/// Run a `:playlist` command. `selected` is what `add` stores: the highlighted
+/// library song, or the current track while streaming.
+fn handle_playlist_command(
+    input: &str,
+    app: &mut App,
+    player: &mut Player,
+    sender: Option<&std::sync::mpsc::Sender<DownloadFinished>>,
+    selected: Option<PlaylistEntry>,
+) -> Option<String> {
+    let command = match PlaylistCommand::parse(input)? {
+        Ok(command) => command,
+        Err(message) => return Some(message),
+    };
+    let result = match command {
+        PlaylistCommand::Browse => {
+            app.show_playlists = true;
+            app.show_history = false;
+            app.queue_focused = false;
+            Ok(if app.playlists.is_empty() {
+                "No playlists yet. Create one with :playlist new <name>.".to_string()
+            } else {
+                format!(
+                    "Playlists: {}",
+                    app.playlists
+                        .iter()
+                        .map(|playlist| format!("{} ({})", playlist.name, playlist.entries.len()))
+                        .collect::<Vec<_>>()
+                        .join(" · ")
+                )
+            })
+        }
+        PlaylistCommand::New(name) => app
+            .playlists
+            .create(&name)
+            .map(|()| format!("Created playlist {}.", name.trim())),
+        PlaylistCommand::Add(name) => match selected {
+            Some(entry) => app.playlists.add(&name, entry.clone()).map(|count| {
+                format!(
+                    "Added {} to {} ({count} track(s)).",
+                    entry.title,
+                    name.trim()
+                )
+            }),
+            None => Err("Select or play a song to add it to a playlist.".to_string()),
+        },
+        PlaylistCommand::Remove(name, position) => app
+            .playlists
+            .remove_entry(&name, position)
+            .map(|entry| format!("Removed {} from {}.", entry.title, name.trim())),
+        PlaylistCommand::Delete(name) => app.playlists.delete(&name).map(|playlist| {
+            app.playlist_selected = app
+                .playlist_selected
+                .min((app.playlists.len() + app.smart_playlists.len()).saturating_sub(1));
+            format!("Deleted playlist {}.", playlist.name)
+        }),
+        PlaylistCommand::Play(name) => Ok(queue_playlist(app, player, sender, &name, true)),
+        PlaylistCommand::Queue(name) => Ok(queue_playlist(app, player, sender, &name, false)),
+        PlaylistCommand::Export(name, format) => {
+            match (app.playlists.find(&name), dirs::audio_dir()) {
+                (Some(playlist), Some(music_dir)) => {
+                    export_playlist(playlist, format, &music_dir, |path| {
+                        app.is_library_file_available(&path.to_string_lossy())
+                    })
+                    .map(|(path, skipped)| {
+                        let mut message =
+                            format!("Exported {} to {}", playlist.name, path.display());
+                        if skipped > 0 {
+                            message.push_str(&format!(
+                                "; {skipped} track(s) outside the library were left out"
+                            ));
+                        }
+                        message + "."
+                    })
+                }
+                (None, _) => Err(format!("No playlist named {}.", name.trim())),
+                (_, None) => Err("The Music directory is unavailable.".to_string()),
+            }
+        }
+        PlaylistCommand::Import(file) => {
+            let file = match file.strip_prefix("~/").zip(dirs::home_dir()) {
+                Some((relative, home)) => home.join(relative),
+                None => std::path::PathBuf::from(file),
+            };
+            match dirs::audio_dir() {
+                Some(music_dir) => import_playlist(&file, &music_dir, |path| {
+                    app.is_library_file_available(&path.to_string_lossy())
+                })
+                .map(|(playlist, skipped)| {
+                    let count = playlist.entries.len();
+                    let name = app.playlists.insert_unique(playlist);
+                    let mut message = format!("Imported {name} with {count} track(s)");
+                    if skipped > 0 {
+                        message.push_str(&format!(
+                            "; skipped {skipped} entry(ies) outside the library or not on YouTube"
+                        ));
+                    }
+                    message + "."
+                }),
+                None => Err("The Music directory is unavailable.".to_string()),
+            }
+        }
+    };
+    app.playlists.save();
+    Some(result.unwrap_or_else(|message| message))
+}
+
+/// Queue a playlist's playable entries. `now` stops the current track and puts
+/// the playlist ahead of anything already queued.
+fn queue_playlist(
+    app: &mut App,
+    player: &mut Player,
+    sender: Option<&std::sync::mpsc::Sender<DownloadFinished>>,
+    name: &str,
+    now: bool,
+) -> String {
+    let Some(playlist) = app.playlists.find(name).cloned() else {
+        return format!("No playlist named {}.", name.trim());
+    };
+    if now {
+        player.stop();
+    }
+    let start = player.queue.len();
+    let mut skipped = 0;
+    for entry in &playlist.entries {
+        // Imported tracks live in nested folders, so membership is decided
+        // by the library index rather than by the folder a file sits in.
+        let local = !entry.path.is_empty() && app.is_library_file_available(&entry.path);
+        if local {
+            player.queue.push((entry.title.clone(), entry.path.clone()));
+        } else if let (Some(sender), Some(video_id)) = (sender, entry.video_id.as_deref()) {
+            queue_youtube_download(app, player, sender, &entry.title, video_id);
+        } else {
+            skipped += 1;
+        }
+    }
+    if now {
+        let added = player.queue.split_off(start);
+        player.queue.splice(0..0, added);
+    }
+    let queued = player.queue.len() - start;
+    if skipped == 0 {
+        format!("Queued {queued} track(s) from {}.", playlist.name)
+    } else {
+        format!(
+            "Queued {queued} track(s) from {}; {skipped} unavailable track(s) skipped.",
+            playlist.name
+        )
+    }
+}
+
+fn handle_smart_command(input: &str, app: &mut App, player: &mut Player) -> Option<String> {
+    let command = match SmartCommand::parse(input)? {
+        Ok(command) => command,
+        Err(message) => return Some(message),
+    };
+    let result = match command {
+        SmartCommand::Browse => {
+            app.show_playlists = true;
+            app.show_history = false;
+            app.queue_focused = false;
+            Ok(if app.smart_playlists.is_empty() {
+                "No smart playlists yet. Create one with :smart new <name> = <rule>.".to_string()
+            } else {
+                format!(
+                    "Smart playlists: {}",
+                    app.smart_playlists
+                        .iter()
+                        .map(|playlist| format!("{} ({})", playlist.name, playlist.rule))
+                        .collect::<Vec<_>>()
+                        .join(" · ")
+                )
+            })
+        }
+        SmartCommand::New(name, rule) => app.smart_playlists.define(&name, &rule).map(|replaced| {
+            let name = name.trim();
+            let count = app
+                .smart_playlists
+                .find(name)
+                .map_or(0, |playlist| playlist.tracks(app).len());
+            if replaced {
+                format!("Updated smart playlist {name}; {count} song(s) match.")
+            } else {
+                format!("Created smart playlist {name}; {count} song(s) match.")
+            }
+        }),
+        SmartCommand::Delete(name) => app.smart_playlists.delete(&name).map(|name| {
+            app.playlist_selected = app
+                .playlist_selected
+                .min((app.playlists.len() + app.smart_playlists.len()).saturating_sub(1));
+            if app
+                .smart_view
+                .as_deref()
+                .is_some_and(|shown| shown.eq_ignore_ascii_case(&name))
+            {
+                app.smart_view = None;
+                refresh_library_results(app);
+            }
+            format!("Deleted smart playlist {name}.")
+        }),
+        SmartCommand::Play(name) => Ok(queue_smart_playlist(app, player, &name, true, false)),
+        SmartCommand::Queue(name) => Ok(queue_smart_playlist(app, player, &name, false, false)),
+        SmartCommand::Shuffle(name) => Ok(queue_smart_playlist(app, player, &name, false, true)),
+        SmartCommand::Show(None) => {
+            app.smart_view = None;
+            refresh_library_results(app);
+            Ok("Showing the whole library.".to_string())
+        }
+        SmartCommand::Show(Some(name)) => match app.smart_playlists.find(&name) {
+            Some(playlist) => {
+                let name = playlist.name.clone();
+                app.smart_view = Some(name.clone());
+                app.selected = 0;
+                refresh_library_results(app);
+                Ok(format!(
+                    "Showing {} song(s) from {name}. :smart show returns to the library.",
+                    app.results.len()
+                ))
+            }
+            None => Err(format!("No smart playlist named {}.", name.trim())),
+        },
+    };
+    app.smart_playlists.save();
+    Some(result.unwrap_or_else(|message| message))
+}
+
+/// Queue the available library songs a smart playlist matches right now.
+/// `now` works as for regular playlists; `shuffle` mixes them into the queue
+/// like `:shuffle all`.
+fn queue_smart_playlist(
+    app: &mut App,
+    player: &mut Player,
+    name: &str,
+    now: bool,
+    shuffle: bool,
+) -> String {
+    let Some(playlist) = app.smart_playlists.find(name) else {
+        return format!("No smart playlist named {}.", name.trim());
+    };
+    if let Some(error) = playlist.error() {
+        return format!("{} cannot be used: {error}", playlist.name);
+    }
+    let name = playlist.name.clone();
+    let mut tracks = playlist.tracks(app);
+    tracks.retain(|(_, path)| app.is_library_file_available(path));
+    let queued = tracks.len();
+    if now {
+        player.stop();
+        player.queue.splice(0..0, tracks);
+    } else {
+        player.queue.extend(tracks);
+    }
+    if shuffle {
+        fastrand::shuffle(&mut player.queue);
+        format!("Added and shuffled {queued} song(s) from {name}.")
+    } else {
+        format!("Queued {queued} song(s) from {name}.")
+    }
+}
+
+/// The regular or smart playlist highlighted in the browser, which lists
+/// smart playlists after the regular ones.
+enum BrowsedPlaylist {
+    Regular(String),
+    Smart(String),
+}
+
+fn browsed_playlist(app: &App) -> Option<BrowsedPlaylist> {
+    match app.playlists.get(app.playlist_selected) {
+        Some(playlist) => Some(BrowsedPlaylist::Regular(playlist.name.clone())),
+        None => app
+            .smart_playlists
+            .get(app.playlist_selected - app.playlists.len())
+            .map(|playlist| BrowsedPlaylist::Smart(playlist.name.clone())),
+    }
+}
+
+/// Ctrl+O opens the playlist browser in Downloaded Music Only mode.
+fn handle_playlist_key(
+    key: &crossterm::event::KeyEvent,
+    app: &mut App,
+    player: &mut Player,
+) -> bool {
+    let control = key
+        .modifiers
+        .contains(crossterm::event::KeyModifiers::CONTROL);
+    match key.code {
+        KeyCode::Char('o') if control => {
+            app.show_playlists = !app.show_playlists;
+            app.playlist_selected = 0;
+            app.show_history = false;
+            app.queue_focused = false;
+        }
+        KeyCode::Down if key.modifiers.is_empty() && app.show_playlists => {
+            app.playlist_selected = (app.playlist_selected + 1)
+                .min((app.playlists.len() + app.smart_playlists.len()).saturating_sub(1));
+        }
+        KeyCode::Up if key.modifiers.is_empty() && app.show_playlists => {
+            app.playlist_selected = app.playlist_selected.saturating_sub(1);
+        }
+        KeyCode::Enter
+            if key.modifiers.is_empty() && app.show_playlists && app.input.trim().is_empty() =>
+        {
+            app.error = match browsed_playlist(app) {
+                Some(BrowsedPlaylist::Regular(name)) => {
+                    Some(queue_playlist(app, player, None, &name, true))
+                }
+                Some(BrowsedPlaylist::Smart(name)) => {
+                    Some(queue_smart_playlist(app, player, &name, true, false))
+                }
+                None => return true,
+            };
+            app.lyrics_active = None;
+        }
+        KeyCode::Char('e') if control && app.show_playlists => {
+            app.error = match browsed_playlist(app) {
+                Some(BrowsedPlaylist::Regular(name)) => {
+                    Some(queue_playlist(app, player, None, &name, false))
+                }
+                Some(BrowsedPlaylist::Smart(name)) => {
+                    Some(queue_smart_playlist(app, player, &name, false, false))
+                }
+                None => return true,
+            };
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Rebuild the previous session. Library files must still be present; streamed
+/// entries are fetched again through the normal download path.
+fn resume_session(
+    app: &mut App,
+    player: &mut Player,
+    sender: &std::sync::mpsc::Sender<DownloadFinished>,
+    session: Session,
+) -> String {
+    let offset = Duration::from_secs(session.position_seconds);
+    let mut restored = 0;
+    let mut skipped = 0;
+    if let Some(current) = &session.current {
+        if app.is_library_file_available(&current.path) {
+            player.stop();
+            player.set_start_offset(&current.path, offset);
+            player.play(&current.path, &current.title);
+            restored += 1;
+        } else if let Some(video_id) = &current.video_id {
+            let queued = player.queue.len();
+            queue_youtube_download(app, player, sender, &current.title, video_id);
+            if player.queue.len() > queued
+                && let Some(pending) = player.queue.pop()
+            {
+                player.set_start_offset(&pending.1, offset);
+                player.queue.insert(0, pending);
+                restored += 1;
+            }
+        } else {
+            skipped += 1;
+        }
+    }
+    for entry in &session.queue {
+        if app.is_library_file_available(&entry.path) {
+            player.queue.push((entry.title.clone(), entry.path.clone()));
+            restored += 1;
+        } else if let Some(video_id) = &entry.video_id {
+            queue_youtube_download(app, player, sender, &entry.title, video_id);
+            restored += 1;
+        } else {
+            skipped += 1;
+        }
+    }
+    if skipped == 0 {
+        format!("Resumed {restored} track(s).")
+    } else {
+        format!("Resumed {restored} track(s); {skipped} missing library file(s) skipped.")
+    }
+}
+
 struct FramePacer {
     fps: u16,
     configured_fps: u16,
@@ -268,6 +1107,10 @@ fn process_download_completions(
     let mut changed = false;
     while let Ok(download) = receiver.try_recv() {
         let cancelled = app.finish_download(&download.queue_path);
+        if download.success && !cancelled {
+            // The gain reaches the player later, so playback starts at once.
+            player.measure_stream_loudness(&download.playback_path);
+        }
         if let Some(index) = player
             .queue
             .iter()
@@ -305,95 +1148,916 @@ fn process_download_completions(
             let _ = std::fs::remove_file(&download.playback_path);
         }
     }
-    changed
+    changed
+}
+
+fn queue_library_download(
+    app: &mut App,
+    manager: &DownloadManager,
+    video_id: String,
+    title: String,
+    video_cache_plan: Option<(u16, u16, u16)>,
+) {
+    if !valid_youtube_id(&video_id) {
+        app.error = Some("YouTube returned an invalid media identifier.".to_string());
+        return;
+    }
+    let url = format!("https://www.youtube.com/watch?v={video_id}");
+    let directory = match app.library_roots.download_dir() {
+        Ok(directory) => directory,
+        Err(error) => {
+            app.error = Some(error);
+            return;
+        }
+    };
+    if let Err(error) = std::fs::create_dir_all(&directory) {
+        app.error = Some(format!("Could not create the download folder: {error}"));
+        return;
+    }
+    // YouTube IDs make output paths stable and prevent two different tracks
+    // with the same title (or titles that sanitize identically) from colliding.
+    let format = app.download_format;
+    let Ok(path) = library_download_path(&directory, &title, &video_id, format.planned_extension())
+    else {
+        app.error =
+            Some("The download title could not be converted to a safe filename.".to_string());
+        return;
+    };
+    let path_string = path.to_string_lossy().into_owned();
+    let library_roots = app.library_roots.paths();
+    // A finished copy on any library root counts, not just the download root,
+    // and so does one saved in another format.
+    let already_downloaded = library_roots.iter().any(|root| {
+        AUDIO_EXTENSIONS.iter().any(|extension| {
+            library_download_path(root, &title, &video_id, extension).is_ok_and(|path| {
+                let cache_is_available = path
+                    .with_extension("crestvid")
+                    .metadata()
+                    .is_ok_and(|metadata| metadata.is_file() && metadata.len() > 0);
+                app.is_library_file_available(&path.to_string_lossy()) && cache_is_available
+            })
+        })
+    });
+    if already_downloaded {
+        app.error = Some(format!("{title} is already downloaded."));
+        return;
+    }
+    if AUDIO_EXTENSIONS
+        .iter()
+        .any(|extension| app.is_downloading(&path.with_extension(extension).to_string_lossy()))
+    {
+        app.error = Some(format!("{title} is already downloading."));
+        return;
+    }
+    app.start_queued_download(path_string.clone(), title.clone());
+    app.error = Some(format!("Queued {title} for download."));
+    if manager
+        .enqueue(DownloadRequest {
+            id: path_string.clone(),
+            title,
+            url,
+            path: path_string.clone(),
+            library_roots: library_roots.into(),
+            format,
+            video_cache_plan,
+        })
+        .is_err()
+    {
+        app.finish_download(&path_string);
+        app.error = Some("The download worker is unavailable.".to_string());
+    }
+}
+
+/// Queue the library downloads left unfinished at the last exit again, in
+/// their old order and pause state.
+fn resume_pending_downloads(
+    app: &mut App,
+    manager: &DownloadManager,
+    pending: PendingDownloads,
+) -> String {
+    let library_roots = app.library_roots.paths();
+    let mut resumed = 0;
+    for download in pending.downloads {
+        if app.is_downloading(&download.path) {
+            continue;
+        }
+        app.start_queued_download(download.path.clone(), download.title.clone());
+        if manager.enqueue(download.request(&library_roots)).is_err() {
+            app.finish_download(&download.path);
+            continue;
+        }
+        if download.paused && manager.pause(&download.id) {
+            app.set_download_state(&download.path, DownloadState::Paused);
+        }
+        resumed += 1;
+    }
+    PendingDownloads::default().save();
+    format!("Resumed {resumed} unfinished download(s).")
+}
+
+fn library_download_path(
+    directory: &std::path::Path,
+    title: &str,
+    video_id: &str,
+    extension: &str,
+) -> std::io::Result<std::path::PathBuf> {
+    let filename_suffix = format!(" [{video_id}]_ytmusic.{extension}");
+    contained_media_path(directory, title, &filename_suffix)
+}
+
+fn video_cache_plan(app: &App, width: u16, height: u16) -> Option<(u16, u16, u16)> {
+    Some({
+        let samples = app.idle_video_render_mode.samples_per_cell();
+        (
+            even_cache_dimension(width.saturating_mul(samples.0)),
+            even_cache_dimension(height.saturating_mul(samples.1)),
+            if app.idle_video_fps == 0 {
+                30
+            } else {
+                app.idle_video_fps
+            },
+        )
+    })
+}
+
+fn even_cache_dimension(value: u16) -> u16 {
+    value.clamp(2, 4096) & !1
+}
+
+fn register_library_gains(app: &App, player: &mut Player) {
+    for path in app.library_loudness.keys() {
+        player.register_replay_gain(path, app.replay_gain(path));
+    }
+}
+
+type LoudnessAnalysis = std::sync::mpsc::Receiver<(String, Result<TrackLoudness, String>)>;
+
+/// Measure unanalysed library tracks only while normalization is in use.
+fn start_loudness_analysis(app: &App) -> Option<LoudnessAnalysis> {
+    if app.normalization_mode == loudness::NormalizationMode::Off {
+        return None;
+    }
+    let paths = app.tracks_missing_loudness();
+    (!paths.is_empty()).then(|| loudness::analyze_in_background(paths))
+}
+
+fn process_loudness_analysis(
+    analysis: &mut Option<LoudnessAnalysis>,
+    app: &mut App,
+    player: &mut Player,
+) -> bool {
+    let Some(receiver) = analysis else {
+        return false;
+    };
+    let mut changed = false;
+    loop {
+        match receiver.try_recv() {
+            Ok((path, Ok(measurement))) => {
+                app.set_track_loudness(&path, measurement);
+                changed = true;
+            }
+            // Unreadable files keep unity gain and are retried next launch.
+            Ok((_, Err(_))) => {}
+            Err(std::sync::mpsc::TryRecvError::Empty) => break,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                *analysis = None;
+                break;
+            }
+        }
+    }
+    if changed {
+        save_library(app);
+        register_library_gains(app, player);
+    }
+    changed
+}
+
+/// `:retag` rewrites the tags and cover art of every available library file from
+/// the metadata stored in the index.
+fn handle_retag_command(input: &str, app: &App, job: &mut Option<RetagJob>) -> Option<String> {
+    if input.trim() != ":retag" {
+        return None;
+    }
+    if let Some(job) = job {
+        return Some(format!(
+            "Retagging is already running ({}/{}).",
+            job.tagged + job.failed,
+            job.total
+        ));
+    }
+    let tracks: Vec<_> = app
+        .library
+        .iter()
+        // Imported files keep their own formats and tags.
+        .filter(|(_, path)| app.is_library_file_available(path) && !app.is_imported_track(path))
+        .map(|(title, path)| {
+            let metadata = app.track_metadata(path).cloned().unwrap_or_default();
+            (title.clone(), path.clone(), metadata)
+        })
+        .collect();
+    if tracks.is_empty() {
+        return Some("No downloaded songs to retag.".to_string());
+    }
+    let count = tracks.len();
+    *job = Some(RetagJob::start(tracks));
+    Some(format!("Retagging {count} song(s) in the background..."))
+}
+
+fn process_retag_job(job: &mut Option<RetagJob>, app: &mut App) -> bool {
+    let Some(progress) = job else {
+        return false;
+    };
+    let mut changed = false;
+    loop {
+        match progress.receiver.try_recv() {
+            Ok((path, Ok(()))) => {
+                progress.tagged += 1;
+                if let Some(metadata) = app.library_metadata.get_mut(&path) {
+                    metadata.fill_from_file(std::path::Path::new(&path));
+                }
+                changed = true;
+            }
+            Ok((_, Err(_))) => {
+                progress.failed += 1;
+                changed = true;
+            }
+            Err(std::sync::mpsc::TryRecvError::Empty) => break,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                app.error = Some(if progress.failed == 0 {
+                    format!("Retagged {} song(s).", progress.tagged)
+                } else {
+                    format!(
+                        "Retagged {} song(s); {} could not be rewritten.",
+                        progress.tagged, progress.failed
+                    )
+                });
+                save_library(app);
+                *job = None;
+                return true;
+            }
+        }
+    }
+    changed
+}
+
+/// `:check` reports where the library index and the Music directory disagree;
+/// `:check fix` also repairs it and rebuilds broken video caches.
+fn handle_check_command(
+    input: &str,
+    app: &mut App,
+    job: &mut Option<CacheRebuildJob>,
+    plan: Option<(u16, u16, u16)>,
+) -> Option<String> {
+    let fix = match input.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [":check"] => false,
+        [":check", "fix"] => true,
+        [":check", ..] => return Some("Library check: :check · :check fix".to_string()),
+        _ => return None,
+    };
+    let report = check_library(app);
+    if !fix || report.is_clean() {
+        let hint = if report.is_clean() {
+            ""
+        } else {
+            " :check fix repairs it."
+        };
+        return Some(format!("{}{hint}", report.summary()));
+    }
+    let outcome = repair_library(app, &report);
+    save_library(app);
+    refresh_library_results(app);
+    let caches = report.rebuildable_caches();
+    let mut message = outcome.summary();
+    if let Some(job) = job {
+        message.push_str(&format!(
+            " Video caches are already being rebuilt ({}/{}).",
+            job.rebuilt + job.failed,
+            job.total
+        ));
+    } else if let (false, Some(plan)) = (caches.is_empty(), plan) {
+        message.push_str(&format!(
+            " Rebuilding {} video cache(s) in the background...",
+            caches.len()
+        ));
+        *job = Some(CacheRebuildJob::start(caches, plan));
+    }
+    Some(message)
+}
+
+fn process_cache_rebuild(job: &mut Option<CacheRebuildJob>, app: &mut App) -> bool {
+    let Some(progress) = job else {
+        return false;
+    };
+    let mut changed = false;
+    loop {
+        match progress.receiver.try_recv() {
+            Ok((_, Ok(()))) => {
+                progress.rebuilt += 1;
+                changed = true;
+            }
+            Ok((_, Err(_))) => {
+                progress.failed += 1;
+                changed = true;
+            }
+            Err(std::sync::mpsc::TryRecvError::Empty) => break,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                app.error = Some(if progress.failed == 0 {
+                    format!("Rebuilt {} video cache(s).", progress.rebuilt)
+                } else {
+                    format!(
+                        "Rebuilt {} video cache(s); {} could not be downloaded again.",
+                        progress.rebuilt, progress.failed
+                    )
+                });
+                *job = None;
+                return true;
+            }
+        }
+    }
+    changed
+}
+
+type FingerprintJob = std::sync::mpsc::Receiver<Result<Vec<(String, String)>, String>>;
+
+/// `:duplicates` groups library songs by source video, title and length;
+/// `:duplicates audio` also compares audio fingerprints in the background.
+fn handle_duplicates_command(
+    input: &str,
+    app: &mut App,
+    job: &mut Option<FingerprintJob>,
+) -> Option<String> {
+    let audio = match input.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [":duplicates" | ":dupes"] => false,
+        [":duplicates" | ":dupes", "audio"] => true,
+        [":duplicates" | ":dupes", ..] => {
+            return Some("Duplicates: :duplicates · :duplicates audio".to_string());
+        }
+        _ => return None,
+    };
+    if !audio {
+        return Some(open_duplicates(app, &[]));
+    }
+    if job.is_some() {
+        return Some("Audio fingerprints are already being compared.".to_string());
+    }
+    let candidates = fingerprint_candidates(app);
+    if candidates.is_empty() {
+        return Some(open_duplicates(app, &[]));
+    }
+    let count = candidates.len();
+    *job = Some(fingerprint_in_background(candidates));
+    Some(format!(
+        "Fingerprinting {count} song(s) of similar length in the background..."
+    ))
+}
+
+fn open_duplicates(app: &mut App, audio_matches: &[(String, String)]) -> String {
+    let groups = find_duplicates(app, audio_matches);
+    if groups.is_empty() {
+        app.duplicates = None;
+        return "No duplicate songs found.".to_string();
+    }
+    let copies: usize = groups.iter().map(|group| group.tracks.len() - 1).sum();
+    let message = format!(
+        "Found {} group(s) with {copies} extra copy(ies). Choose the copy to keep with ←/→ and press Enter.",
+        groups.len()
+    );
+    app.duplicates = Some(DuplicateView {
+        groups,
+        group: 0,
+        track: 0,
+    });
+    app.show_playlists = false;
+    app.show_history = false;
+    app.queue_focused = false;
+    message
+}
+
+fn process_fingerprint_job(job: &mut Option<FingerprintJob>, app: &mut App) -> bool {
+    let Some(receiver) = job else {
+        return false;
+    };
+    let message = match receiver.try_recv() {
+        Ok(Ok(matches)) => open_duplicates(app, &matches),
+        Ok(Err(error)) => format!("Could not compare audio: {error}."),
+        Err(std::sync::mpsc::TryRecvError::Empty) => return false,
+        Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+            *job = None;
+            return false;
+        }
+    };
+    app.error = Some(message);
+    *job = None;
+    true
+}
+
+/// Follow the current track with its cover art, decoded in the background.
+fn process_album_art(loader: &mut AlbumArtLoader, app: &mut App, player: &Player) -> bool {
+    let current = player.current_entry().map(|(_, path)| path);
+    if loader.track() != current.as_deref() {
+        let request = current.map(|path| ArtRequest {
+            video_id: player
+                .video_id_for(&path)
+                .or_else(|| {
+                    app.track_metadata(&path)
+                        .and_then(|metadata| metadata.video_id.clone())
+                })
+                .or_else(|| video_id_from_filename(std::path::Path::new(&path))),
+            sidecar: app.is_imported_track(&path),
+            path,
+        });
+        loader.load(request);
+        return app.album_art.take().is_some();
+    }
+    match loader.poll() {
+        Some(cover) => {
+            app.album_art = cover;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Keys for the duplicate comparison view in Downloaded Music Only mode.
+fn handle_duplicates_key(
+    key: &crossterm::event::KeyEvent,
+    app: &mut App,
+    player: &mut Player,
+) -> bool {
+    let idle_input = app.input.trim().is_empty();
+    let Some(view) = &mut app.duplicates else {
+        return false;
+    };
+    if !key.modifiers.is_empty() {
+        return false;
+    }
+    let copies = view.current().map_or(0, |group| group.tracks.len());
+    match key.code {
+        KeyCode::Left => view.track = view.track.saturating_sub(1),
+        KeyCode::Right => view.track = (view.track + 1).min(copies.saturating_sub(1)),
+        KeyCode::Up => {
+            view.group = view.group.saturating_sub(1);
+            view.track = 0;
+        }
+        KeyCode::Down => {
+            view.group = (view.group + 1).min(view.groups.len().saturating_sub(1));
+            view.track = 0;
+        }
+        KeyCode::Esc if idle_input => app.duplicates = None,
+        // The song list is hidden, so Delete must not remove its selection.
+        KeyCode::Delete => {}
+        KeyCode::Enter if idle_input => app.error = Some(keep_duplicate(app, player)),
+        _ => return false,
+    }
+    true
+}
+
+/// Keep the selected copy of the current group and delete the others, with
+/// their video caches. Imported copies are only dropped from the library and
+/// left out of later scans.
+fn keep_duplicate(app: &mut App, player: &mut Player) -> String {
+    let Some(view) = &mut app.duplicates else {
+        return String::new();
+    };
+    if view.group >= view.groups.len() {
+        return String::new();
+    }
+    let group = view.groups.remove(view.group);
+    let keep = view.track.min(group.tracks.len() - 1);
+    view.group = view.group.min(view.groups.len().saturating_sub(1));
+    view.track = 0;
+    if view.groups.is_empty() {
+        app.duplicates = None;
+    }
+    let mut removed = 0;
+    let mut dismissed = 0;
+    let mut freed = 0u64;
+    let mut errors = Vec::new();
+    for (index, track) in group.tracks.iter().enumerate() {
+        if index == keep {
+            continue;
+        }
+        if app.is_imported_track(&track.path) {
+            // Imported files are never deleted; they stay out of the library.
+            app.dismiss_imported_track(&track.path);
+            dismissed += 1;
+            player.queue.retain(|(_, path)| path != &track.path);
+            continue;
+        }
+        match app.remove_library_track(&track.path) {
+            Ok(()) => {
+                removed += 1;
+                freed = freed.saturating_add(track.bytes);
+                player.queue.retain(|(_, path)| path != &track.path);
+            }
+            Err(error) => errors.push(format!("{}: {error}", track.title)),
+        }
+    }
+    save_library(app);
+    if dismissed > 0 {
+        save_settings(app);
+    }
+    refresh_library_results(app);
+    let mut message = format!(
+        "Kept {}; deleted {removed} copy(ies), freeing {}.",
+        group.tracks[keep].title,
+        storage::format_bytes(freed)
+    );
+    if dismissed > 0 {
+        message.push_str(&format!(
+            " {dismissed} imported copy(ies) were only removed from the library; their files are untouched."
+        ));
+    }
+    if !errors.is_empty() {
+        message.push_str(&format!(" Could not delete {}.", errors.join(" | ")));
+    }
+    message
+}
+
+type LibraryScan = std::sync::mpsc::Receiver<ScanResult>;
+
+/// Rescan the configured folders. Only imported entries are compared against
+/// the disk; downloads are never picked up a second time.
+fn start_library_scan(app: &App) -> Option<LibraryScan> {
+    if app.library_folders.is_empty() {
+        return None;
+    }
+    let mut known = std::collections::HashMap::new();
+    let mut skip = std::collections::HashSet::new();
+    for (_, path) in &app.library {
+        // Files the watcher imported from the Music directory are not under a
+        // scanned folder and must not be reported as deleted.
+        match app
+            .track_metadata(path)
+            .filter(|metadata| metadata.imported && in_library_folder(app, path))
+        {
+            Some(metadata) => {
+                known.insert(
+                    path.clone(),
+                    KnownFile {
+                        file_size: metadata.file_size,
+                        modified_at: metadata.modified_at,
+                    },
+                );
+            }
+            None => {
+                skip.insert(path.clone());
+            }
+        }
+    }
+    skip.extend(app.dismissed_imports.iter().cloned());
+    let folders = app
+        .library_folders
+        .iter()
+        .map(std::path::PathBuf::from)
+        .collect();
+    Some(library_scan::scan_in_background(folders, known, skip))
+}
+
+fn in_library_folder(app: &App, path: &str) -> bool {
+    app.library_folders
+        .iter()
+        .any(|folder| std::path::Path::new(path).starts_with(folder))
+}
+
+fn handle_scan_command(
+    input: &str,
+    app: &mut App,
+    scan: &mut Option<LibraryScan>,
+) -> Option<String> {
+    let command = match ScanCommand::parse(input)? {
+        Ok(command) => command,
+        Err(message) => return Some(message),
+    };
+    Some(match command {
+        ScanCommand::Folders if app.library_folders.is_empty() => {
+            "No library folders yet. Try :scan add <folder>".to_string()
+        }
+        ScanCommand::Folders => format!("Library folders: {}", app.library_folders.join(" · ")),
+        ScanCommand::AddFolder(folder) => {
+            let folder = match expand_home(&folder).canonicalize() {
+                Ok(folder) if folder.is_dir() => folder.to_string_lossy().into_owned(),
+                _ => return Some(format!("{folder} is not a folder.")),
+            };
+            if !app.library_folders.contains(&folder) {
+                app.library_folders.push(folder.clone());
+                save_settings(app);
+            }
+            restart_library_scan(app, scan, format!("Scanning {folder}..."))
+        }
+        ScanCommand::RemoveFolder(folder) => {
+            let expanded = expand_home(&folder);
+            let canonical = expanded.canonicalize().unwrap_or(expanded);
+            let Some(index) = app
+                .library_folders
+                .iter()
+                .position(|known| known == &folder || std::path::Path::new(known) == canonical)
+            else {
+                return Some(format!("{folder} is not a library folder."));
+            };
+            let removed = std::path::PathBuf::from(app.library_folders.remove(index));
+            save_settings(app);
+            // Songs another configured folder also contains stay in the library.
+            let dropped: Vec<String> = app
+                .library
+                .iter()
+                .map(|(_, path)| path.clone())
+                .filter(|path| {
+                    app.is_imported_track(path)
+                        && std::path::Path::new(path).starts_with(&removed)
+                        && !in_library_folder(app, path)
+                })
+                .collect();
+            for path in &dropped {
+                app.forget_library_track(path);
+            }
+            save_library(app);
+            format!(
+                "Stopped scanning {}; {} song(s) removed from the library.",
+                removed.display(),
+                dropped.len()
+            )
+        }
+        ScanCommand::Rescan if app.library_folders.is_empty() => {
+            "No library folders yet. Try :scan add <folder>".to_string()
+        }
+        ScanCommand::Rescan => {
+            restart_library_scan(app, scan, "Scanning library folders...".to_string())
+        }
+    })
+}
+
+fn handle_roots_command(input: &str, app: &mut App) -> Option<String> {
+    let command = match RootCommand::parse(input)? {
+        Ok(command) => command,
+        Err(message) => return Some(message),
+    };
+    let result = match command {
+        RootCommand::List => {
+            app.library_roots.refresh();
+            let roots: Vec<String> = app
+                .library_roots
+                .paths()
+                .iter()
+                .map(|root| {
+                    let mut label = root.display().to_string();
+                    if app.library_roots.is_download_root(root) {
+                        label.push_str(" (downloads)");
+                    }
+                    if app.library_roots.is_offline(root) {
+                        label.push_str(" (offline)");
+                    }
+                    label
+                })
+                .collect();
+            return Some(format!("Library roots: {}", roots.join(" · ")));
+        }
+        RootCommand::Add(folder) => app.library_roots.add(&expand_home(&folder)).map(|root| {
+            format!(
+                "Added {} as a library root. :roots download {} sends new downloads there.",
+                root.display(),
+                root.display()
+            )
+        }),
+        RootCommand::Remove(folder) => {
+            app.library_roots
+                .remove(&expand_home(&folder))
+                .map(|root| {
+                    // Downloads stored there leave the index; the files stay.
+                    let dropped: Vec<String> = app
+                        .library
+                        .iter()
+                        .map(|(_, path)| path.clone())
+                        .filter(|path| {
+                            !app.is_imported_track(path)
+                                && std::path::Path::new(path).parent() == Some(root.as_path())
+                        })
+                        .collect();
+                    for path in &dropped {
+                        app.forget_library_track(path);
+                    }
+                    save_library(app);
+                    format!(
+                        "Removed library root {}; {} song(s) left the library and their files were kept.",
+                        root.display(),
+                        dropped.len()
+                    )
+                })
+        }
+        RootCommand::Download(folder) => {
+            let folder = folder.map(|folder| expand_home(&folder));
+            app.library_roots.set_download(folder.as_deref()).map(|()| {
+                match app.library_roots.download_dir() {
+                    Ok(directory) => format!("New downloads go to {}.", directory.display()),
+                    Err(offline) => offline,
+                }
+            })
+        }
+    };
+    if result.is_ok() {
+        save_settings(app);
+        app.refresh_library_availability();
+    }
+    Some(result.unwrap_or_else(|message| message))
+}
+
+fn offline_track_message(title: &str) -> String {
+    format!("{title} is on an offline library root. Reconnect its disk to play it.")
+}
+
+fn restart_library_scan(app: &App, scan: &mut Option<LibraryScan>, message: String) -> String {
+    if scan.is_some() {
+        return "A library scan is already running.".to_string();
+    }
+    *scan = start_library_scan(app);
+    message
+}
+
+fn expand_home(path: &str) -> std::path::PathBuf {
+    match path.strip_prefix("~/").zip(dirs::home_dir()) {
+        Some((rest, home)) => home.join(rest),
+        None => std::path::PathBuf::from(path),
+    }
+}
+
+fn process_library_scan(scan: &mut Option<LibraryScan>, app: &mut App) -> bool {
+    let Some(receiver) = scan else {
+        return false;
+    };
+    let result = match receiver.try_recv() {
+        Ok(result) => result,
+        Err(std::sync::mpsc::TryRecvError::Empty) => return false,
+        Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+            *scan = None;
+            return false;
+        }
+    };
+    *scan = None;
+    for (from, to) in &result.moved {
+        app.move_library_track(from, to.clone());
+    }
+    for path in &result.removed {
+        app.forget_library_track(path);
+    }
+    for track in result.updated.iter().chain(&result.added) {
+        // A download can finish at the same path while the probe runs.
+        if app.is_library_path(&track.path) && !app.is_imported_track(&track.path) {
+            continue;
+        }
+        let mut metadata = track.metadata.clone();
+        metadata.added_at = app
+            .track_metadata(&track.path)
+            .and_then(|existing| existing.added_at);
+        if metadata.added_at.is_none() {
+            metadata.mark_added_now();
+        }
+        app.add_library_track(track.title.clone(), track.path.clone());
+        app.set_track_metadata(&track.path, metadata);
+    }
+    save_library(app);
+    app.error = Some(result.summary());
+    true
 }
 
-fn queue_library_download(
+/// Apply a settled burst of filesystem changes: refresh which indexed files
+/// exist and probe audio files that appeared.
+fn process_library_watch(
+    watcher: &mut Option<LibraryWatcher>,
     app: &mut App,
-    manager: &DownloadManager,
-    video_id: String,
-    title: String,
-    video_cache_plan: Option<(u16, u16, u16)>,
-) {
-    if !valid_youtube_id(&video_id) {
-        app.error = Some("YouTube returned an invalid media identifier.".to_string());
-        return;
-    }
-    let url = format!("https://www.youtube.com/watch?v={video_id}");
-    let Some(directory) = dirs::audio_dir() else {
-        app.error = Some("The Music directory is unavailable.".to_string());
-        return;
+    scan: &mut Option<LibraryScan>,
+) -> bool {
+    let Some(watcher) = watcher else {
+        return false;
     };
-    if let Err(error) = std::fs::create_dir_all(&directory) {
-        app.error = Some(format!("Could not create the Music directory: {error}"));
-        return;
+    // Songs on a disk that was just mounted or unplugged change availability
+    // without any event from the watched directories.
+    let roots_changed =
+        watcher.roots_due() && app.library_roots.refresh() && app.refresh_library_availability();
+    let roots = app.library_roots.online();
+    if !watcher.watches(&roots, &app.library_folders) {
+        // Roots or folders changed through `:roots`, `:scan`, or a disk.
+        if let Some(restarted) = LibraryWatcher::start(&roots, &app.library_folders) {
+            *watcher = restarted;
+        }
+        return roots_changed;
     }
-    // YouTube IDs make output paths stable and prevent two different tracks
-    // with the same title (or titles that sanitize identically) from colliding.
-    let Ok(path) = library_download_path(&directory, &title, &video_id) else {
-        app.error =
-            Some("The download title could not be converted to a safe filename.".to_string());
-        return;
+    let Some(batch) = watcher.poll() else {
+        return roots_changed;
     };
-    let path_string = path.to_string_lossy().into_owned();
-    let cache_path = path.with_extension("crestvid");
-    let cache_is_available = cache_path
-        .metadata()
-        .is_ok_and(|metadata| metadata.is_file() && metadata.len() > 0);
-    if app.is_library_file_available(&path_string) && cache_is_available {
-        app.error = Some(format!("{title} is already downloaded."));
-        return;
+    let changed = app.refresh_library_availability() || roots_changed;
+    if scan.is_some() {
+        // New files are picked up once the running scan has been applied.
+        watcher.requeue(batch);
+        return changed;
     }
-    if app.is_downloading(&path_string) {
-        app.error = Some(format!("{title} is already downloading."));
-        return;
+    let new_directory = batch.paths.iter().any(|path| path.is_dir());
+    if batch.rescan || new_directory {
+        *scan = start_library_scan(app);
     }
-    app.start_queued_download(path_string.clone(), title.clone());
-    app.error = Some(format!("Queued {title} for download."));
-    if manager
-        .enqueue(DownloadRequest {
-            id: path_string.clone(),
-            title,
-            url,
-            path: path_string.clone(),
-            video_cache_plan,
-        })
-        .is_err()
-    {
-        app.finish_download(&path_string);
-        app.error = Some("The download worker is unavailable.".to_string());
+    if scan.is_none() {
+        let mut paths: Vec<_> = batch.paths.into_iter().collect();
+        if batch.rescan {
+            paths.extend(
+                roots
+                    .iter()
+                    .filter_map(|root| std::fs::read_dir(root).ok())
+                    .flatten()
+                    .flatten()
+                    .map(|entry| entry.path()),
+            );
+        }
+        let new_files: Vec<_> = paths
+            .into_iter()
+            .filter(|path| {
+                let path_text = path.to_string_lossy();
+                library_scan::is_scannable(path)
+                    && path.is_file()
+                    && !app.is_library_path(&path_text)
+                    && !app.dismissed_imports.contains(path_text.as_ref())
+            })
+            .collect();
+        if !new_files.is_empty() {
+            *scan = Some(library_scan::probe_in_background(new_files));
+        }
     }
+    changed
 }
 
-fn library_download_path(
-    directory: &std::path::Path,
-    title: &str,
-    video_id: &str,
-) -> std::io::Result<std::path::PathBuf> {
-    let filename_suffix = format!(" [{video_id}]_ytmusic.mp3");
-    contained_media_path(directory, title, &filename_suffix)
+/// Downloaded Music Only lists the whole library; keep it in step with
+/// background changes.
+fn refresh_library_results(app: &mut App) {
+    let query = LibraryQuery::new(filter_text(&app.input));
+    app.results = library_view(app, &query, app.library_sort);
+    app.selected = app.selected.min(app.results.len().saturating_sub(1));
 }
 
-fn video_cache_plan(app: &App, width: u16, height: u16) -> Option<(u16, u16, u16)> {
-    Some({
-        let samples = app.idle_video_render_mode.samples_per_cell();
-        (
-            even_cache_dimension(width.saturating_mul(samples.0)),
-            even_cache_dimension(height.saturating_mul(samples.1)),
-            if app.idle_video_fps == 0 {
-                30
+/// Re-filter after the filter text changed. Appending to it only narrows the
+/// current results, which keeps typing fast in very large libraries.
+fn update_library_filter(app: &mut App, previous: &str) {
+    let current = filter_text(&app.input);
+    if !current.is_empty() && current.starts_with(filter_text(previous)) {
+        let query = LibraryQuery::new(current);
+        let mut results = std::mem::take(&mut app.results);
+        narrow_view(app, &query, &mut results);
+        app.results = results;
+    } else {
+        let query = LibraryQuery::new(current);
+        app.results = library_view(app, &query, app.library_sort);
+    }
+    app.selected = 0;
+}
+
+/// `:favorite` and `:rate` mark the selected library song.
+fn handle_track_command(input: &str, app: &mut App, selected: Option<&str>) -> Option<String> {
+    let command = match TrackCommand::parse(input)? {
+        Ok(command) => command,
+        Err(message) => return Some(message),
+    };
+    let Some(path) = selected else {
+        return Some("Select a library song first.".to_string());
+    };
+    let message = match command {
+        TrackCommand::ToggleFavorite => toggle_favorite(app, path)?,
+        TrackCommand::Rate(stars) if app.set_rating(path, stars) => {
+            save_library(app);
+            if stars == 0 {
+                "Rating cleared.".to_string()
             } else {
-                app.idle_video_fps
-            },
-        )
+                format!("Rated {}.", "★".repeat(usize::from(stars)))
+            }
+        }
+        TrackCommand::Rate(_) => "Only library songs can be rated.".to_string(),
+    };
+    Some(message)
+}
+
+fn toggle_favorite(app: &mut App, path: &str) -> Option<String> {
+    let favorite = app.toggle_favorite(path)?;
+    save_library(app);
+    Some(if favorite {
+        "Added to favorites.".to_string()
+    } else {
+        "Removed from favorites.".to_string()
     })
 }
 
-fn even_cache_dimension(value: u16) -> u16 {
-    value.clamp(2, 4096) & !1
+fn handle_sort_command(input: &str, app: &mut App) -> Option<String> {
+    Some(match SortCommand::parse(input)? {
+        Ok(SortCommand(sort)) => {
+            app.library_sort = sort;
+            save_settings(app);
+            refresh_library_results(app);
+            format!("Sorted by {}.", sort.label())
+        }
+        Err(message) => message,
+    })
 }
 
-fn process_library_download_completions(manager: &DownloadManager, app: &mut App) -> bool {
+fn process_library_download_completions(
+    manager: &DownloadManager,
+    app: &mut App,
+    player: &mut Player,
+) -> bool {
     let mut changed = false;
     let mut completed_titles = Vec::new();
     let mut failures = Vec::new();
@@ -402,13 +2066,22 @@ fn process_library_download_completions(manager: &DownloadManager, app: &mut App
             id,
             title,
             path,
+            loudness,
+            metadata,
             error,
         } = event
         else {
-            if let LibraryDownloadEvent::Started { id } = event {
-                app.mark_download_started(&id);
-                changed = true;
+            match event {
+                LibraryDownloadEvent::Started { id } => app.mark_download_started(&id),
+                LibraryDownloadEvent::Paused { id } => {
+                    app.set_download_state(&id, DownloadState::Paused)
+                }
+                LibraryDownloadEvent::Progress { id, progress } => {
+                    app.set_download_progress(&id, progress)
+                }
+                LibraryDownloadEvent::Finished { .. } => {}
             }
+            changed = true;
             continue;
         };
         let cancelled = app.finish_download(&id);
@@ -419,8 +2092,16 @@ fn process_library_download_completions(manager: &DownloadManager, app: &mut App
             // This also refreshes availability when an indexed file was missing
             // and the user downloaded it again.
             completed_titles.push(title.clone());
-            app.add_library_track(title, path);
-            save_library(&app.library);
+            app.add_library_track(title, path.clone());
+            if let Some(mut metadata) = metadata {
+                metadata.mark_added_now();
+                app.set_track_metadata(&path, *metadata);
+            }
+            if let Some(loudness) = loudness {
+                app.set_track_loudness(&path, loudness);
+                register_library_gains(app, player);
+            }
+            save_library(app);
         } else {
             failures.push(format!(
                 "{}: {}",
@@ -458,6 +2139,15 @@ fn handle_command_line() -> Result<bool, String> {
             );
             println!("      --remove       Interactively remove Crest Player and its data");
             println!("      --storage      Show application and downloaded-media storage usage");
+            println!(
+                "      --check-library [--fix]  Report missing songs, broken video caches, and leftover partial downloads; --fix repairs them"
+            );
+            println!(
+                "      --export-library <file> [--with-media]  Back up the library, playlists, and settings; --with-media adds downloaded songs"
+            );
+            println!(
+                "      --import-library <file>  Restore a library backup into this Music directory"
+            );
             println!();
             println!("Run without an option to start Crest Player.");
             Ok(true)
@@ -467,6 +2157,19 @@ fn handle_command_line() -> Result<bool, String> {
         }
         [argument] if argument == "--remove" => uninstall::remove_crest_player().map(|_| true),
         [argument] if argument == "--storage" => storage::display_storage().map(|_| true),
+        [argument] if argument == "--check-library" => check_library_from_command_line(false),
+        [argument, fix] if argument == "--check-library" && fix == "--fix" => {
+            check_library_from_command_line(true)
+        }
+        [argument, file] if argument == "--export-library" => {
+            library_archive::export_library(std::path::Path::new(file), false).map(|_| true)
+        }
+        [argument, file, media] if argument == "--export-library" && media == "--with-media" => {
+            library_archive::export_library(std::path::Path::new(file), true).map(|_| true)
+        }
+        [argument, file] if argument == "--import-library" => {
+            library_archive::import_library(std::path::Path::new(file)).map(|_| true)
+        }
         _ => Err(format!(
             "unknown option or argument: {}\nRun 'crest-player --help' for usage.",
             arguments
@@ -477,6 +2180,14 @@ fn handle_command_line() -> Result<bool, String> {
     }
 }
 
+fn check_library_from_command_line(fix: bool) -> Result<bool, String> {
+    let app = App::new();
+    // Caches are sized for the terminal running the check, as when downloading.
+    let (width, height) = crossterm::terminal::size().unwrap_or((120, 40));
+    let plan = video_cache_plan(&app, width, height).unwrap_or((240, 80, 30));
+    library_check::check_from_command_line(app, fix, plan).map(|_| true)
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     match handle_command_line() {
         Ok(true) => return Ok(()),
@@ -497,7 +2208,23 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let backend = CrosstermBackend::new(BufWriter::with_capacity(1024 * 1024, stdout));
     let mut terminal = Terminal::new(backend)?;
     let mut app = App::new();
+    if app.migrate_library_index() {
+        save_library(&app);
+    }
     let mut player = Player::new();
+    player.set_volume(app.volume, app.muted);
+    player.set_normalization_mode(app.normalization_mode);
+    player.set_crossfade_seconds(app.crossfade_seconds);
+    player.set_playback_modes(app.repeat_mode, app.shuffle_enabled);
+    register_library_gains(&app, &mut player);
+    let mut loudness_analysis = start_loudness_analysis(&app);
+    let mut retag_job = None;
+    let mut cache_rebuild = None;
+    let mut fingerprint_job = None;
+    let mut album_art = AlbumArtLoader::default();
+    let mut library_scan = start_library_scan(&app);
+    let mut library_watcher =
+        LibraryWatcher::start(&app.library_roots.online(), &app.library_folders);
     let mut discord_presence = DiscordPresence::new();
     let mut last_tick = Instant::now();
     let mut needs_redraw = true;
@@ -508,7 +2235,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut last_rendered_video_second = 0u64;
     let (lyrics_tx, lyrics_rx) = std::sync::mpsc::channel::<(String, Result<Lyrics, String>)>();
     let (download_tx, download_rx) = std::sync::mpsc::channel::<DownloadFinished>();
-    let library_downloads = DownloadManager::new();
+    let mut library_downloads = DownloadManager::new(app.download_limits);
     let (recommendation_tx, recommendation_rx) =
         std::sync::mpsc::channel::<(String, Result<Recommendation, String>)>();
     let (party_queue_tx, party_queue_rx) = std::sync::mpsc::channel::<(String, String)>();
@@ -533,8 +2260,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut autoplay_requested_for: Option<String>;
     let mut lyrics_requested_for: Option<String> = None;
     let mut lyrics_requested_at: Option<Instant> = None;
+    let mut shown_track_serial = player.track_serial();
     let mut autoplay_history: Vec<String> = Vec::new();
 
+    let mut resume_offer = Session::load();
+    let mut download_offer = PendingDownloads::load();
+    let mut last_session_save = Instant::now();
     let mut startup_selected = 0; // 0 = stream+downloaded, 1 = downloaded only
     let mut settings_selected = 0;
     let mut removal_requested = false;
@@ -563,8 +2294,25 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 &mut app,
                 &mut video_screensaver,
             );
-            process_library_download_completions(&library_downloads, &mut app);
+            process_library_download_completions(&library_downloads, &mut app, &mut player);
+            process_loudness_analysis(&mut loudness_analysis, &mut app, &mut player);
+            process_retag_job(&mut retag_job, &mut app);
+            process_cache_rebuild(&mut cache_rebuild, &mut app);
+            process_fingerprint_job(&mut fingerprint_job, &mut app);
+            process_library_scan(&mut library_scan, &mut app);
+            process_library_watch(&mut library_watcher, &mut app, &mut library_scan);
             player.is_playing();
+            record_play_history(&mut app, &mut player);
+            // A pending resume offer must not be overwritten before it is answered.
+            if resume_offer.is_none() && last_session_save.elapsed() >= SESSION_SAVE_INTERVAL {
+                Session::capture(&player).save();
+                last_session_save = Instant::now();
+            }
+            // The session is offered first, then the unfinished downloads.
+            let resume_prompt = resume_offer
+                .as_ref()
+                .map(Session::summary)
+                .or_else(|| download_offer.as_ref().map(PendingDownloads::summary));
             while let Ok((title, video_id)) = party_queue_rx.try_recv() {
                 queue_youtube_download(&mut app, &mut player, &download_tx, &title, &video_id);
             }
@@ -617,12 +2365,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                             app.hardware_acceleration_enabled,
                         ),
                         autoplay_enabled: app.autoplay_enabled,
+                        normalization_mode: app.normalization_mode,
+                        crossfade_seconds: app.crossfade_seconds,
+                        download_limits: app.download_limits,
+                        download_format: app.download_format,
                         discord_presence_enabled: app.discord_presence_enabled,
                         discord_presence_configured: discord_presence::is_configured(),
                         library_track_count: app.library.len(),
                         home_wallpaper: app.home_wallpaper.as_ref(),
                         playback: (player.title.as_deref(), player.status.as_str()),
-                        party_notice: party_notice.as_deref(),
+                        party_notice: resume_prompt.as_deref().or(party_notice.as_deref()),
                     },
                 )
             })?;
@@ -676,6 +2428,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     }
                     continue;
                 }
+                if handle_volume_key(&key, &mut app, &mut player)
+                    || handle_playback_mode_key(&key, &mut app, &mut player)
+                {
+                    continue;
+                }
                 match key.code {
                     KeyCode::Up => {
                         if settings_page {
@@ -770,6 +2527,35 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                                     app.autoplay_enabled = !app.autoplay_enabled;
                                 }
                                 9 => {
+                                    app.normalization_mode = app.normalization_mode.next();
+                                    player.set_normalization_mode(app.normalization_mode);
+                                    if loudness_analysis.is_none() {
+                                        loudness_analysis = start_loudness_analysis(&app);
+                                    }
+                                }
+                                10 => {
+                                    app.crossfade_seconds =
+                                        if app.crossfade_seconds >= player::MAX_CROSSFADE_SECONDS {
+                                            0
+                                        } else {
+                                            app.crossfade_seconds + 1
+                                        };
+                                    player.set_crossfade_seconds(app.crossfade_seconds);
+                                }
+                                11 | 12 => {
+                                    let DownloadLimits { fetch, encode } = app.download_limits;
+                                    let cycle = |workers: usize| workers % MAX_STAGE_WORKERS + 1;
+                                    app.download_limits = if settings_selected == 11 {
+                                        DownloadLimits::new(cycle(fetch), encode)
+                                    } else {
+                                        DownloadLimits::new(fetch, cycle(encode))
+                                    };
+                                    library_downloads.set_limits(app.download_limits);
+                                }
+                                13 => {
+                                    app.download_format = app.download_format.next();
+                                }
+                                14 => {
                                     if discord_presence::is_configured() {
                                         app.discord_presence_enabled =
                                             !app.discord_presence_enabled;
@@ -782,7 +2568,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                                         );
                                     }
                                 }
-                                10 => {
+                                15 => {
                                     #[cfg(feature = "casting")]
                                     {
                                         speakers_page = true;
@@ -802,12 +2588,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                                     }
                                 }
                                 DELETE_MEDIA_SETTING => {
+                                    for job in &app.downloads {
+                                        library_downloads.cancel(&job.path);
+                                    }
                                     app.cancel_active_downloads();
                                     player.stop();
                                     player.cleanup_temp_media();
                                     player.queue.clear();
                                     let errors = app.delete_all_library_media();
-                                    save_library(&app.library);
+                                    save_library(&app);
                                     app.results = app.library.clone();
                                     app.error = Some(if errors.is_empty() {
                                         "Deleted all songs and videos tracked by Crest Player."
@@ -847,6 +2636,35 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     {
                         settings_page = false;
                     }
+                    KeyCode::Char('r') | KeyCode::Char('R') if !settings_page => {
+                        if let Some(session) = resume_offer.take() {
+                            app.error =
+                                Some(resume_session(&mut app, &mut player, &download_tx, session));
+                        } else if let Some(pending) = download_offer.take() {
+                            app.error = Some(resume_pending_downloads(
+                                &mut app,
+                                &library_downloads,
+                                pending,
+                            ));
+                        }
+                    }
+                    KeyCode::Char('x') | KeyCode::Char('X')
+                        if !settings_page
+                            && (resume_offer.is_some() || download_offer.is_some()) =>
+                    {
+                        if resume_offer.take().is_some() {
+                            Session::default().save();
+                        } else if let Some(pending) = download_offer.take() {
+                            let library_roots = app.library_roots.paths();
+                            for download in &pending.downloads {
+                                remove_partial_download(
+                                    std::path::Path::new(&download.path),
+                                    &library_roots,
+                                );
+                            }
+                            PendingDownloads::default().save();
+                        }
+                    }
                     KeyCode::Char('q') => {
                         break 'home;
                     }
@@ -859,8 +2677,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         // If "downloaded only" mode, set up the UI for library-only navigation
         if downloaded_only_mode {
-            app.results = app.library.clone();
             app.input.clear();
+            refresh_library_results(&mut app);
             app.show_library = false; // results panel is now the library
             app.selected = 0;
         } else {
@@ -908,6 +2726,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                                 title: player.title.as_deref(),
                                 position: player.position(),
                                 video_frame: video_screensaver.frame(),
+                                cover_art: app.album_art.as_ref(),
                                 render_mode: app.idle_video_render_mode,
                                 color_precision: app.color_precision,
                                 synced_lyrics: if app.lyrics_enabled
@@ -972,6 +2791,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     continue;
                 }
                 let was_idle = idle_mode.is_visible();
+                if let Event::Key(key) = &input_event
+                    && (handle_volume_key(key, &mut app, &mut player)
+                        || handle_playback_mode_key(key, &mut app, &mut player))
+                {
+                    if !was_idle {
+                        idle_mode.note_activity();
+                    }
+                    needs_redraw = true;
+                    continue;
+                }
                 if was_idle {
                     let handled_in_cinema = match &input_event {
                         Event::Key(key)
@@ -1010,12 +2839,20 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                                     .modifiers
                                     .contains(crossterm::event::KeyModifiers::CONTROL) =>
                         {
-                            player.stop();
+                            player.skip_to_next();
+                            app.lyrics_active = None;
+                            video_screensaver.restart();
+                            true
+                        }
+                        Event::Key(key)
+                            if key.code == KeyCode::Char('b')
+                                && key
+                                    .modifiers
+                                    .contains(crossterm::event::KeyModifiers::CONTROL) =>
+                        {
+                            let sender = (!downloaded_only_mode).then_some(&download_tx);
+                            play_previous(&mut app, &mut player, sender);
                             app.lyrics_active = None;
-                            if !player.queue.is_empty() {
-                                let (title, path) = player.queue.remove(0);
-                                player.play(&path, &title);
-                            }
                             video_screensaver.restart();
                             true
                         }
@@ -1061,6 +2898,17 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
                 if let Event::Key(key) = input_event {
                     needs_redraw = true;
+                    let history_sender = (!downloaded_only_mode).then_some(&download_tx);
+                    if (downloaded_only_mode && handle_duplicates_key(&key, &mut app, &mut player))
+                        || handle_download_queue_key(&key, &mut app, &library_downloads)
+                        || handle_queue_key(&key, &mut app, &mut player)
+                        || (downloaded_only_mode
+                            && handle_playlist_key(&key, &mut app, &mut player))
+                        || handle_history_key(&key, &mut app, &mut player, history_sender)
+                    {
+                        video_screensaver.restart();
+                        continue;
+                    }
                     if downloaded_only_mode {
                         // Only allow navigation and playback in the downloaded songs list (results panel)
                         match (key.code, key.modifiers) {
@@ -1078,12 +2926,17 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                                 app.lyrics_scroll = app.lyrics_scroll.saturating_sub(5);
                             }
                             (KeyCode::Backspace, m) if m.is_empty() => {
+                                let previous = app.input.clone();
                                 app.input.pop();
                                 app.error = None;
+                                update_library_filter(&mut app, &previous);
                             }
                             (KeyCode::Esc, m) if m.is_empty() => {
-                                app.input.clear();
+                                let previous = std::mem::take(&mut app.input);
                                 app.error = None;
+                                if !filter_text(&previous).is_empty() {
+                                    update_library_filter(&mut app, &previous);
+                                }
                             }
                             (KeyCode::Char('+') | KeyCode::Char('='), m)
                                 if m.contains(crossterm::event::KeyModifiers::ALT) =>
@@ -1097,72 +2950,145 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                                 player.seek_by(-5);
                                 video_screensaver.seek_to(player.position());
                             }
-                            (KeyCode::Down, m) if m.is_empty() => {
-                                if !app.results.is_empty() {
-                                    app.selected = (app.selected + 1).min(app.results.len() - 1);
-                                }
+                            (KeyCode::Char('f'), m)
+                                if m.contains(crossterm::event::KeyModifiers::CONTROL)
+                                    && !app.results.is_empty() =>
+                            {
+                                let path = app.results[app.selected].1.clone();
+                                app.error = toggle_favorite(&mut app, &path);
                             }
-                            (KeyCode::Up, m) if m.is_empty() => {
-                                if !app.results.is_empty() && app.selected > 0 {
-                                    app.selected -= 1;
-                                }
+                            (KeyCode::Char('t'), m)
+                                if m.contains(crossterm::event::KeyModifiers::CONTROL) =>
+                            {
+                                app.library_sort = app.library_sort.next();
+                                save_settings(&app);
+                                refresh_library_results(&mut app);
+                                app.selected = 0;
+                                app.error =
+                                    Some(format!("Sorted by {}.", app.library_sort.label()));
+                            }
+                            (KeyCode::Down, m) if m.is_empty() && !app.results.is_empty() => {
+                                app.selected = (app.selected + 1).min(app.results.len() - 1);
+                            }
+                            (KeyCode::Up, m)
+                                if m.is_empty() && !app.results.is_empty() && app.selected > 0 =>
+                            {
+                                app.selected -= 1;
                             }
                             (KeyCode::Enter, m) if m.is_empty() => {
-                                if !app.input.trim().is_empty() {
-                                    app.error = Some(
-                                        handle_cast_command(&app.input, &mut player)
-                                            .unwrap_or_else(|| {
-                                                match DownloadCommand::parse(&app.input) {
-                                                    Ok(command) => command
-                                                        .execute(&app.library, &mut player.queue),
-                                                    Err(message) => message,
-                                                }
-                                            }),
-                                    );
+                                if app.input.starts_with(':') {
+                                    let input = std::mem::take(&mut app.input);
+                                    let selected =
+                                        app.results.get(app.selected).and_then(|(title, path)| {
+                                            PlaylistEntry::new(title, path, None)
+                                        });
+                                    let selected_path =
+                                        app.results.get(app.selected).map(|(_, path)| path.clone());
+                                    let message = handle_cast_command(&input, &mut player)
+                                        .or_else(|| {
+                                            handle_playback_mode_command(
+                                                &input,
+                                                &mut app,
+                                                &mut player,
+                                            )
+                                        })
+                                        .or_else(|| {
+                                            handle_playlist_command(
+                                                &input,
+                                                &mut app,
+                                                &mut player,
+                                                None,
+                                                selected,
+                                            )
+                                        })
+                                        .or_else(|| {
+                                            handle_retag_command(&input, &app, &mut retag_job)
+                                        })
+                                        .or_else(|| {
+                                            let plan =
+                                                video_cache_plan(&app, screen.width, screen.height);
+                                            handle_check_command(
+                                                &input,
+                                                &mut app,
+                                                &mut cache_rebuild,
+                                                plan,
+                                            )
+                                        })
+                                        .or_else(|| {
+                                            handle_duplicates_command(
+                                                &input,
+                                                &mut app,
+                                                &mut fingerprint_job,
+                                            )
+                                        })
+                                        .or_else(|| {
+                                            handle_scan_command(&input, &mut app, &mut library_scan)
+                                        })
+                                        .or_else(|| handle_roots_command(&input, &mut app))
+                                        .or_else(|| handle_sort_command(&input, &mut app))
+                                        .or_else(|| {
+                                            handle_smart_command(&input, &mut app, &mut player)
+                                        })
+                                        .or_else(|| {
+                                            handle_track_command(
+                                                &input,
+                                                &mut app,
+                                                selected_path.as_deref(),
+                                            )
+                                        })
+                                        .unwrap_or_else(|| match DownloadCommand::parse(&input) {
+                                            Ok(command) => command.execute(
+                                                &app.library,
+                                                &app.library_stats,
+                                                &mut player.queue,
+                                            ),
+                                            Err(message) => message,
+                                        });
+                                    app.error = Some(message);
                                     app.input.clear();
                                 } else if !app.results.is_empty() {
                                     let (title, path) = &app.results[app.selected];
-                                    if player.child.is_some() {
+                                    if app.is_library_track_offline(path) {
+                                        app.error = Some(offline_track_message(title));
+                                    } else if player.child.is_some() {
                                         player.queue.push((title.clone(), path.clone()));
                                     } else {
                                         player.play(path, title);
                                     }
                                 }
                             }
-                            (KeyCode::Char(character), m) if m.is_empty() => {
-                                if !app.input.is_empty() || character == ':' {
-                                    app.input.push(character);
-                                    app.error = None;
+                            // Text filters the list; a leading `:` starts a command.
+                            (KeyCode::Char(character), m)
+                                if (m - crossterm::event::KeyModifiers::SHIFT).is_empty()
+                                    && !(app.input.is_empty() && character == ' ') =>
+                            {
+                                let previous = app.input.clone();
+                                app.input.push(character);
+                                app.error = None;
+                                if !app.input.starts_with(':') {
+                                    update_library_filter(&mut app, &previous);
                                 }
                             }
-                            (KeyCode::Delete, m) if m.is_empty() => {
-                                if !app.results.is_empty() {
-                                    let path = app.results[app.selected].1.clone();
-                                    if let Err(error) = app.remove_library_track(&path) {
-                                        app.error = Some(format!("Could not delete song: {error}"));
-                                    } else {
-                                        app.results.retain(|(_, result_path)| result_path != &path);
-                                        app.selected =
-                                            app.selected.min(app.results.len().saturating_sub(1));
-                                        save_library(&app.library);
-                                    }
+                            (KeyCode::Delete, m) if m.is_empty() && !app.results.is_empty() => {
+                                let path = app.results[app.selected].1.clone();
+                                if let Err(error) = app.remove_library_track(&path) {
+                                    app.error = Some(format!("Could not delete song: {error}"));
+                                } else {
+                                    app.results.retain(|(_, result_path)| result_path != &path);
+                                    app.selected =
+                                        app.selected.min(app.results.len().saturating_sub(1));
+                                    save_library(&app);
                                 }
                             }
                             (KeyCode::Char('n'), m)
                                 if m.contains(crossterm::event::KeyModifiers::CONTROL) =>
                             {
-                                player.stop();
+                                player.skip_to_next();
                                 app.lyrics_active = None;
-                                if !player.queue.is_empty() {
-                                    let (title, url) = player.queue.remove(0);
-                                    player.play(&url, &title);
-                                }
                             }
                             (KeyCode::Char('q'), m)
                                 if m.contains(crossterm::event::KeyModifiers::CONTROL) =>
                             {
-                                player.stop();
-                                player.queue.clear();
                                 break 'home;
                             }
                             (KeyCode::Char('p'), m)
@@ -1177,8 +3103,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                             _ => {}
                         }
                         let playing_changed = player.is_playing();
+                        let history_changed = record_play_history(&mut app, &mut player);
                         discord_presence.sync(&app, &player);
-                        if playing_changed {
+                        if playing_changed || history_changed {
                             needs_redraw = true;
                         }
                         if last_tick.elapsed() >= tick_rate {
@@ -1194,18 +3121,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                             video_screensaver.restart();
                             continue 'home;
                         }
-                        (KeyCode::PageDown, m) if m.is_empty() => {
-                            app.lyrics_scroll = app.lyrics_scroll.saturating_add(5);
-                        }
-                        (KeyCode::PageUp, m) if m.is_empty() => {
-                            app.lyrics_scroll = app.lyrics_scroll.saturating_sub(5);
-                        }
-                        (KeyCode::Backspace, m)
-                            if m.is_empty() && app.results.is_empty() && !app.searching =>
-                        {
-                            app.input.pop();
-                            app.error = None;
-                            needs_redraw = true;
+                        (code, m) if m.is_empty() && code != KeyCode::Enter => {
+                            handle_search_view_key(code, &mut app);
                         }
                         (KeyCode::Char('+') | KeyCode::Char('='), m)
                             if m.contains(crossterm::event::KeyModifiers::ALT) =>
@@ -1219,26 +3136,29 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                             player.seek_by(-5);
                             video_screensaver.seek_to(player.position());
                         }
-                        // Special case: if user types exactly :library, show library in results
-                        (KeyCode::Char(c), m) if m.is_empty() => {
-                            if !app.searching && app.results.is_empty() {
-                                app.input.push(c);
-                                needs_redraw = true;
-                                if app.input == ":library" {
-                                    app.results = app.library.clone();
-                                    app.selected = 0;
-                                    app.show_library = false;
-                                    app.input.clear();
-                                }
-                            }
-                        }
                         (KeyCode::Enter, m) if m.is_empty() => {
                             // If results are empty and input is not empty, trigger a search
                             if app.results.is_empty()
                                 && !app.input.trim().is_empty()
                                 && !app.searching
                             {
-                                if let Some(message) = handle_cast_command(&app.input, &mut player)
+                                let input = app.input.clone();
+                                let current = player.current_entry().and_then(|(title, path)| {
+                                    PlaylistEntry::new(&title, &path, player.video_id_for(&path))
+                                });
+                                if let Some(message) = handle_cast_command(&input, &mut player)
+                                    .or_else(|| {
+                                        handle_playback_mode_command(&input, &mut app, &mut player)
+                                    })
+                                    .or_else(|| {
+                                        handle_playlist_command(
+                                            &input,
+                                            &mut app,
+                                            &mut player,
+                                            Some(&download_tx),
+                                            current,
+                                        )
+                                    })
                                 {
                                     app.error = Some(message);
                                     app.input.clear();
@@ -1264,7 +3184,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                                 if app.show_library {
                                     if !app.library.is_empty() {
                                         let (title, path) = &app.library[app.selected];
-                                        if player.child.is_some() {
+                                        if app.is_library_track_offline(path) {
+                                            app.error = Some(offline_track_message(title));
+                                        } else if player.child.is_some() {
                                             player.queue.push((title.clone(), path.clone()));
                                         } else {
                                             player.play(path, title);
@@ -1287,21 +3209,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         (KeyCode::Char('n'), m)
                             if m.contains(crossterm::event::KeyModifiers::CONTROL) =>
                         {
-                            // Ctrl+n: Skip to next song in queue
-                            player.stop();
+                            // Ctrl+n: Skip to the next song, honoring repeat and shuffle
+                            player.skip_to_next();
                             app.lyrics_active = None;
-                            // Play next in queue if available (FIFO order)
-                            if !player.queue.is_empty() {
-                                let (title, url) = player.queue.remove(0);
-                                player.play(&url, &title);
-                            }
                             needs_redraw = true;
                         }
                         (KeyCode::Char('q'), m)
                             if m.contains(crossterm::event::KeyModifiers::CONTROL) =>
                         {
-                            player.stop();
-                            player.queue.clear();
                             break 'home;
                         }
                         (KeyCode::Char('p'), m)
@@ -1315,19 +3230,6 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                             }
                             needs_redraw = true;
                         }
-                        (KeyCode::Delete, m) if m.is_empty() && app.show_library => {
-                            if !app.library.is_empty() {
-                                let path = app.library[app.selected].1.clone();
-                                if let Err(error) = app.remove_library_track(&path) {
-                                    app.error = Some(format!("Could not delete song: {error}"));
-                                } else {
-                                    app.selected =
-                                        app.selected.min(app.library.len().saturating_sub(1));
-                                    save_library(&app.library);
-                                }
-                                needs_redraw = true;
-                            }
-                        }
                         (KeyCode::Char('l'), m)
                             if m.contains(crossterm::event::KeyModifiers::CONTROL)
                                 && !app.show_library =>
@@ -1349,47 +3251,6 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                                 needs_redraw = true;
                             }
                         }
-                        (KeyCode::Char('v'), m) if m.is_empty() => {
-                            // Toggle library view
-                            app.show_library = !app.show_library;
-                            needs_redraw = true;
-                        }
-                        // j/k navigation removed
-                        (KeyCode::Down, m) if m.is_empty() => {
-                            if app.show_library {
-                                if !app.library.is_empty() {
-                                    app.selected = (app.selected + 1).min(app.library.len() - 1);
-                                    needs_redraw = true;
-                                }
-                            } else if !app.results.is_empty() {
-                                app.selected = (app.selected + 1).min(app.results.len() - 1);
-                                needs_redraw = true;
-                            }
-                        }
-                        // j/k navigation removed
-                        (KeyCode::Up, m) if m.is_empty() => {
-                            if app.show_library {
-                                if !app.library.is_empty() && app.selected > 0 {
-                                    app.selected -= 1;
-                                    needs_redraw = true;
-                                }
-                            } else if !app.results.is_empty() && app.selected > 0 {
-                                app.selected -= 1;
-                                needs_redraw = true;
-                            }
-                        }
-                        (KeyCode::Char(c), m) if m.is_empty() => {
-                            if !app.searching && app.results.is_empty() {
-                                app.input.push(c);
-                                needs_redraw = true;
-                            }
-                        }
-                        (KeyCode::Esc, m) if m.is_empty() && !app.results.is_empty() => {
-                            app.results.clear();
-                            app.input.clear();
-                            app.selected = 0;
-                            needs_redraw = true;
-                        }
                         _ => {}
                     }
                 }
@@ -1403,7 +3264,31 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             ) {
                 needs_redraw = true;
             }
-            if process_library_download_completions(&library_downloads, &mut app) {
+            if process_library_download_completions(&library_downloads, &mut app, &mut player) {
+                needs_redraw = true;
+            }
+            if process_loudness_analysis(&mut loudness_analysis, &mut app, &mut player) {
+                needs_redraw = true;
+            }
+            if process_retag_job(&mut retag_job, &mut app) {
+                needs_redraw = true;
+            }
+            if process_cache_rebuild(&mut cache_rebuild, &mut app) {
+                needs_redraw = true;
+            }
+            if process_fingerprint_job(&mut fingerprint_job, &mut app) {
+                needs_redraw = true;
+            }
+            if process_library_scan(&mut library_scan, &mut app) {
+                if downloaded_only_mode {
+                    refresh_library_results(&mut app);
+                }
+                needs_redraw = true;
+            }
+            if process_library_watch(&mut library_watcher, &mut app, &mut library_scan) {
+                needs_redraw = true;
+            }
+            if process_album_art(&mut album_art, &mut app, &player) {
                 needs_redraw = true;
             }
             while let Ok((title, video_id)) = party_queue_rx.try_recv() {
@@ -1414,6 +3299,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 let seed_is_relevant = player.title.as_ref() == Some(&seed_title)
                     || player.last_finished_title() == Some(seed_title.as_str());
                 if app.autoplay_enabled
+                    && app.repeat_mode == RepeatMode::Off
                     && seed_is_relevant
                     && player.queue.is_empty()
                     && let Ok(recommendation) = result
@@ -1433,12 +3319,30 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }
             let playing_changed = player.is_playing();
+            let history_changed = record_play_history(&mut app, &mut player);
+            if player.track_serial() != shown_track_serial {
+                // A crossfade starts the next track while the previous one
+                // still plays; the video and lyrics follow the new track.
+                shown_track_serial = player.track_serial();
+                video_screensaver.restart();
+                app.lyrics_active = None;
+                app.lyrics_scroll = 0;
+                needs_redraw = true;
+            }
+            if resume_offer.is_none() && last_session_save.elapsed() >= SESSION_SAVE_INTERVAL {
+                Session::capture(&player).save();
+                last_session_save = Instant::now();
+            }
             discord_presence.sync(&app, &player);
-            if playing_changed {
+            if playing_changed || history_changed {
                 needs_redraw = true;
             }
 
-            if app.autoplay_enabled && player.status == "Playing" {
+            // Repeat modes never run out of queued music, so autoplay stays idle.
+            if app.autoplay_enabled
+                && app.repeat_mode == RepeatMode::Off
+                && player.status == "Playing"
+            {
                 if let Some(title) = player.title.clone()
                     && autoplay_requested_for.as_ref() != Some(&title)
                     && player.queue.is_empty()
@@ -1590,7 +3494,26 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
     // Save and load library to a file in the Music directory
+    // Quitting keeps the queue for the next launch unless Crest Player is being
+    // removed or the previous session was never answered.
+    if !removal_requested && resume_offer.is_none() {
+        Session::capture(&player).save();
+    }
+    // Unfinished library downloads, including an unanswered offer, are kept
+    // for the next launch along with their partial files.
+    if !removal_requested {
+        let mut pending = download_offer.take().unwrap_or_default();
+        pending.merge(PendingDownloads {
+            downloads: library_downloads
+                .pending()
+                .iter()
+                .filter_map(|(request, paused)| PendingDownload::new(request, *paused))
+                .collect(),
+        });
+        pending.save();
+    }
     player.shutdown();
+    record_play_history(&mut app, &mut player);
     disable_raw_mode()?;
     execute!(io::stdout(), DisableMouseCapture, LeaveAlternateScreen)?;
 
@@ -1604,7 +3527,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
 #[cfg(test)]
 mod frame_pacer_tests {
-    use super::{FramePacer, even_cache_dimension, library_download_path, next_stream_queue_path};
+    use super::{
+        App, FramePacer, KeyCode, Player, PlaylistEntry, even_cache_dimension, export_playlist,
+        handle_search_view_key, import_playlist, library_download_path, next_stream_queue_path,
+        queue_playlist,
+    };
+    use crate::playlists::{Playlist, PlaylistFormat};
     use std::collections::HashSet;
     use std::time::Duration;
 
@@ -1643,12 +3571,103 @@ mod frame_pacer_tests {
     #[test]
     fn long_duplicate_titles_keep_distinct_youtube_ids_in_their_paths() {
         let title = "x".repeat(300);
-        let first =
-            library_download_path(std::path::Path::new("/music"), &title, "aaaaaaaaaaa").unwrap();
-        let second =
-            library_download_path(std::path::Path::new("/music"), &title, "bbbbbbbbbbb").unwrap();
+        let path = |video_id| {
+            library_download_path(std::path::Path::new("/music"), &title, video_id, "opus").unwrap()
+        };
+        let (first, second) = (path("aaaaaaaaaaa"), path("bbbbbbbbbbb"));
         assert_ne!(first, second);
+        assert_eq!(first.extension().unwrap(), "opus");
         assert!(first.to_string_lossy().contains("aaaaaaaaaaa"));
         assert!(second.to_string_lossy().contains("bbbbbbbbbbb"));
     }
+
+    #[test]
+    fn playlists_play_and_export_nested_imported_tracks() {
+        let base = std::env::temp_dir().join(format!(
+            "crest-player-nested-playlist-{}",
+            std::process::id()
+        ));
+        let album = base.join("Collection/Artist/Album");
+        std::fs::create_dir_all(&album).unwrap();
+        let music = base.join("Music");
+        std::fs::create_dir_all(&music).unwrap();
+        let song = album.join("Song.mp3");
+        std::fs::write(&song, b"audio").unwrap();
+        let song = song.canonicalize().unwrap();
+        let song_path = song.to_string_lossy().into_owned();
+
+        let mut app = App::new();
+        app.add_library_track("Nested Song".to_string(), song_path.clone());
+        let name = app.playlists.insert_unique(Playlist {
+            name: "Nested Test".to_string(),
+            entries: vec![PlaylistEntry::new("Nested Song", &song_path, None).unwrap()],
+        });
+        let mut player = Player::new();
+        queue_playlist(&mut app, &mut player, None, &name, false);
+        assert_eq!(
+            player.queue,
+            vec![("Nested Song".to_string(), song_path.clone())]
+        );
+
+        let playlist = app.playlists.find(&name).unwrap().clone();
+        let in_library =
+            |path: &std::path::Path| app.is_library_file_available(&path.to_string_lossy());
+        for format in [PlaylistFormat::M3u8, PlaylistFormat::Xspf] {
+            let (file, skipped) = export_playlist(&playlist, format, &music, in_library).unwrap();
+            assert_eq!(skipped, 0);
+            let (imported, skipped) = import_playlist(&file, &music, in_library).unwrap();
+            assert_eq!(skipped, 0);
+            assert_eq!(imported.entries[0].path, song_path);
+        }
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[test]
+    fn dismissed_imports_leave_their_files_and_stay_out_of_the_library() {
+        let folder = std::env::temp_dir().join(format!(
+            "crest-player-dismissed-import-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&folder).unwrap();
+        let copy = folder.join("Copy.mp3");
+        std::fs::write(&copy, b"audio").unwrap();
+        let copy = copy.canonicalize().unwrap().to_string_lossy().into_owned();
+
+        let mut app = App::new();
+        app.add_library_track("Copy".to_string(), copy.clone());
+        app.set_track_metadata(
+            &copy,
+            crate::track_metadata::TrackMetadata {
+                imported: true,
+                ..Default::default()
+            },
+        );
+        app.dismiss_imported_track(&copy);
+        assert!(!app.is_library_path(&copy));
+        assert!(app.dismissed_imports.contains(&copy));
+        assert!(std::path::Path::new(&copy).is_file());
+        let _ = std::fs::remove_dir_all(folder);
+    }
+
+    #[test]
+    fn search_view_keys_are_consumed_by_their_own_arm() {
+        let mut app = App::new();
+        app.show_library = false;
+        app.results = vec![("Song".to_string(), "dQw4w9WgXcQ".to_string())];
+        // Typing is ignored while results are listed.
+        handle_search_view_key(KeyCode::Char('v'), &mut app);
+        assert!(!app.show_library);
+        assert!(app.input.is_empty());
+
+        app.results.clear();
+        app.selected = 0;
+        handle_search_view_key(KeyCode::Down, &mut app);
+        handle_search_view_key(KeyCode::Up, &mut app);
+        assert_eq!(app.selected, 0);
+        for character in ":library".chars() {
+            handle_search_view_key(KeyCode::Char(character), &mut app);
+        }
+        assert!(app.input.is_empty());
+        assert_eq!(app.results, app.library);
+    }
 }
@@ -0,0 +1,186 @@
+use crate::download_format::{AUDIO_EXTENSIONS, DownloadFormat};
+use crate::download_manager::DownloadRequest;
+use crate::security::{read_file_limited, sanitize_display_text_limited, valid_youtube_id};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const MAX_PENDING_FILE_BYTES: usize = 4 * 1024 * 1024;
+
+/// A permanent download that had not finished when Crest Player exited.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PendingDownload {
+    pub id: String,
+    pub title: String,
+    pub video_id: String,
+    pub path: String,
+    pub video_cache_plan: Option<(u16, u16, u16)>,
+    /// Files saved before formats were selectable hold MP3 downloads.
+    #[serde(default)]
+    pub format: DownloadFormat,
+    #[serde(default)]
+    pub paused: bool,
+}
+
+impl PendingDownload {
+    pub fn new(request: &DownloadRequest, paused: bool) -> Option<Self> {
+        let video_id = request.url.split_once("v=")?.1;
+        valid_youtube_id(video_id).then(|| Self {
+            id: request.id.clone(),
+            title: request.title.clone(),
+            video_id: video_id.to_string(),
+            path: request.path.clone(),
+            video_cache_plan: request.video_cache_plan,
+            format: request.format,
+            paused,
+        })
+    }
+
+    /// A request for the manager. `library_roots` are the current ones, so a
+    /// download saved for a root that has since been removed is refused.
+    pub fn request(&self, library_roots: &[PathBuf]) -> DownloadRequest {
+        DownloadRequest {
+            id: self.id.clone(),
+            title: self.title.clone(),
+            url: format!("https://www.youtube.com/watch?v={}", self.video_id),
+            path: self.path.clone(),
+            library_roots: library_roots.into(),
+            format: self.format,
+            video_cache_plan: self.video_cache_plan,
+        }
+    }
+}
+
+/// The download queue as it stood at exit, kept in `downloads.json` beside
+/// `settings.json` until the user resumes or discards it.
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct PendingDownloads {
+    pub downloads: Vec<PendingDownload>,
+}
+
+impl PendingDownloads {
+    pub fn is_empty(&self) -> bool {
+        self.downloads.is_empty()
+    }
+
+    pub fn load() -> Option<Self> {
+        let contents = read_file_limited(pending_path()?, MAX_PENDING_FILE_BYTES).ok()?;
+        let saved: Self = serde_json::from_slice(&contents).ok()?;
+        let pending = Self {
+            downloads: saved
+                .downloads
+                .into_iter()
+                .filter(|download| {
+                    valid_youtube_id(&download.video_id)
+                        && download.id == download.path
+                        && Path::new(&download.path)
+                            .extension()
+                            .and_then(|extension| extension.to_str())
+                            .is_some_and(|extension| AUDIO_EXTENSIONS.contains(&extension))
+                })
+                .map(|download| PendingDownload {
+                    title: sanitize_display_text_limited(&download.title, 512),
+                    ..download
+                })
+                .collect(),
+        };
+        (!pending.is_empty()).then_some(pending)
+    }
+
+    /// An empty queue removes the file so the next launch offers nothing.
+    pub fn save(&self) {
+        let Some(path) = pending_path() else { return };
+        if self.is_empty() {
+            let _ = std::fs::remove_file(path);
+            return;
+        }
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        if let Ok(json) = serde_json::to_string(self) {
+            let _ = std::fs::write(path, json);
+        }
+    }
+
+    /// Add downloads not already listed, keeping the existing order first.
+    pub fn merge(&mut self, other: Self) {
+        for download in other.downloads {
+            if !self.downloads.iter().any(|known| known.id == download.id) {
+                self.downloads.push(download);
+            }
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "Resume {} unfinished download(s)? R resume · X discard",
+            self.downloads.len()
+        )
+    }
+}
+
+fn pending_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|directory| directory.join("crest-player/downloads.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PendingDownload, PendingDownloads};
+    use crate::download_format::DownloadFormat;
+    use crate::download_manager::DownloadRequest;
+    use std::path::PathBuf;
+
+    #[test]
+    fn pending_downloads_round_trip_through_requests() {
+        let request = DownloadRequest {
+            id: "/music/Song_ytmusic.opus".to_string(),
+            title: "Song".to_string(),
+            url: "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string(),
+            path: "/music/Song_ytmusic.opus".to_string(),
+            library_roots: Box::new([]),
+            format: DownloadFormat::Opus128,
+            video_cache_plan: Some((160, 90, 15)),
+        };
+        let pending = PendingDownload::new(&request, true).unwrap();
+        assert_eq!(pending.video_id, "dQw4w9WgXcQ");
+        let restored = pending.request(&[PathBuf::from("/music")]);
+        assert_eq!(restored.url, request.url);
+        assert_eq!(restored.video_cache_plan, request.video_cache_plan);
+        assert_eq!(restored.format, DownloadFormat::Opus128);
+        assert_eq!(&*restored.library_roots, [PathBuf::from("/music")]);
+
+        let invalid = DownloadRequest {
+            url: "https://example.com/watch?v=../../etc".to_string(),
+            ..request
+        };
+        assert!(PendingDownload::new(&invalid, false).is_none());
+    }
+
+    #[test]
+    fn merging_keeps_the_first_copy_of_each_download() {
+        let download = |id: &str| PendingDownload {
+            id: id.to_string(),
+            title: id.to_string(),
+            video_id: "dQw4w9WgXcQ".to_string(),
+            path: id.to_string(),
+            video_cache_plan: None,
+            format: DownloadFormat::default(),
+            paused: false,
+        };
+        let mut pending = PendingDownloads {
+            downloads: vec![download("a.mp3"), download("b.mp3")],
+        };
+        pending.merge(PendingDownloads {
+            downloads: vec![
+                PendingDownload {
+                    paused: true,
+                    ..download("b.mp3")
+                },
+                download("c.mp3"),
+            ],
+        });
+        let ids: Vec<_> = pending.downloads.iter().map(|d| d.id.as_str()).collect();
+        assert_eq!(ids, ["a.mp3", "b.mp3", "c.mp3"]);
+        assert!(!pending.downloads[1].paused);
+    }
+}
@@ -1,13 +1,94 @@
 #[cfg(feature = "casting")]
 use crate::casting::{CastTarget, Caster};
+use crate::download_format::AUDIO_EXTENSIONS;
+use crate::loudness::{NormalizationMode, ReplayGain, analyze_track_loudness};
 use crate::security::{external_command, sanitize_display_text_limited, valid_media_url};
 use std::collections::HashMap;
 use std::process::{Child, Stdio};
 use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::time::{Duration, Instant};
 
 const RECONNECT_OVERLAP: Duration = Duration::from_secs(5);
 const STREAM_READ_TIMEOUT_MICROS: &str = "5000000";
+pub const MAX_VOLUME: u8 = 150;
+// ffplay cannot change its gain after startup, so level changes restart the
+// audio process. Coalesce held keys into one restart.
+const VOLUME_RESTART_DELAY: Duration = Duration::from_millis(250);
+pub const MAX_CROSSFADE_SECONDS: u8 = 12;
+// Start the next decoder slightly before the current one drains so process
+// startup does not leave an audible gap between consecutive tracks.
+const GAPLESS_LEAD: Duration = Duration::from_millis(200);
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum RepeatMode {
+    #[default]
+    Off,
+    Queue,
+    Track,
+}
+
+impl RepeatMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Queue,
+            Self::Queue => Self::Track,
+            Self::Track => Self::Off,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Off => "Repeat off",
+            Self::Queue => "Repeat queue",
+            Self::Track => "Repeat track",
+        }
+    }
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Queue => "queue",
+            Self::Track => "track",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "off" => Some(Self::Off),
+            "queue" | "all" => Some(Self::Queue),
+            "track" | "one" => Some(Self::Track),
+            _ => None,
+        }
+    }
+}
+
+/// A track that stopped being current, reported for the play history.
+pub struct PlayedTrack {
+    pub title: String,
+    pub path: String,
+    pub video_id: Option<String>,
+    pub listened: Duration,
+    pub outcome: PlayOutcome,
+}
+
+/// How a track stopped playing.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PlayOutcome {
+    /// Played to its end, or handed over to the next track.
+    Finished,
+    /// Skipped to the next queue entry.
+    Skipped,
+    /// Stopped, replaced, or interrupted any other way.
+    Stopped,
+}
+
+// The previous track keeps playing while the next one fades in.
+struct OutgoingTrack {
+    child: Child,
+    stop_at: Instant,
+    temp_file: Option<String>,
+}
 
 pub struct Player {
     pub child: Option<Child>,
@@ -23,12 +104,40 @@ pub struct Player {
     stream_durations: HashMap<String, Duration>,
     audio_retry_at: Option<Instant>,
     cast_clock_calibrated: bool,
+    volume: u8,
+    muted: bool,
+    volume_restart_at: Option<Instant>,
+    normalization: NormalizationMode,
+    replay_gains: HashMap<String, ReplayGain>,
+    // Streamed tracks are measured after their download, off the UI thread.
+    gain_sender: Sender<(String, ReplayGain)>,
+    measured_gains: Receiver<(String, ReplayGain)>,
+    crossfade: Duration,
+    track_durations: HashMap<String, Duration>,
+    // Local durations are probed with ffprobe off the UI thread; the
+    // crossfade waits for them. `None` reports a probe that failed.
+    duration_sender: Sender<(String, Option<Duration>)>,
+    probed_durations: Receiver<(String, Option<Duration>)>,
+    // The queue head whose probe was already started, so it runs once.
+    probed_queue_head: Option<String>,
+    outgoing: Option<OutgoingTrack>,
+    fade_in_next: bool,
+    // Counts started tracks, so views can follow a crossfade handover.
+    track_serial: u64,
+    repeat: RepeatMode,
+    shuffle: bool,
+    // The track that just finished, kept until a repeat mode requeues it.
+    repeat_entry: Option<(String, String)>,
+    played: Vec<PlayedTrack>,
+    start_offsets: HashMap<String, Duration>,
     #[cfg(feature = "casting")]
     caster: Caster,
 }
 
 impl Player {
     pub fn new() -> Self {
+        let (gain_sender, measured_gains) = mpsc::channel();
+        let (duration_sender, probed_durations) = mpsc::channel();
         Self {
             child: None,
             title: None,
@@ -43,6 +152,26 @@ impl Player {
             stream_durations: HashMap::new(),
             audio_retry_at: None,
             cast_clock_calibrated: false,
+            volume: 100,
+            muted: false,
+            volume_restart_at: None,
+            normalization: NormalizationMode::Off,
+            replay_gains: HashMap::new(),
+            gain_sender,
+            measured_gains,
+            crossfade: Duration::default(),
+            track_durations: HashMap::new(),
+            duration_sender,
+            probed_durations,
+            probed_queue_head: None,
+            outgoing: None,
+            fade_in_next: false,
+            track_serial: 0,
+            repeat: RepeatMode::Off,
+            shuffle: false,
+            repeat_entry: None,
+            played: Vec::new(),
+            start_offsets: HashMap::new(),
             #[cfg(feature = "casting")]
             caster: Caster::new(),
         }
@@ -62,9 +191,17 @@ impl Player {
             if last.contains("ytmusic_play_") && last.ends_with(".mp3") {
                 let _ = fs::remove_file(&last);
                 self.video_sources.remove(&last);
+                self.replay_gains.remove(&last);
+                self.track_durations.remove(&last);
             }
         }
+        // A crossfade hands over the previous decoder; keep it playing.
+        let outgoing = self.outgoing.take();
+        let fade_in = self.fade_in_next;
         self.stop();
+        self.outgoing = outgoing;
+        self.fade_in_next = fade_in;
+        self.track_serial += 1;
         // A streaming download creates its output file before it has finished.
         // Do not treat that partial file as playable until the completion event
         // replaces the temporary queue label with the resolved title.
@@ -109,8 +246,16 @@ impl Player {
         } else {
             self.last_temp_file = None;
         }
+        self.probe_duration(&play_path);
 
-        let mut command = self.audio_clock_command(&play_path, None);
+        let start_offset = self
+            .start_offsets
+            .remove(path)
+            .or_else(|| self.start_offsets.remove(&play_path))
+            .unwrap_or_default();
+        let seek = (!start_offset.is_zero()).then(|| format!("{:.3}", start_offset.as_secs_f64()));
+        let mut command = self.audio_clock_command(&play_path, seek.as_deref());
+        self.fade_in_next = false;
         let child = command
             .stdin(Stdio::null())
             .stdout(Stdio::null())
@@ -125,13 +270,16 @@ impl Player {
         self.title = Some(title.to_string());
         self.last_finished_title = None;
         self.audio_retry_at = None;
+        self.volume_restart_at = None;
         self.cast_clock_calibrated = false;
         self.status = "Playing".to_string();
-        self.elapsed_before_start = Duration::default();
+        self.elapsed_before_start = start_offset;
         self.playback_started = Some(Instant::now());
         #[cfg(feature = "casting")]
         if let Err(error) = self.caster.play(&play_path) {
             self.status = error;
+        } else if !start_offset.is_zero() {
+            self.caster.seek_to(start_offset);
         }
     }
 
@@ -140,6 +288,100 @@ impl Player {
             .insert(audio_path.to_string(), Arc::from(youtube_url));
     }
 
+    pub fn register_replay_gain(&mut self, audio_path: &str, gain: ReplayGain) {
+        let previous = self.replay_gains.insert(audio_path.to_string(), gain);
+        if previous != Some(gain)
+            && self.current_path.as_deref() == Some(audio_path)
+            && self.normalization != NormalizationMode::Off
+        {
+            self.volume_restart_at = Some(Instant::now() + VOLUME_RESTART_DELAY);
+        }
+    }
+
+    /// Measure a downloaded stream in the background. Its gain is registered
+    /// by [`Self::is_playing`] once known, restarting the track if it is
+    /// already playing.
+    pub fn measure_stream_loudness(&self, audio_path: &str) {
+        let sender = self.gain_sender.clone();
+        let path = audio_path.to_string();
+        std::thread::spawn(move || {
+            if let Ok(loudness) = analyze_track_loudness(std::path::Path::new(&path)) {
+                let gain = ReplayGain {
+                    track_gain: Some(loudness.track_gain()),
+                    album_gain: None,
+                };
+                let _ = sender.send((path, gain));
+            }
+        });
+    }
+
+    fn receive_measured_gains(&mut self) {
+        while let Ok((path, gain)) = self.measured_gains.try_recv() {
+            // A track removed meanwhile must not leave a gain entry behind.
+            let still_referenced = self.current_path.as_deref() == Some(path.as_str())
+                || self.queue.iter().any(|(_, queued)| queued == &path);
+            if still_referenced {
+                self.register_replay_gain(&path, gain);
+            }
+        }
+    }
+
+    /// Probe a local file's duration in the background unless it is known.
+    fn probe_duration(&self, path: &str) {
+        if valid_media_url(path)
+            || self.stream_durations.contains_key(path)
+            || self.track_durations.contains_key(path)
+        {
+            return;
+        }
+        let sender = self.duration_sender.clone();
+        let path = path.to_string();
+        std::thread::spawn(move || {
+            let duration = crate::search::probe_duration(std::path::Path::new(&path));
+            let _ = sender.send((path, duration));
+        });
+    }
+
+    /// Probe the entry that plays next while the current one is running, so
+    /// its crossfade is known by the time it starts.
+    fn probe_queue_head(&mut self) {
+        let Some((title, path)) = self.queue.first() else {
+            return;
+        };
+        if title.ends_with(" (Downloading...)")
+            || self.probed_queue_head.as_deref() == Some(path.as_str())
+        {
+            return;
+        }
+        let path = path.clone();
+        self.probe_duration(&path);
+        self.probed_queue_head = Some(path);
+    }
+
+    fn receive_probed_durations(&mut self) {
+        while let Ok((path, duration)) = self.probed_durations.try_recv() {
+            let Some(duration) = duration else {
+                continue;
+            };
+            let still_referenced = self.current_path.as_deref() == Some(path.as_str())
+                || self.queue.iter().any(|(_, queued)| queued == &path);
+            if still_referenced {
+                self.track_durations.insert(path, duration);
+            }
+        }
+    }
+
+    pub fn set_normalization_mode(&mut self, mode: NormalizationMode) {
+        if mode != self.normalization {
+            self.normalization = mode;
+            self.volume_restart_at = Some(Instant::now() + VOLUME_RESTART_DELAY);
+        }
+    }
+
+    pub fn set_crossfade_seconds(&mut self, seconds: u8) {
+        self.crossfade = Duration::from_secs(u64::from(seconds.min(MAX_CROSSFADE_SECONDS)));
+    }
+
     pub fn register_stream_duration(&mut self, audio_path: &str, duration: Duration) {
         self.stream_durations
             .insert(audio_path.to_string(), duration);
@@ -171,22 +413,34 @@ impl Player {
             let _ = std::fs::remove_file(&audio_path);
             self.video_sources.remove(&audio_path);
             self.stream_durations.remove(&audio_path);
+            self.replay_gains.remove(&audio_path);
+            self.track_durations.remove(&audio_path);
         }
+        self.stop_outgoing();
         for (_, path) in self.queue.clone() {
             self.video_sources.remove(&path);
             self.stream_durations.remove(&path);
+            self.track_durations.remove(&path);
             if path.contains("ytmusic_play_") {
                 let _ = std::fs::remove_file(&path);
+                self.replay_gains.remove(&path);
             }
         }
     }
 
+    /// Changes whenever a new track starts, including one that starts during
+    /// a crossfade while the previous track is still audible.
+    pub fn track_serial(&self) -> u64 {
+        self.track_serial
+    }
+
     pub fn current_video_id(&self) -> Option<String> {
-        let source = self
-            .current_path
-            .as_ref()
-            .and_then(|path| self.video_sources.get(path))?;
-        source
+        self.video_id_for(self.current_path.as_ref()?)
+    }
+
+    pub fn video_id_for(&self, path: &str) -> Option<String> {
+        self.video_sources
+            .get(path)?
             .split_once("v=")
             .map(|(_, value)| value.split('&').next().unwrap_or(value).to_string())
     }
@@ -198,7 +452,11 @@ impl Player {
             if let Ok(entries) = std::fs::read_dir(dir) {
                 for entry in entries.flatten() {
                     let fname = entry.file_name().to_string_lossy().to_string();
-                    if fname.starts_with(&prefix) && fname.ends_with(".mp3") {
+                    let audio = std::path::Path::new(&fname)
+                        .extension()
+                        .and_then(|extension| extension.to_str())
+                        .is_some_and(|extension| AUDIO_EXTENSIONS.contains(&extension));
+                    if fname.starts_with(&prefix) && audio {
                         return Some(entry.path().to_string_lossy().to_string());
                     }
                 }
@@ -207,6 +465,7 @@ impl Player {
         None
     }
     pub fn pause(&mut self) {
+        self.stop_outgoing();
         if let Some(child) = &mut self.child {
             let _ = external_command("kill")
                 .arg("-STOP")
@@ -233,12 +492,22 @@ impl Player {
         }
     }
     pub fn stop(&mut self) {
+        self.stop_as(PlayOutcome::Stopped);
+    }
+
+    fn stop_as(&mut self, outcome: PlayOutcome) {
+        if self.status != "Downloading..." {
+            self.record_played(outcome);
+        }
         #[cfg(feature = "casting")]
         self.caster.stop();
+        self.stop_outgoing();
         if let Some(mut child) = self.child.take() {
             let _ = child.kill();
             let _ = child.wait();
         }
+        self.fade_in_next = false;
+        self.repeat_entry = None;
         self.status = "Stopped".to_string();
         self.title = None;
         self.current_path = None;
@@ -246,6 +515,7 @@ impl Player {
         self.elapsed_before_start = Duration::default();
         self.last_finished_title = None;
         self.audio_retry_at = None;
+        self.volume_restart_at = None;
         // Do not clear the queue here; only clear on quit
     }
 
@@ -290,25 +560,37 @@ impl Player {
             self.elapsed_before_start = Duration::default();
             self.cast_clock_calibrated = true;
         }
+        self.receive_measured_gains();
+        self.receive_probed_durations();
+        self.probe_queue_head();
+        if self
+            .volume_restart_at
+            .is_some_and(|restart_at| Instant::now() >= restart_at)
+        {
+            self.volume_restart_at = None;
+            if self.child.is_some() && !self.casting_active() {
+                self.seek_by(0);
+                return true;
+            }
+        }
+        self.reap_outgoing();
+        if self.should_start_next_track() {
+            return self.start_next_track();
+        }
         if let Some(child) = &mut self.child {
             match child.try_wait() {
                 Ok(Some(exit_status)) if exit_status.success() && self.reached_expected_end() => {
                     self.child = None;
                     self.status = "Stopped".to_string();
-                    self.last_finished_title = self.title.take();
-                    if let Some(path) = self.current_path.take() {
-                        self.video_sources.remove(&path);
-                        self.stream_durations.remove(&path);
-                    }
-                    self.playback_started = None;
-                    self.elapsed_before_start = Duration::default();
                     // After playback, delete temp streaming file if needed
-                    if let Some(last) = self.last_temp_file.take()
+                    if let Some(last) = self.finish_current_track()
                         && last.contains("ytmusic_play_")
                         && last.ends_with(".mp3")
                     {
                         let _ = fs::remove_file(&last);
                         self.video_sources.remove(&last);
+                        self.replay_gains.remove(&last);
+                        self.track_durations.remove(&last);
                     }
                     self.advance_queue()
                 }
@@ -356,6 +638,152 @@ impl Player {
         self.position() + Duration::from_secs(2) >= *expected
     }
 
+    fn current_duration(&self) -> Option<Duration> {
+        let path = self.current_path.as_ref()?;
+        self.stream_durations
+            .get(path)
+            .or_else(|| self.track_durations.get(path))
+            .copied()
+    }
+
+    fn should_start_next_track(&self) -> bool {
+        if self.child.is_none() || self.status != "Playing" || self.casting_active() {
+            return false;
+        }
+        let Some(duration) = self.current_duration() else {
+            return false;
+        };
+        self.next_entry_ready() && self.position() + self.crossfade.max(GAPLESS_LEAD) >= duration
+    }
+
+    fn next_entry_ready(&self) -> bool {
+        let ready = |(title, _): &(String, String)| !title.ends_with(" (Downloading...)");
+        match self.repeat {
+            RepeatMode::Track => true,
+            RepeatMode::Queue if self.queue.is_empty() => true,
+            _ if self.shuffle => self.queue.iter().any(ready),
+            _ => self.queue.first().is_some_and(ready),
+        }
+    }
+
+    /// Release the finished track and return its temporary file, unless a
+    /// repeat mode will play it again.
+    fn finish_current_track(&mut self) -> Option<String> {
+        self.record_played(PlayOutcome::Finished);
+        let title = self.title.take();
+        let path = self.current_path.take();
+        self.last_finished_title = title.clone();
+        self.playback_started = None;
+        self.elapsed_before_start = Duration::default();
+        let temp_file = self.last_temp_file.take();
+        if self.repeat != RepeatMode::Off
+            && let (Some(title), Some(path)) = (title, path.as_ref())
+        {
+            self.repeat_entry = Some((title, path.clone()));
+            return None;
+        }
+        if let Some(path) = path {
+            self.video_sources.remove(&path);
+            self.stream_durations.remove(&path);
+            self.track_durations.remove(&path);
+        }
+        temp_file
+    }
+
+    fn record_played(&mut self, outcome: PlayOutcome) {
+        let (Some(title), Some(path)) = (&self.title, &self.current_path) else {
+            return;
+        };
+        self.played.push(PlayedTrack {
+            title: title.clone(),
+            path: path.clone(),
+            video_id: self.video_id_for(path),
+            listened: self.position(),
+            outcome,
+        });
+    }
+
+    /// The track currently playing or paused. A pending download is still
+    /// part of the queue, so it is not reported here.
+    pub fn current_entry(&self) -> Option<(String, String)> {
+        if self.status == "Downloading..." {
+            return None;
+        }
+        self.title.clone().zip(self.current_path.clone())
+    }
+
+    /// Start `path` at `offset` the next time it is played.
+    pub fn set_start_offset(&mut self, path: &str, offset: Duration) {
+        self.start_offsets.insert(path.to_string(), offset);
+    }
+
+    pub fn take_played(&mut self) -> Vec<PlayedTrack> {
+        std::mem::take(&mut self.played)
+    }
+
+    /// Put the current track back at the head of the queue without counting
+    /// it as played, so stepping back through history can return to it.
+    pub fn requeue_current(&mut self) {
+        if self.status == "Downloading..." {
+            // The pending entry never left the queue.
+            self.stop();
+            return;
+        }
+        let current = self.title.take().zip(self.current_path.clone());
+        if current.is_some() {
+            self.last_temp_file = None;
+        }
+        self.stop();
+        if let Some(entry) = current {
+            self.queue.insert(0, entry);
+        }
+    }
+
+    /// Hand the output over to the next queue entry while the current
+    /// decoder finishes its tail.
+    fn start_next_track(&mut self) -> bool {
+        let Some(child) = self.child.take() else {
+            return false;
+        };
+        let overlap = self.crossfade.max(GAPLESS_LEAD);
+        self.stop_outgoing();
+        let temp_file = self.finish_current_track();
+        self.outgoing = Some(OutgoingTrack {
+            child,
+            stop_at: Instant::now() + overlap + Duration::from_secs(1),
+            temp_file,
+        });
+        self.fade_in_next = !self.crossfade.is_zero();
+        self.advance_queue()
+    }
+
+    fn reap_outgoing(&mut self) {
+        let Some(outgoing) = &mut self.outgoing else {
+            return;
+        };
+        let finished = matches!(outgoing.child.try_wait(), Ok(Some(_)) | Err(_));
+        if finished || Instant::now() >= outgoing.stop_at {
+            self.stop_outgoing();
+        }
+    }
+
+    fn stop_outgoing(&mut self) {
+        let Some(mut outgoing) = self.outgoing.take() else {
+            return;
+        };
+        let _ = outgoing.child.kill();
+        let _ = outgoing.child.wait();
+        if let Some(path) = outgoing.temp_file
+            && path.contains("ytmusic_play_")
+            && path.ends_with(".mp3")
+        {
+            let _ = std::fs::remove_file(&path);
+            self.video_sources.remove(&path);
+            self.replay_gains.remove(&path);
+            self.track_durations.remove(&path);
+        }
+    }
+
     fn retry_audio_if_due(&mut self) -> bool {
         let Some(retry_at) = self.audio_retry_at else {
             return false;
@@ -391,7 +819,19 @@ impl Player {
     }
 
     fn advance_queue(&mut self) -> bool {
-        let Some((title, path)) = self.queue.first().cloned() else {
+        if let Some((title, path)) = self.repeat_entry.take() {
+            if self.repeat == RepeatMode::Track {
+                self.play(&path, &title);
+                return true;
+            }
+            self.queue.push((title, path));
+        }
+        let index = self.next_queue_index();
+        self.start_queue_entry(index)
+    }
+
+    fn start_queue_entry(&mut self, index: usize) -> bool {
+        let Some((title, path)) = self.queue.get(index).cloned() else {
             return false;
         };
         if title.ends_with(" (Downloading...)") {
@@ -402,11 +842,121 @@ impl Player {
             self.elapsed_before_start = Duration::default();
             return true;
         }
-        self.queue.remove(0);
+        self.queue.remove(index);
         self.play(&path, &title);
         true
     }
 
+    /// Shuffle picks among entries that can start now and leaves the order
+    /// of everything else untouched.
+    fn next_queue_index(&self) -> usize {
+        if !self.shuffle {
+            return 0;
+        }
+        let ready: Vec<usize> = self
+            .queue
+            .iter()
+            .enumerate()
+            .filter(|(_, (title, _))| !title.ends_with(" (Downloading...)"))
+            .map(|(index, _)| index)
+            .collect();
+        if ready.is_empty() {
+            0
+        } else {
+            ready[fastrand::usize(..ready.len())]
+        }
+    }
+
+    /// Skip the current track. Repeat-queue keeps it in rotation; repeat-track
+    /// only applies to tracks that finish on their own.
+    pub fn skip_to_next(&mut self) {
+        self.leave_current(PlayOutcome::Skipped);
+        self.advance_queue();
+    }
+
+    fn leave_current(&mut self, outcome: PlayOutcome) {
+        let current = self.title.clone().zip(self.current_path.clone());
+        let rotate = self.repeat == RepeatMode::Queue && self.status != "Downloading...";
+        if rotate && current.is_some() {
+            // The skipped streaming file is requeued, so it must not be deleted.
+            self.last_temp_file = None;
+        }
+        self.stop_as(outcome);
+        if rotate && let Some(entry) = current {
+            self.queue.push(entry);
+        }
+    }
+
+    /// Start a queue entry immediately, keeping the rest of the queue.
+    pub fn jump_to_queue_entry(&mut self, index: usize) -> bool {
+        if index >= self.queue.len() {
+            return false;
+        }
+        self.leave_current(PlayOutcome::Stopped);
+        self.start_queue_entry(index)
+    }
+
+    /// Move an entry by `offset` places and return its new index.
+    pub fn move_queue_entry(&mut self, index: usize, offset: isize) -> Option<usize> {
+        let target = index.checked_add_signed(offset)?;
+        if index >= self.queue.len() || target >= self.queue.len() {
+            return None;
+        }
+        let entry = self.queue.remove(index);
+        self.queue.insert(target, entry);
+        Some(target)
+    }
+
+    pub fn play_queue_entry_next(&mut self, index: usize) -> bool {
+        if index >= self.queue.len() {
+            return false;
+        }
+        let entry = self.queue.remove(index);
+        self.queue.insert(0, entry);
+        true
+    }
+
+    /// Remove one entry and release whatever only it referenced. A pending
+    /// download keeps writing its file; the completion handler deletes it
+    /// once it finds the entry gone.
+    pub fn remove_queue_entry(&mut self, index: usize) -> Option<(String, String)> {
+        if index >= self.queue.len() {
+            return None;
+        }
+        let (title, path) = self.queue.remove(index);
+        let pending = title.ends_with(" (Downloading...)");
+        let still_referenced = (!pending && self.current_path.as_deref() == Some(path.as_str()))
+            || self.queue.iter().any(|(_, queued)| queued == &path);
+        if !still_referenced {
+            self.video_sources.remove(&path);
+            self.stream_durations.remove(&path);
+            self.track_durations.remove(&path);
+            self.replay_gains.remove(&path);
+            if !pending && path.contains("ytmusic_play_") {
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+        if pending {
+            // Stop waiting if playback was blocked on this download.
+            self.download_failed(&path);
+        }
+        Some((title, path))
+    }
+
+    pub fn set_playback_modes(&mut self, repeat: RepeatMode, shuffle: bool) {
+        self.repeat = repeat;
+        self.shuffle = shuffle;
+    }
+
+    pub fn mode_label(&self) -> Option<String> {
+        match (self.repeat, self.shuffle) {
+            (RepeatMode::Off, false) => None,
+            (RepeatMode::Off, true) => Some("Shuffle".to_string()),
+            (repeat, false) => Some(repeat.label().to_string()),
+            (repeat, true) => Some(format!("{}, Shuffle", repeat.label())),
+        }
+    }
+
     pub fn position(&self) -> Duration {
         #[cfg(feature = "casting")]
         if !self.cast_clock_calibrated && self.caster.is_waiting_for_stream() {
@@ -427,6 +977,7 @@ impl Player {
             return;
         };
         let was_paused = self.status == "Paused";
+        self.stop_outgoing();
         let current = self.position().as_secs_f64();
         let target = (current + seconds as f64).max(0.0);
 
@@ -493,6 +1044,57 @@ impl Player {
         self.caster.adjust_volume(change)
     }
 
+    pub fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    /// Apply a persisted local output level without announcing the change.
+    pub fn set_volume(&mut self, volume: u8, muted: bool) {
+        let volume = volume.min(MAX_VOLUME);
+        if volume == self.volume && muted == self.muted {
+            return;
+        }
+        self.volume = volume;
+        self.muted = muted;
+        self.volume_restart_at = Some(Instant::now() + VOLUME_RESTART_DELAY);
+    }
+
+    pub fn adjust_volume(&mut self, change: i16) -> String {
+        let volume = (i16::from(self.volume) + change).clamp(0, i16::from(MAX_VOLUME)) as u8;
+        // Changing the level is an explicit request to hear the result.
+        self.set_volume(volume, false);
+        self.volume_label()
+    }
+
+    pub fn toggle_mute(&mut self) -> String {
+        self.set_volume(self.volume, !self.muted);
+        self.volume_label()
+    }
+
+    pub fn volume_label(&self) -> String {
+        if self.muted {
+            format!("Muted ({}%)", self.volume)
+        } else {
+            format!("Volume {}%", self.volume)
+        }
+    }
+
+    fn output_gain(&self, path: &str) -> f64 {
+        if self.muted {
+            return 0.0;
+        }
+        let normalization_db = self
+            .replay_gains
+            .get(path)
+            .map(|gain| gain.gain_db(self.normalization))
+            .unwrap_or(0.0);
+        f64::from(self.volume) / 100.0 * 10f64.powf(normalization_db / 20.0)
+    }
+
     fn casting_active(&self) -> bool {
         #[cfg(feature = "casting")]
         {
@@ -527,6 +1129,35 @@ impl Player {
             if let Some(seek) = seek {
                 command.args(["-ss", seek]);
             }
+            let mut filters = Vec::new();
+            let gain = self.output_gain(path);
+            if gain == 0.0 {
+                filters.push("volume=0".to_string());
+            } else if gain != 1.0 {
+                // Decibels keep low volumes with a negative track gain audible
+                // and smooth instead of rounding them to silence.
+                filters.push(format!("volume={:.2}dB", 20.0 * gain.log10()));
+            }
+            let crossfade = self.crossfade.as_secs_f64();
+            if crossfade > 0.0 {
+                if self.fade_in_next && seek.is_none() {
+                    filters.push(format!("afade=t=in:d={crossfade:.2}"));
+                }
+                // The last track plays out instead of fading into silence.
+                if self.next_entry_ready()
+                    && let Some(duration) = self
+                        .stream_durations
+                        .get(path)
+                        .or_else(|| self.track_durations.get(path))
+                    && duration.as_secs_f64() > crossfade
+                {
+                    let start = duration.as_secs_f64() - crossfade;
+                    filters.push(format!("afade=t=out:st={start:.2}:d={crossfade:.2}"));
+                }
+            }
+            if !filters.is_empty() {
+                command.args(["-af", &filters.join(",")]);
+            }
             command.args(["-nodisp", "-autoexit", path]);
             command
         }
@@ -662,6 +1293,293 @@ mod tests {
         let _ = std::fs::remove_file(path);
     }
 
+    #[test]
+    fn local_volume_survives_restarts_and_is_clamped() {
+        let mut player = Player::new();
+        player.adjust_volume(80);
+        assert_eq!(player.volume(), super::MAX_VOLUME);
+        let command = player.audio_clock_command("song.mp3", Some("12.000"));
+        let arguments: Vec<_> = command.get_args().collect();
+        assert!(arguments.contains(&std::ffi::OsStr::new("volume=3.52dB")));
+
+        player.toggle_mute();
+        let command = player.audio_clock_command("song.mp3", None);
+        let arguments: Vec<_> = command.get_args().collect();
+        assert!(arguments.contains(&std::ffi::OsStr::new("volume=0")));
+
+        player.adjust_volume(-200);
+        assert_eq!(player.volume(), 0);
+        assert!(!player.is_muted());
+    }
+
+    #[test]
+    fn normalization_gain_applies_only_when_enabled() {
+        use crate::loudness::{NormalizationMode, ReplayGain};
+
+        let mut player = Player::new();
+        player.register_replay_gain(
+            "loud.mp3",
+            ReplayGain {
+                track_gain: Some(-6.0),
+                album_gain: None,
+            },
+        );
+        let arguments = |player: &Player| {
+            player
+                .audio_clock_command("loud.mp3", None)
+                .get_args()
+                .map(|argument| argument.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+        assert!(
+            !arguments(&player)
+                .iter()
+                .any(|argument| argument.starts_with("volume="))
+        );
+        player.set_normalization_mode(NormalizationMode::Album);
+        assert!(arguments(&player).contains(&"volume=-6.00dB".to_string()));
+        player.set_volume(1, false);
+        player.register_replay_gain(
+            "loud.mp3",
+            ReplayGain {
+                track_gain: Some(-12.0),
+                album_gain: None,
+            },
+        );
+        assert!(arguments(&player).contains(&"volume=-52.00dB".to_string()));
+        player.set_volume(100, false);
+
+        // Streams measured in the background only keep gains still in use.
+        let gain = ReplayGain {
+            track_gain: Some(3.0),
+            album_gain: None,
+        };
+        player
+            .queue
+            .push(("Queued".to_string(), "queued.mp3".to_string()));
+        for path in ["queued.mp3", "removed.mp3"] {
+            player.gain_sender.send((path.to_string(), gain)).unwrap();
+        }
+        player.receive_measured_gains();
+        assert_eq!(player.replay_gains.get("queued.mp3"), Some(&gain));
+        assert!(!player.replay_gains.contains_key("removed.mp3"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn queued_track_starts_before_the_current_one_drains() {
+        use std::process::Command;
+        use std::time::{Duration, Instant};
+
+        let mut player = Player::new();
+        player.set_crossfade_seconds(4);
+        player.child = Some(Command::new("sleep").arg("5").spawn().unwrap());
+        player.title = Some("Ending".to_string());
+        player.current_path = Some("ending.mp3".to_string());
+        player.status = "Playing".to_string();
+        player
+            .track_durations
+            .insert("ending.mp3".to_string(), Duration::from_secs(180));
+        player.playback_started = Some(Instant::now() - Duration::from_secs(177));
+        player
+            .queue
+            .push(("Next".to_string(), "definitely-missing-next".to_string()));
+
+        let serial = player.track_serial();
+        assert!(player.is_playing());
+        assert!(player.outgoing.is_some());
+        assert_eq!(player.track_serial(), serial + 1);
+        assert!(player.queue.is_empty());
+        assert!(player.status.starts_with("Invalid file or ID:"));
+        player.stop();
+        assert!(player.outgoing.is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn crossfade_waits_for_the_probed_duration() {
+        use std::process::Command;
+        use std::time::{Duration, Instant};
+
+        let mut player = Player::new();
+        player.set_crossfade_seconds(4);
+        player.child = Some(Command::new("sleep").arg("5").spawn().unwrap());
+        player.title = Some("Ending".to_string());
+        player.current_path = Some("ending.mp3".to_string());
+        player.status = "Playing".to_string();
+        player.playback_started = Some(Instant::now() - Duration::from_secs(177));
+        player
+            .queue
+            .push(("Next".to_string(), "definitely-missing-next".to_string()));
+
+        assert!(!player.is_playing());
+        assert!(player.outgoing.is_none());
+        player
+            .duration_sender
+            .send(("ending.mp3".to_string(), Some(Duration::from_secs(180))))
+            .unwrap();
+        player
+            .duration_sender
+            .send(("removed.mp3".to_string(), Some(Duration::from_secs(60))))
+            .unwrap();
+        assert!(player.is_playing());
+        assert!(player.outgoing.is_some());
+        assert!(!player.track_durations.contains_key("removed.mp3"));
+        player.stop();
+    }
+
+    #[test]
+    fn crossfade_adds_fade_filters_around_known_durations() {
+        use std::time::Duration;
+
+        let mut player = Player::new();
+        player.set_crossfade_seconds(20);
+        player
+            .track_durations
+            .insert("song.mp3".to_string(), Duration::from_secs(100));
+        player.fade_in_next = true;
+        let filters = |player: &Player| {
+            player
+                .audio_clock_command("song.mp3", None)
+                .get_args()
+                .map(|argument| argument.to_string_lossy().into_owned())
+                .find(|argument| argument.contains("afade"))
+                .unwrap()
+        };
+        assert_eq!(filters(&player), "afade=t=in:d=12.00");
+        player
+            .queue
+            .push(("Next".to_string(), "next.mp3".to_string()));
+        assert_eq!(
+            filters(&player),
+            "afade=t=in:d=12.00,afade=t=out:st=88.00:d=12.00"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn repeat_modes_requeue_or_replay_the_finished_track() {
+        use super::RepeatMode;
+        use std::process::Command;
+        use std::time::Duration;
+
+        for (repeat, expected_queue) in [
+            (RepeatMode::Queue, vec![("Finished", "finished-missing")]),
+            (RepeatMode::Track, vec![("Next", "next-missing")]),
+        ] {
+            let mut player = Player::new();
+            player.set_playback_modes(repeat, false);
+            player.child = Some(Command::new("sh").args(["-c", "exit 0"]).spawn().unwrap());
+            player.title = Some("Finished".to_string());
+            player.current_path = Some("finished-missing".to_string());
+            player.status = "Playing".to_string();
+            player
+                .queue
+                .push(("Next".to_string(), "next-missing".to_string()));
+            std::thread::sleep(Duration::from_millis(20));
+
+            assert!(player.is_playing());
+            let queue: Vec<_> = player
+                .queue
+                .iter()
+                .map(|(title, path)| (title.as_str(), path.as_str()))
+                .collect();
+            assert_eq!(queue, expected_queue);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn skipping_in_repeat_queue_keeps_the_track_in_rotation() {
+        use super::RepeatMode;
+        use std::process::Command;
+
+        let mut player = Player::new();
+        player.set_playback_modes(RepeatMode::Queue, true);
+        player.child = Some(Command::new("sleep").arg("5").spawn().unwrap());
+        player.title = Some("Current".to_string());
+        player.current_path = Some("current-missing".to_string());
+        player.status = "Playing".to_string();
+        player.queue.push((
+            "Pending (Downloading...)".to_string(),
+            "pending".to_string(),
+        ));
+
+        player.skip_to_next();
+
+        // The skipped track rotates to the back, and shuffle starts it again
+        // because the download ahead of it cannot play yet.
+        assert_eq!(
+            player.queue,
+            vec![(
+                "Pending (Downloading...)".to_string(),
+                "pending".to_string()
+            )]
+        );
+        assert_eq!(player.status, "Invalid file or ID: current-missing");
+        assert!(player.child.is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stepping_back_requeues_the_current_track_without_recording_it() {
+        use std::process::Command;
+
+        let mut player = Player::new();
+        player.child = Some(Command::new("sleep").arg("5").spawn().unwrap());
+        player.title = Some("Current".to_string());
+        player.current_path = Some("current.mp3".to_string());
+        player.status = "Playing".to_string();
+        player.register_video_source("current.mp3", "https://www.youtube.com/watch?v=abcdefghijk");
+
+        player.requeue_current();
+        assert!(player.take_played().is_empty());
+        assert_eq!(
+            player.queue,
+            vec![("Current".to_string(), "current.mp3".to_string())]
+        );
+
+        player.child = Some(Command::new("sleep").arg("5").spawn().unwrap());
+        player.title = Some("Current".to_string());
+        player.current_path = Some("current.mp3".to_string());
+        player.stop();
+        let played = player.take_played();
+        assert_eq!(played.len(), 1);
+        assert_eq!(played[0].video_id.as_deref(), Some("abcdefghijk"));
+    }
+
+    #[test]
+    fn queue_edits_move_entries_and_clean_up_removed_streams() {
+        let mut player = Player::new();
+        let stream = std::env::temp_dir().join(format!(
+            "ytmusic_play_queue_edit_test_{}.mp3",
+            std::process::id()
+        ));
+        std::fs::write(&stream, b"audio").unwrap();
+        let stream = stream.to_string_lossy().into_owned();
+        player.queue = vec![
+            ("One".to_string(), "one.mp3".to_string()),
+            ("Streamed".to_string(), stream.clone()),
+            (
+                "Pending (Downloading...)".to_string(),
+                "pending.mp3".to_string(),
+            ),
+        ];
+
+        assert_eq!(player.move_queue_entry(0, 1), Some(1));
+        assert_eq!(player.move_queue_entry(2, 1), None);
+        assert!(player.play_queue_entry_next(2));
+        assert_eq!(player.queue[0].1, "pending.mp3");
+
+        // Waiting on a pending download is released when it is removed.
+        assert!(player.is_playing());
+        assert_eq!(player.status, "Downloading...");
+        assert_eq!(player.remove_queue_entry(1).unwrap().1, stream);
+        assert!(!std::path::Path::new(&stream).exists());
+        player.remove_queue_entry(0);
+        assert_ne!(player.status, "Downloading...");
+    }
+
     #[test]
     fn failed_download_releases_the_waiting_state() {
         let mut player = Player::new();
@@ -0,0 +1,598 @@
+use crate::security::{
+    contained_media_path, read_file_limited, sanitize_display_text, valid_youtube_id,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const MAX_PLAYLISTS_FILE_BYTES: usize = 8 * 1024 * 1024;
+const MAX_IMPORT_FILE_BYTES: usize = 4 * 1024 * 1024;
+const MAX_PLAYLIST_NAME_CHARS: usize = 80;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PlaylistEntry {
+    pub title: String,
+    /// Library file, or empty for a YouTube entry.
+    #[serde(default)]
+    pub path: String,
+    #[serde(default)]
+    pub video_id: Option<String>,
+}
+
+impl PlaylistEntry {
+    pub fn new(title: &str, path: &str, video_id: Option<String>) -> Option<Self> {
+        let entry = Self {
+            title: sanitize_display_text(title.trim_end_matches(" (Downloading...)")),
+            // Streaming files are temporary; only their source can be saved.
+            path: if path.contains("ytmusic_play_") {
+                String::new()
+            } else {
+                path.to_string()
+            },
+            video_id: video_id.filter(|id| valid_youtube_id(id)),
+        };
+        (!entry.path.is_empty() || entry.video_id.is_some()).then_some(entry)
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Playlist {
+    pub name: String,
+    #[serde(default)]
+    pub entries: Vec<PlaylistEntry>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PlaylistFormat {
+    M3u8,
+    Xspf,
+}
+
+impl PlaylistFormat {
+    pub fn from_id(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "m3u" | "m3u8" => Some(Self::M3u8),
+            "xspf" => Some(Self::Xspf),
+            _ => None,
+        }
+    }
+
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("xspf") => Self::Xspf,
+            _ => Self::M3u8,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::M3u8 => ".m3u8",
+            Self::Xspf => ".xspf",
+        }
+    }
+}
+
+/// Named playlists, kept in the order they were created.
+#[derive(Default)]
+pub struct Playlists {
+    playlists: Vec<Playlist>,
+}
+
+impl Playlists {
+    pub fn load() -> Self {
+        let playlists = playlists_path()
+            .and_then(|path| read_file_limited(path, MAX_PLAYLISTS_FILE_BYTES).ok())
+            .and_then(|contents| serde_json::from_slice::<Vec<Playlist>>(&contents).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|playlist| {
+                Some(Playlist {
+                    name: valid_name(&playlist.name).ok()?,
+                    entries: playlist
+                        .entries
+                        .into_iter()
+                        .filter_map(|entry| {
+                            PlaylistEntry::new(&entry.title, &entry.path, entry.video_id)
+                        })
+                        .collect(),
+                })
+            })
+            .collect();
+        Self { playlists }
+    }
+
+    pub fn save(&self) {
+        let Some(path) = playlists_path() else { return };
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        if let Ok(json) = serde_json::to_string(&self.playlists) {
+            let _ = std::fs::write(path, json);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.playlists.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.playlists.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Playlist> {
+        self.playlists.iter()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Playlist> {
+        self.playlists.get(index)
+    }
+
+    /// Names are matched without regard to case.
+    pub fn find(&self, name: &str) -> Option<&Playlist> {
+        self.position(name).map(|index| &self.playlists[index])
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        let name = name.trim();
+        self.playlists
+            .iter()
+            .position(|playlist| playlist.name.eq_ignore_ascii_case(name))
+    }
+
+    fn find_mut(&mut self, name: &str) -> Result<&mut Playlist, String> {
+        let index = self
+            .position(name)
+            .ok_or_else(|| format!("No playlist named {}.", name.trim()))?;
+        Ok(&mut self.playlists[index])
+    }
+
+    pub fn create(&mut self, name: &str) -> Result<(), String> {
+        let name = valid_name(name)?;
+        if self.position(&name).is_some() {
+            return Err(format!("A playlist named {name} already exists."));
+        }
+        self.playlists.push(Playlist {
+            name,
+            entries: Vec::new(),
+        });
+        Ok(())
+    }
+
+    pub fn delete(&mut self, name: &str) -> Result<Playlist, String> {
+        let index = self
+            .position(name)
+            .ok_or_else(|| format!("No playlist named {}.", name.trim()))?;
+        Ok(self.playlists.remove(index))
+    }
+
+    /// Returns the new length of the playlist.
+    pub fn add(&mut self, name: &str, entry: PlaylistEntry) -> Result<usize, String> {
+        let playlist = self.find_mut(name)?;
+        playlist.entries.push(entry);
+        Ok(playlist.entries.len())
+    }
+
+    /// `position` counts from 1, as shown in messages.
+    pub fn remove_entry(&mut self, name: &str, position: usize) -> Result<PlaylistEntry, String> {
+        let playlist = self.find_mut(name)?;
+        if position == 0 || position > playlist.entries.len() {
+            return Err(format!(
+                "{} has {} track(s).",
+                playlist.name,
+                playlist.entries.len()
+            ));
+        }
+        Ok(playlist.entries.remove(position - 1))
+    }
+
+    /// An imported playlist whose name is taken gets a numbered name instead of
+    /// replacing the existing one. Returns the name it was stored under.
+    pub fn insert_unique(&mut self, mut playlist: Playlist) -> String {
+        let base = playlist.name.clone();
+        let mut number = 2;
+        while self.position(&playlist.name).is_some() {
+            playlist.name = format!("{base} ({number})");
+            number += 1;
+        }
+        let name = playlist.name.clone();
+        self.playlists.push(playlist);
+        name
+    }
+}
+
+pub fn valid_name(name: &str) -> Result<String, String> {
+    let name = sanitize_display_text(name.trim());
+    if name.is_empty() {
+        return Err("Playlist names cannot be empty.".to_string());
+    }
+    if name.chars().count() > MAX_PLAYLIST_NAME_CHARS {
+        return Err(format!(
+            "Playlist names are limited to {MAX_PLAYLIST_NAME_CHARS} characters."
+        ));
+    }
+    Ok(name)
+}
+
+fn playlists_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|directory| directory.join("crest-player/playlists.json"))
+}
+
+/// Local entries must be files directly inside the Music directory, the same
+/// place the library keeps its downloads.
+pub fn music_file(path: &Path, music_dir: &Path) -> Option<PathBuf> {
+    let root = music_dir.canonicalize().ok()?;
+    let canonical = path.canonicalize().ok()?;
+    (canonical.parent() == Some(root.as_path()) && canonical.is_file()).then_some(canonical)
+}
+
+/// Write the playlist into the Music directory so relative entries resolve
+/// against the library. `in_library` accepts the other library tracks, such
+/// as imported ones in nested folders. Returns the file and how many entries
+/// were left out.
+pub fn export_playlist(
+    playlist: &Playlist,
+    format: PlaylistFormat,
+    music_dir: &Path,
+    in_library: impl Fn(&Path) -> bool,
+) -> Result<(PathBuf, usize), String> {
+    let path = contained_media_path(music_dir, &playlist.name, format.extension())
+        .map_err(|error| format!("Could not export {}: {error}", playlist.name))?;
+    let (contents, skipped) = render_playlist(playlist, format, music_dir, in_library);
+    std::fs::write(&path, contents)
+        .map_err(|error| format!("Could not export {}: {error}", playlist.name))?;
+    Ok((path, skipped))
+}
+
+fn render_playlist(
+    playlist: &Playlist,
+    format: PlaylistFormat,
+    music_dir: &Path,
+    in_library: impl Fn(&Path) -> bool,
+) -> (String, usize) {
+    let mut skipped = 0;
+    let tracks: Vec<(&str, String)> = playlist
+        .entries
+        .iter()
+        .filter_map(|entry| {
+            let location = export_location(entry, music_dir, format, &in_library);
+            if location.is_none() {
+                skipped += 1;
+            }
+            location.map(|location| (entry.title.as_str(), location))
+        })
+        .collect();
+    let contents = match format {
+        PlaylistFormat::M3u8 => {
+            let mut contents = "#EXTM3U\n".to_string();
+            for (title, location) in tracks {
+                // A line break in a title would start a new entry.
+                let title = title.replace(['\r', '\n'], " ");
+                contents.push_str(&format!("#EXTINF:-1,{title}\n{location}\n"));
+            }
+            contents
+        }
+        PlaylistFormat::Xspf => {
+            let mut contents = format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n  <title>{}</title>\n  <trackList>\n",
+                xml_escape(&playlist.name)
+            );
+            for (title, location) in tracks {
+                contents.push_str(&format!(
+                    "    <track>\n      <location>{}</location>\n      <title>{}</title>\n    </track>\n",
+                    xml_escape(&location),
+                    xml_escape(title)
+                ));
+            }
+            contents.push_str("  </trackList>\n</playlist>\n");
+            contents
+        }
+    };
+    (contents, skipped)
+}
+
+fn export_location(
+    entry: &PlaylistEntry,
+    music_dir: &Path,
+    format: PlaylistFormat,
+    in_library: impl Fn(&Path) -> bool,
+) -> Option<String> {
+    if !entry.path.is_empty() {
+        let path = Path::new(&entry.path);
+        // Only library tracks are written, so nothing else on disk can be
+        // referenced. Tracks in Music are written relative to it.
+        if path.parent() == Some(music_dir) || music_file(path, music_dir).is_some() {
+            let name = path.file_name()?.to_str()?;
+            return Some(match format {
+                PlaylistFormat::M3u8 => name.to_string(),
+                PlaylistFormat::Xspf => percent_encode(name),
+            });
+        }
+        if in_library(path) {
+            let location = match path.strip_prefix(music_dir) {
+                Ok(relative) => relative.to_str()?.replace('\\', "/"),
+                Err(_) => path.to_str()?.to_string(),
+            };
+            return Some(match format {
+                PlaylistFormat::M3u8 => location,
+                PlaylistFormat::Xspf if path.starts_with(music_dir) => percent_encode(&location),
+                PlaylistFormat::Xspf => format!("file://{}", percent_encode(&location)),
+            });
+        }
+    }
+    entry
+        .video_id
+        .as_ref()
+        .map(|video_id| format!("https://music.youtube.com/watch?v={video_id}"))
+}
+
+/// Read an M3U/M3U8 or XSPF file. Local entries that are neither in the Music
+/// directory nor accepted by `in_library`, and unrecognized URLs, are dropped;
+/// the count of dropped entries is returned.
+pub fn import_playlist(
+    file: &Path,
+    music_dir: &Path,
+    in_library: impl Fn(&Path) -> bool,
+) -> Result<(Playlist, usize), String> {
+    let contents = read_file_limited(file, MAX_IMPORT_FILE_BYTES)
+        .map_err(|error| format!("Could not read {}: {error}", file.display()))?;
+    let contents = String::from_utf8_lossy(&contents);
+    let base_dir = file.parent().unwrap_or(Path::new("."));
+    let name = file
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("Imported");
+    let format = if contents.trim_start().starts_with("<?xml") {
+        PlaylistFormat::Xspf
+    } else {
+        PlaylistFormat::from_path(file)
+    };
+    parse_playlist(&contents, format, name, base_dir, music_dir, in_library)
+}
+
+fn parse_playlist(
+    contents: &str,
+    format: PlaylistFormat,
+    fallback_name: &str,
+    base_dir: &Path,
+    music_dir: &Path,
+    in_library: impl Fn(&Path) -> bool,
+) -> Result<(Playlist, usize), String> {
+    let (name, tracks) = match format {
+        PlaylistFormat::M3u8 => (None, parse_m3u(contents)),
+        PlaylistFormat::Xspf => parse_xspf(contents),
+    };
+    let mut skipped = 0;
+    let entries = tracks
+        .into_iter()
+        .filter_map(|(title, location)| {
+            let entry = import_entry(title, &location, format, base_dir, music_dir, &in_library);
+            if entry.is_none() {
+                skipped += 1;
+            }
+            entry
+        })
+        .collect();
+    let name = name
+        .and_then(|name| valid_name(&name).ok())
+        .or_else(|| valid_name(fallback_name).ok())
+        .unwrap_or_else(|| "Imported".to_string());
+    Ok((Playlist { name, entries }, skipped))
+}
+
+fn parse_m3u(contents: &str) -> Vec<(Option<String>, String)> {
+    let mut tracks = Vec::new();
+    let mut title = None;
+    for line in contents.lines() {
+        let line = line.trim().trim_start_matches('\u{feff}');
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            title = info
+                .split_once(',')
+                .map(|(_, title)| title.trim().to_string())
+                .filter(|title| !title.is_empty());
+        } else if !line.is_empty() && !line.starts_with('#') {
+            tracks.push((title.take(), line.to_string()));
+        }
+    }
+    tracks
+}
+
+type XspfTracks = (Option<String>, Vec<(Option<String>, String)>);
+
+fn parse_xspf(contents: &str) -> XspfTracks {
+    let track_list_start = contents.find("<trackList").unwrap_or(contents.len());
+    let name = xml_element(&contents[..track_list_start], "title");
+    let tracks = contents[track_list_start..]
+        .split("<track>")
+        .skip(1)
+        .filter_map(|track| {
+            let track = track.split("</track>").next()?;
+            Some((xml_element(track, "title"), xml_element(track, "location")?))
+        })
+        .collect();
+    (name, tracks)
+}
+
+fn xml_element(contents: &str, tag: &str) -> Option<String> {
+    let start = contents.find(&format!("<{tag}>"))? + tag.len() + 2;
+    let end = contents[start..].find(&format!("</{tag}>"))? + start;
+    let value = xml_unescape(contents[start..end].trim());
+    (!value.is_empty()).then_some(value)
+}
+
+fn import_entry(
+    title: Option<String>,
+    location: &str,
+    format: PlaylistFormat,
+    base_dir: &Path,
+    music_dir: &Path,
+    in_library: impl Fn(&Path) -> bool,
+) -> Option<PlaylistEntry> {
+    if let Some(video_id) = youtube_id(location) {
+        let title = title.unwrap_or_else(|| video_id.clone());
+        return PlaylistEntry::new(&title, "", Some(video_id));
+    }
+    let local = if let Some(path) = location.strip_prefix("file://") {
+        PathBuf::from(percent_decode(
+            path.strip_prefix("localhost").unwrap_or(path),
+        ))
+    } else if location.contains("://") {
+        return None;
+    } else if format == PlaylistFormat::Xspf {
+        base_dir.join(percent_decode(location))
+    } else {
+        base_dir.join(location)
+    };
+    let path = music_file(&local, music_dir).or_else(|| {
+        local
+            .canonicalize()
+            .ok()
+            .filter(|canonical| in_library(canonical))
+    })?;
+    let title = title.unwrap_or_else(|| {
+        path.file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    });
+    PlaylistEntry::new(&title, &path.to_string_lossy(), None)
+}
+
+/// Accept bare IDs, `watch?v=` links, and `youtu.be` short links.
+fn youtube_id(location: &str) -> Option<String> {
+    if valid_youtube_id(location) {
+        return Some(location.to_string());
+    }
+    let rest = location
+        .strip_prefix("https://")
+        .or_else(|| location.strip_prefix("http://"))?;
+    let (host, query) = rest.split_once('/')?;
+    let candidate = match host.trim_start_matches("www.") {
+        "youtube.com" | "music.youtube.com" | "m.youtube.com" => query
+            .split_once('?')?
+            .1
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("v="))?,
+        "youtu.be" => query.split(['?', '#']).next()?,
+        _ => return None,
+    };
+    valid_youtube_id(candidate).then(|| candidate.to_string())
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn xml_unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%'
+            && let Some(byte) = value
+                .get(index + 1..index + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            index += 3;
+        } else {
+            decoded.push(bytes[index]);
+            index += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Playlist, PlaylistEntry, PlaylistFormat, Playlists, parse_playlist, render_playlist,
+    };
+
+    fn music_dir(test: &str) -> std::path::PathBuf {
+        let directory = std::env::temp_dir().join(format!(
+            "crest-player-playlist-{test}-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("Local Song.mp3"), b"audio").unwrap();
+        directory.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn playlists_round_trip_through_both_formats() {
+        let music = music_dir("round-trip");
+        let local = music.join("Local Song.mp3");
+        let playlist = Playlist {
+            name: "Road & Trip".to_string(),
+            entries: vec![
+                PlaylistEntry::new("Local <Song>", &local.to_string_lossy(), None).unwrap(),
+                PlaylistEntry::new("Streamed", "", Some("dQw4w9WgXcQ".to_string())).unwrap(),
+                PlaylistEntry::new("Elsewhere", "/etc/passwd", None).unwrap(),
+            ],
+        };
+        for format in [PlaylistFormat::M3u8, PlaylistFormat::Xspf] {
+            let (contents, skipped) = render_playlist(&playlist, format, &music, |_| false);
+            assert_eq!(skipped, 1);
+            assert!(!contents.contains("passwd"));
+            let (imported, skipped) =
+                parse_playlist(&contents, format, "Fallback", &music, &music, |_| false).unwrap();
+            assert_eq!(skipped, 0);
+            assert_eq!(imported.entries, playlist.entries[..2]);
+        }
+        let _ = std::fs::remove_dir_all(music);
+    }
+
+    #[test]
+    fn imports_drop_paths_outside_the_music_directory() {
+        let music = music_dir("import");
+        let m3u = "#EXTM3U\n#EXTINF:200,Escape\n../outside.mp3\n/etc/hosts\nhttps://youtu.be/dQw4w9WgXcQ\nLocal Song.mp3\n";
+        let (playlist, skipped) =
+            parse_playlist(m3u, PlaylistFormat::M3u8, "Mixed", &music, &music, |_| {
+                false
+            })
+            .unwrap();
+        assert_eq!(skipped, 2);
+        assert_eq!(playlist.name, "Mixed");
+        assert_eq!(playlist.entries[0].video_id.as_deref(), Some("dQw4w9WgXcQ"));
+        assert_eq!(playlist.entries[1].title, "Local Song");
+        let _ = std::fs::remove_dir_all(music);
+    }
+
+    #[test]
+    fn names_are_unique_regardless_of_case() {
+        let mut playlists = Playlists::default();
+        playlists.create("Focus").unwrap();
+        assert!(playlists.create("focus").is_err());
+        assert!(playlists.create("   ").is_err());
+        let name = playlists.insert_unique(Playlist {
+            name: "FOCUS".to_string(),
+            entries: Vec::new(),
+        });
+        assert_eq!(name, "FOCUS (2)");
+        assert!(playlists.remove_entry("Focus", 1).is_err());
+    }
+}
@@ -0,0 +1,59 @@
+use crate::app::App;
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState},
+};
+
+pub fn render_playlists(frame: &mut Frame, area: Rect, app: &App) {
+    let count = app.playlists.len() + app.smart_playlists.len();
+    let items: Vec<ListItem> = if count == 0 {
+        vec![ListItem::new(Line::styled(
+            "No playlists yet. Try :playlist new <name> or :smart new <name> = <rule>",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        let regular = app.playlists.iter().map(|playlist| {
+            Line::from(vec![
+                Span::raw(playlist.name.clone()),
+                Span::styled(
+                    format!("  {} track(s)", playlist.entries.len()),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ])
+            .into()
+        });
+        // Smart playlists show their rule rather than a count, which would
+        // mean evaluating every rule on each frame.
+        let smart = app.smart_playlists.iter().map(|playlist| {
+            let (detail, color) = match playlist.error() {
+                Some(error) => (format!("  {error}"), Color::Red),
+                None => (format!("  {}", playlist.rule), Color::DarkGray),
+            };
+            Line::from(vec![
+                Span::styled("⚡ ", Style::default().fg(Color::Yellow)),
+                Span::raw(playlist.name.clone()),
+                Span::styled(detail, Style::default().fg(color)),
+            ])
+            .into()
+        });
+        regular.chain(smart).collect()
+    };
+    let mut state = ListState::default();
+    if count > 0 {
+        state.select(Some(app.playlist_selected.min(count - 1)));
+    }
+    frame.render_stateful_widget(
+        List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Playlists (Enter play, Ctrl+e queue, Ctrl+o close)"),
+            )
+            .highlight_style(Style::default().bg(Color::Green).fg(Color::Black)),
+        area,
+        &mut state,
+    );
+}
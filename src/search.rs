@@ -1,11 +1,17 @@
 //
-use crate::lyrics::fetch_lyrics_with_caption_fallback;
+use crate::download_format::{AUDIO_EXTENSIONS, DownloadFormat};
+use crate::download_progress::{
+    DownloadProgress, DownloadStage, FfmpegProgress, YT_DLP_PROGRESS_TEMPLATE, parse_yt_dlp_line,
+};
+use crate::loudness::{TrackLoudness, analyze_track_loudness};
+use crate::lyrics::{Lyrics, fetch_lyrics_with_caption_fallback};
 use crate::security::{
-    MAX_METADATA_BYTES, bounded_output, cancellable_status, external_command,
-    sanitize_display_text_limited, valid_youtube_id,
+    MAX_METADATA_BYTES, bounded_output, cancellable_status_with_lines, containing_root,
+    external_command, read_file_limited, sanitize_display_text_limited, valid_youtube_id,
 };
+use crate::tagging::write_tags;
+use crate::track_metadata::TrackMetadata;
 use crate::video_cache::build_video_cache_cancellable;
-use dirs::audio_dir;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 
@@ -45,106 +51,207 @@ pub fn search_youtube(query: &str) -> Result<Vec<(String, String)>, String> {
     Ok(songs)
 }
 
-pub fn download_audio(
+/// Best-effort metadata from the info JSON yt-dlp wrote beside a fetched
+/// source; downloads continue without it.
+fn read_track_metadata(info_json: &std::path::Path) -> Option<TrackMetadata> {
+    let contents = read_file_limited(info_json, MAX_METADATA_BYTES).ok()?;
+    let json = serde_json::from_slice::<serde_json::Value>(&contents).ok()?;
+    Some(TrackMetadata::from_yt_dlp_json(&json))
+}
+
+/// Where [`download_source`] has yt-dlp write the video's metadata.
+fn info_json_path(source_path: &std::path::Path) -> PathBuf {
+    source_path.with_extension("info.json")
+}
+
+pub struct DownloadedAudio {
+    pub path: PathBuf,
+    pub loudness: Option<TrackLoudness>,
+    pub metadata: TrackMetadata,
+}
+
+/// A permanent download whose source video has been fetched and is waiting
+/// for the encode stage. The temporary source is removed once the encode
+/// succeeds; until then it lets an interrupted download resume.
+pub struct FetchedSource {
+    path: PathBuf,
+    source_path: PathBuf,
+    metadata: TrackMetadata,
+    lyrics: Option<Lyrics>,
+}
+
+/// Remove every temporary file of the download publishing `path`. Paths
+/// outside the library roots are left alone.
+pub fn remove_partial_download(path: &std::path::Path, library_roots: &[PathBuf]) {
+    if containing_root(path, library_roots).is_none() {
+        return;
+    }
+    remove_partial_sources(&path.with_extension("download.mkv"));
+    let cache_part = path.with_extension("crestvid.part");
+    for extension in AUDIO_EXTENSIONS {
+        let _ = std::fs::remove_file(path.with_extension(format!("{extension}.part")));
+    }
+    let _ = std::fs::remove_file(cache_part.with_extension("part.lyrics.vtt"));
+    let _ = std::fs::remove_file(cache_part);
+}
+
+/// Remove `source_path` and the per-stream `.part` files yt-dlp leaves
+/// beside it when it is interrupted, such as `Song.download.f137.mp4.part`.
+fn remove_partial_sources(source_path: &std::path::Path) {
+    let _ = std::fs::remove_file(source_path);
+    let (Some(directory), Some(stem)) = (
+        source_path.parent(),
+        source_path
+            .file_stem()
+            .map(|stem| format!("{}.", stem.to_string_lossy())),
+    ) else {
+        return;
+    };
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if entry.file_name().to_string_lossy().starts_with(&stem)
+            && entry.file_type().is_ok_and(|kind| kind.is_file())
+        {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// The network-bound half of a permanent download: the yt-dlp source fetch,
+/// which also writes the video's metadata, plus the lyrics lookup.
+pub fn fetch_download(
     url: &str,
     title: &str,
     path: &std::path::Path,
-    video_cache_plan: Option<(u16, u16, u16)>,
+    library_roots: &[PathBuf],
     cancelled: &AtomicBool,
-) -> Result<PathBuf, String> {
-    let dir = audio_dir().ok_or_else(|| "the Music directory is unavailable".to_string())?;
-    std::fs::create_dir_all(&dir)
-        .map_err(|error| format!("could not create the Music directory: {error}"))?;
-    if path.parent() != Some(dir.as_path()) {
-        return Err("the queued output path is outside the Music directory".to_string());
+    report: &mut dyn FnMut(DownloadProgress),
+) -> Result<FetchedSource, String> {
+    if containing_root(path, library_roots).is_none() {
+        return Err("the queued output path is outside the library roots".to_string());
     }
-    let (width, height, fps) = video_cache_plan
-        .ok_or_else(|| "a .crestvid cache plan is required for every download".to_string())?;
     let source_path = path.with_extension("download.mkv");
-    let audio_part_path = path.with_extension("mp3.part");
-    let cache_path = path.with_extension("crestvid");
     let source = source_path
         .to_str()
         .ok_or_else(|| "the temporary source path is not valid UTF-8".to_string())?;
+    let mut fetched = FetchedSource {
+        path: path.to_path_buf(),
+        source_path: source_path.clone(),
+        metadata: TrackMetadata::default(),
+        lyrics: None,
+    };
+    report(DownloadProgress::stage(DownloadStage::Fetching));
+    // A source finished before an interruption is reused as it is; partial
+    // streams are continued by yt-dlp.
+    if probe_duration(&source_path).is_none() {
+        let _ = std::fs::remove_file(&source_path);
+        download_source(url, source, cancelled, report)?;
+    }
+    fetched.metadata = read_track_metadata(&info_json_path(&source_path)).unwrap_or_default();
+    if fetched.metadata.video_id.is_none() {
+        fetched.metadata.video_id = url
+            .split_once("v=")
+            .map(|(_, id)| id.to_string())
+            .filter(|id| valid_youtube_id(id));
+    }
+    if cancelled.load(Ordering::Acquire) {
+        return Err("download cancelled".to_string());
+    }
+    fetched.lyrics = fetch_lyrics_with_caption_fallback(title, url).ok();
+    Ok(fetched)
+}
+
+/// The encode-bound half of a permanent download: audio conversion, loudness
+/// analysis, tagging, and the `.crestvid` cache. The published file takes the
+/// extension of `format`, which for the original stream depends on its codec.
+pub fn encode_download(
+    fetched: &FetchedSource,
+    title: &str,
+    format: DownloadFormat,
+    video_cache_plan: Option<(u16, u16, u16)>,
+    cancelled: &AtomicBool,
+    report: &mut dyn FnMut(DownloadProgress),
+) -> Result<DownloadedAudio, String> {
+    let video_cache_plan = video_cache_plan
+        .ok_or_else(|| "a .crestvid cache plan is required for every download".to_string())?;
+    let encoding = format.encoding(probe_audio_codec(&fetched.source_path).as_deref());
+    let published = fetched.path.with_extension(encoding.extension);
+    let path = published.as_path();
+    let audio_part_path = path.with_extension(format!("{}.part", encoding.extension));
+    let cache_path = path.with_extension("crestvid");
+    let source = fetched
+        .source_path
+        .to_str()
+        .ok_or_else(|| "the temporary source path is not valid UTF-8".to_string())?;
     let audio_part = audio_part_path
         .to_str()
-        .ok_or_else(|| "the temporary MP3 path is not valid UTF-8".to_string())?;
+        .ok_or_else(|| "the temporary audio path is not valid UTF-8".to_string())?;
     let cache = cache_path
         .to_str()
         .ok_or_else(|| "the video cache path is not valid UTF-8".to_string())?;
 
-    let _ = std::fs::remove_file(&source_path);
     let _ = std::fs::remove_file(&audio_part_path);
+    // Percentages need the length; yt-dlp's metadata saves probing for it.
+    let duration = fetched
+        .metadata
+        .duration
+        .or_else(|| probe_duration(&fetched.source_path));
     let result = (|| {
-        let mut source_command = external_command("yt-dlp");
-        source_command.args([
-                "--ignore-config",
-                "--socket-timeout",
-                "10",
-                "--retries",
-                "3",
-                "--fragment-retries",
-                "10",
-                "--force-overwrites",
-                "--no-playlist",
-                "-f",
-                "bestvideo[vcodec^=avc1][height<=720]+bestaudio/bestvideo[height<=720]+bestaudio/best[height<=720]/best",
-                "--merge-output-format",
-                "mkv",
-                "--remux-video",
-                "mkv",
-                "-o",
-                source,
-                url,
-            ])
-            .stdin(std::process::Stdio::null())
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null());
-        let source_status = cancellable_status(source_command, cancelled)
-            .map_err(|error| format!("could not start the source download: {error}"))?;
-        if !source_status.success() || !source_path.is_file() {
-            return Err(format!("the source download exited with {source_status}"));
-        }
-
+        report(DownloadProgress::stage(DownloadStage::Converting));
         let mut audio_command = external_command("ffmpeg");
         audio_command
+            .args(["-y", "-nostdin", "-loglevel", "error", "-i", source, "-vn"])
+            .args(encoding.codec_args)
             .args([
-                "-y",
-                "-nostdin",
-                "-loglevel",
-                "error",
-                "-i",
-                source,
-                "-vn",
-                "-c:a",
-                "libmp3lame",
-                "-q:a",
-                "2",
                 "-f",
-                "mp3",
+                encoding.muxer,
+                "-progress",
+                "pipe:1",
+                "-nostats",
                 audio_part,
             ])
             .stdin(std::process::Stdio::null())
-            .stdout(std::process::Stdio::null())
             .stderr(std::process::Stdio::null());
-        let audio_status = cancellable_status(audio_command, cancelled)
-            .map_err(|error| format!("could not start MP3 conversion: {error}"))?;
+        let mut conversion = FfmpegProgress::new(DownloadStage::Converting, duration);
+        let audio_status = cancellable_status_with_lines(audio_command, cancelled, &mut |line| {
+            if let Some(progress) = conversion.line(line) {
+                report(progress);
+            }
+        })
+        .map_err(|error| format!("could not start audio conversion: {error}"))?;
         if !audio_status.success() || !playable_audio_file(&audio_part_path) {
-            return Err(format!("MP3 conversion failed with {audio_status}"));
+            return Err(format!("audio conversion failed with {audio_status}"));
         }
+        // Loudness is optional metadata; a failed measurement never fails the
+        // download and can be retried by the library's background pass.
+        let loudness = analyze_track_loudness(&audio_part_path).ok();
+        let metadata = fetched.metadata.clone();
+        // Untagged files still play everywhere, so tagging never fails a download.
+        let _ = write_tags(&audio_part_path, encoding, title, &metadata);
 
         if cancelled.load(Ordering::Acquire) {
             return Err("download cancelled".to_string());
         }
-        let lyrics = fetch_lyrics_with_caption_fallback(title, url).ok();
+        report(DownloadProgress::stage(DownloadStage::EncodingVideo));
+        let mut encode = FfmpegProgress::new(DownloadStage::EncodingVideo, duration);
         build_video_cache_cancellable(
             source,
             cache,
-            width,
-            height,
-            fps,
-            lyrics.as_ref(),
+            video_cache_plan,
+            fetched.lyrics.as_ref(),
             cancelled,
+            &mut |line| {
+                if let Some(progress) = encode.line(line) {
+                    report(progress);
+                }
+                // The subtitle track is written alongside the video, so
+                // ffmpeg finishing the video leaves only the lyrics mux.
+                if line.trim() == "progress=end" && fetched.lyrics.is_some() {
+                    report(DownloadProgress::stage(DownloadStage::EmbeddingLyrics));
+                }
+            },
         )
         .map_err(|error| format!("could not build the .crestvid cache: {error}"))?;
         if !cache_path
@@ -159,11 +266,16 @@ pub fn download_audio(
         }
         let _ = std::fs::remove_file(path);
         std::fs::rename(&audio_part_path, path)
-            .map_err(|error| format!("could not publish the completed MP3: {error}"))?;
-        Ok(path.to_path_buf())
+            .map_err(|error| format!("could not publish the completed audio: {error}"))?;
+        Ok(DownloadedAudio {
+            path: path.to_path_buf(),
+            loudness,
+            metadata,
+        })
     })();
-    let _ = std::fs::remove_file(&source_path);
-    if result.is_err() {
+    if result.is_ok() {
+        remove_partial_sources(&fetched.source_path);
+    } else {
         let _ = std::fs::remove_file(&audio_part_path);
         let _ = std::fs::remove_file(&cache_path);
         let _ = std::fs::remove_file(format!("{cache}.part"));
@@ -171,6 +283,93 @@ pub fn download_audio(
     result
 }
 
+/// Fetch the audio and video source yt-dlp picks for the library into
+/// `source`, a temporary Matroska file.
+fn download_source(
+    url: &str,
+    source: &str,
+    cancelled: &AtomicBool,
+    report: &mut dyn FnMut(DownloadProgress),
+) -> Result<(), String> {
+    let info_json = info_json_path(std::path::Path::new(source));
+    let info_json = format!(
+        "infojson:{}",
+        info_json
+            .to_str()
+            .ok_or_else(|| "the metadata path is not valid UTF-8".to_string())?
+    );
+    let mut source_command = external_command("yt-dlp");
+    source_command.args([
+            "--ignore-config",
+            "--newline",
+            "--progress-template",
+            YT_DLP_PROGRESS_TEMPLATE,
+            "--socket-timeout",
+            "10",
+            "--retries",
+            "3",
+            "--fragment-retries",
+            "10",
+            "--continue",
+            "--no-playlist",
+            "-f",
+            "bestvideo[vcodec^=avc1][height<=720]+bestaudio/bestvideo[height<=720]+bestaudio/best[height<=720]/best",
+            "--merge-output-format",
+            "mkv",
+            "--remux-video",
+            "mkv",
+            "-o",
+            source,
+            // The metadata comes with the download instead of a second request.
+            "--write-info-json",
+            "-o",
+            &info_json,
+            url,
+        ])
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+    let source_status = cancellable_status_with_lines(source_command, cancelled, &mut |line| {
+        if let Some(progress) = parse_yt_dlp_line(line) {
+            report(progress);
+        }
+    })
+    .map_err(|error| format!("could not start the source download: {error}"))?;
+    if !source_status.success() || !std::path::Path::new(source).is_file() {
+        return Err(format!("the source download exited with {source_status}"));
+    }
+    Ok(())
+}
+
+/// Download the video of a library track again and rebuild its `.crestvid`
+/// cache, leaving the audio file untouched.
+pub fn rebuild_video_cache(
+    url: &str,
+    title: &str,
+    path: &std::path::Path,
+    video_cache_plan: (u16, u16, u16),
+    cancelled: &AtomicBool,
+) -> Result<(), String> {
+    let source_path = path.with_extension("download.mkv");
+    let cache_path = path.with_extension("crestvid");
+    let (Some(source), Some(cache)) = (source_path.to_str(), cache_path.to_str()) else {
+        return Err("the library path is not valid UTF-8".to_string());
+    };
+    let result = download_source(url, source, cancelled, &mut |_| {}).and_then(|()| {
+        let lyrics = fetch_lyrics_with_caption_fallback(title, url).ok();
+        build_video_cache_cancellable(
+            source,
+            cache,
+            video_cache_plan,
+            lyrics.as_ref(),
+            cancelled,
+            &mut |_| {},
+        )
+        .map_err(|error| format!("could not build the .crestvid cache: {error}"))
+    });
+    remove_partial_sources(&source_path);
+    result
+}
+
 pub fn playable_audio_file(path: &std::path::Path) -> bool {
     if !path
         .metadata()
@@ -178,9 +377,11 @@ pub fn playable_audio_file(path: &std::path::Path) -> bool {
     {
         return false;
     }
-    let Some(path) = path.to_str() else {
-        return false;
-    };
+    probe_duration(path).is_some()
+}
+
+pub fn probe_duration(path: &std::path::Path) -> Option<std::time::Duration> {
+    let path = path.to_str()?;
     let mut command = external_command("ffprobe");
     command.args([
         "-v",
@@ -191,11 +392,34 @@ pub fn playable_audio_file(path: &std::path::Path) -> bool {
         "default=noprint_wrappers=1:nokey=1",
         path,
     ]);
-    bounded_output(command, 1024).is_ok_and(|output| {
-        output.status.success()
-            && String::from_utf8_lossy(&output.stdout)
-                .trim()
-                .parse::<f64>()
-                .is_ok_and(|duration| duration.is_finite() && duration > 0.0)
-    })
+    let output = bounded_output(command, 1024).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|duration| duration.is_finite() && *duration > 0.0)
+        .and_then(|duration| std::time::Duration::try_from_secs_f64(duration).ok())
+}
+
+/// The ffprobe codec name of the first audio stream, such as `opus` or `aac`.
+fn probe_audio_codec(path: &std::path::Path) -> Option<String> {
+    let path = path.to_str()?;
+    let mut command = external_command("ffprobe");
+    command.args([
+        "-v",
+        "error",
+        "-select_streams",
+        "a:0",
+        "-show_entries",
+        "stream=codec_name",
+        "-of",
+        "default=noprint_wrappers=1:nokey=1",
+        path,
+    ]);
+    let output = bounded_output(command, 1024).ok()?;
+    let codec = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !codec.is_empty()).then_some(codec)
 }
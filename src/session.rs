@@ -0,0 +1,139 @@
+use crate::player::Player;
+use crate::security::{read_file_limited, sanitize_display_text, valid_youtube_id};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+pub const SESSION_SAVE_INTERVAL: Duration = Duration::from_secs(15);
+const MAX_SESSION_FILE_BYTES: usize = 4 * 1024 * 1024;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SessionEntry {
+    pub title: String,
+    /// Library file, or empty when the track must be streamed again.
+    #[serde(default)]
+    pub path: String,
+    #[serde(default)]
+    pub video_id: Option<String>,
+}
+
+impl SessionEntry {
+    fn new(title: &str, path: &str, video_id: Option<String>) -> Option<Self> {
+        let entry = Self {
+            title: sanitize_display_text(title.trim_end_matches(" (Downloading...)")),
+            // Streaming files are removed on exit; only their source survives.
+            path: if path.contains("ytmusic_play_") {
+                String::new()
+            } else {
+                path.to_string()
+            },
+            video_id: video_id.filter(|id| valid_youtube_id(id)),
+        };
+        (!entry.path.is_empty() || entry.video_id.is_some()).then_some(entry)
+    }
+}
+
+/// What was playing when Crest Player last exited.
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Session {
+    pub current: Option<SessionEntry>,
+    pub position_seconds: u64,
+    pub queue: Vec<SessionEntry>,
+}
+
+impl Session {
+    pub fn capture(player: &Player) -> Self {
+        let entry =
+            |title: &str, path: &str| SessionEntry::new(title, path, player.video_id_for(path));
+        let current = player
+            .current_entry()
+            .and_then(|(title, path)| entry(&title, &path));
+        Self {
+            position_seconds: if current.is_some() {
+                player.position().as_secs()
+            } else {
+                0
+            },
+            current,
+            queue: player
+                .queue
+                .iter()
+                .filter_map(|(title, path)| entry(title, path))
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.current.is_none() && self.queue.is_empty()
+    }
+
+    pub fn load() -> Option<Self> {
+        let contents = read_file_limited(session_path()?, MAX_SESSION_FILE_BYTES).ok()?;
+        let session: Self = serde_json::from_slice(&contents).ok()?;
+        let clean =
+            |entry: SessionEntry| SessionEntry::new(&entry.title, &entry.path, entry.video_id);
+        let session = Self {
+            current: session.current.and_then(clean),
+            position_seconds: session.position_seconds,
+            queue: session.queue.into_iter().filter_map(clean).collect(),
+        };
+        (!session.is_empty()).then_some(session)
+    }
+
+    /// An empty session removes the file so the next launch starts fresh.
+    pub fn save(&self) {
+        let Some(path) = session_path() else { return };
+        if self.is_empty() {
+            let _ = std::fs::remove_file(path);
+            return;
+        }
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        if let Ok(json) = serde_json::to_string(self) {
+            let _ = std::fs::write(path, json);
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let position = format!(
+            "{}:{:02}",
+            self.position_seconds / 60,
+            self.position_seconds % 60
+        );
+        match &self.current {
+            Some(current) => format!(
+                "Resume {} at {position} with {} queued? R resume · X discard",
+                current.title,
+                self.queue.len()
+            ),
+            None => format!(
+                "Resume {} queued track(s)? R resume · X discard",
+                self.queue.len()
+            ),
+        }
+    }
+}
+
+fn session_path() -> Option<std::path::PathBuf> {
+    dirs::config_dir().map(|directory| directory.join("crest-player/session.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Session, SessionEntry};
+
+    #[test]
+    fn streamed_entries_are_kept_by_video_id_only() {
+        let streamed = SessionEntry::new(
+            "Song (Downloading...)",
+            "/tmp/ytmusic_play_1_1.mp3",
+            Some("dQw4w9WgXcQ".to_string()),
+        )
+        .unwrap();
+        assert_eq!(streamed.title, "Song");
+        assert!(streamed.path.is_empty());
+        assert!(SessionEntry::new("Lost", "/tmp/ytmusic_play_1_2.mp3", None).is_none());
+        assert!(Session::default().is_empty());
+    }
+}
@@ -0,0 +1,717 @@
+use crate::app::{App, TrackStats};
+use crate::playlists::valid_name;
+use crate::security::read_file_limited;
+use crate::track_metadata::TrackMetadata;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MAX_SMART_PLAYLISTS_FILE_BYTES: usize = 1024 * 1024;
+const MAX_RULE_CHARS: usize = 500;
+const DAY: f64 = 86_400.0;
+
+/// A playlist whose songs are every library track its rule matches, such as
+/// `rating >= 4 and duration < 5m`.
+pub struct SmartPlaylist {
+    pub name: String,
+    pub rule: String,
+    parsed: Result<Rule, String>,
+}
+
+impl SmartPlaylist {
+    /// Why the rule cannot be used, for rules edited by hand in the file.
+    pub fn error(&self) -> Option<&str> {
+        self.parsed.as_ref().err().map(String::as_str)
+    }
+
+    pub fn matches(
+        &self,
+        title: &str,
+        metadata: Option<&TrackMetadata>,
+        stats: Option<&TrackStats>,
+        now: u64,
+    ) -> bool {
+        let track = Track {
+            title,
+            metadata,
+            stats,
+            now,
+        };
+        self.parsed.as_ref().is_ok_and(|rule| rule.matches(&track))
+    }
+
+    /// Matching library tracks, in library order.
+    pub fn tracks(&self, app: &App) -> Vec<(String, String)> {
+        let now = now();
+        app.library
+            .iter()
+            .filter(|(title, path)| {
+                self.matches(title, app.track_metadata(path), app.track_stats(path), now)
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct StoredSmartPlaylist {
+    name: String,
+    rule: String,
+}
+
+/// Smart playlists, kept in `smart_playlists.json` next to the regular ones so
+/// they can also be written by hand.
+#[derive(Default)]
+pub struct SmartPlaylists {
+    playlists: Vec<SmartPlaylist>,
+}
+
+impl SmartPlaylists {
+    pub fn load() -> Self {
+        let playlists = smart_playlists_path()
+            .and_then(|path| read_file_limited(path, MAX_SMART_PLAYLISTS_FILE_BYTES).ok())
+            .and_then(|contents| serde_json::from_slice::<Vec<StoredSmartPlaylist>>(&contents).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|stored| {
+                Some(SmartPlaylist {
+                    name: valid_name(&stored.name).ok()?,
+                    parsed: Rule::parse(&stored.rule),
+                    rule: stored.rule,
+                })
+            })
+            .collect();
+        Self { playlists }
+    }
+
+    pub fn save(&self) {
+        let Some(path) = smart_playlists_path() else {
+            return;
+        };
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let stored: Vec<_> = self
+            .playlists
+            .iter()
+            .map(|playlist| StoredSmartPlaylist {
+                name: playlist.name.clone(),
+                rule: playlist.rule.clone(),
+            })
+            .collect();
+        if let Ok(json) = serde_json::to_string_pretty(&stored) {
+            let _ = std::fs::write(path, json);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.playlists.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.playlists.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SmartPlaylist> {
+        self.playlists.iter()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&SmartPlaylist> {
+        self.playlists.get(index)
+    }
+
+    /// Names are matched without regard to case.
+    pub fn find(&self, name: &str) -> Option<&SmartPlaylist> {
+        let name = name.trim();
+        self.playlists
+            .iter()
+            .find(|playlist| playlist.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Define a smart playlist, replacing the rule of one with the same name.
+    /// Returns whether it already existed.
+    pub fn define(&mut self, name: &str, rule: &str) -> Result<bool, String> {
+        let name = valid_name(name)?;
+        let rule = rule.trim();
+        let parsed = Rule::parse(rule)?;
+        let playlist = SmartPlaylist {
+            name,
+            rule: rule.to_string(),
+            parsed: Ok(parsed),
+        };
+        match self
+            .playlists
+            .iter_mut()
+            .find(|existing| existing.name.eq_ignore_ascii_case(&playlist.name))
+        {
+            Some(existing) => {
+                *existing = playlist;
+                Ok(true)
+            }
+            None => {
+                self.playlists.push(playlist);
+                Ok(false)
+            }
+        }
+    }
+
+    pub fn delete(&mut self, name: &str) -> Result<String, String> {
+        let name = name.trim();
+        let index = self
+            .playlists
+            .iter()
+            .position(|playlist| playlist.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("No smart playlist named {name}."))?;
+        Ok(self.playlists.remove(index).name)
+    }
+}
+
+fn smart_playlists_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|directory| directory.join("crest-player/smart_playlists.json"))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+struct Track<'a> {
+    title: &'a str,
+    metadata: Option<&'a TrackMetadata>,
+    stats: Option<&'a TrackStats>,
+    now: u64,
+}
+
+#[derive(Debug)]
+enum Rule {
+    And(Box<Rule>, Box<Rule>),
+    Or(Box<Rule>, Box<Rule>),
+    Not(Box<Rule>),
+    Favorite,
+    Imported,
+    Number(NumberField, Comparison, f64),
+    /// Time since the event, in seconds.
+    Age(AgeField, Comparison, f64),
+    Text(TextField, TextTest),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum NumberField {
+    Rating,
+    Plays,
+    Skips,
+    Year,
+    /// Seconds.
+    Duration,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum AgeField {
+    Added,
+    Played,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum TextField {
+    Title,
+    Artist,
+    Album,
+}
+
+#[derive(Debug)]
+enum TextTest {
+    Equals(String),
+    NotEquals(String),
+    Matches(Regex),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Comparison {
+    fn holds(self, left: f64, right: f64) -> bool {
+        match self {
+            Self::Less => left < right,
+            Self::LessOrEqual => left <= right,
+            Self::Greater => left > right,
+            Self::GreaterOrEqual => left >= right,
+            Self::Equal => left == right,
+            Self::NotEqual => left != right,
+        }
+    }
+}
+
+impl Rule {
+    fn parse(text: &str) -> Result<Self, String> {
+        if text.chars().count() > MAX_RULE_CHARS {
+            return Err(format!("Rules are limited to {MAX_RULE_CHARS} characters."));
+        }
+        let tokens = tokenize(text)?;
+        if tokens.is_empty() {
+            return Err("The rule is empty.".to_string());
+        }
+        let mut parser = Parser { tokens, next: 0 };
+        let rule = parser.or()?;
+        match parser.tokens.get(parser.next) {
+            None => Ok(rule),
+            Some(token) => Err(format!("Unexpected {} in the rule.", token.describe())),
+        }
+    }
+
+    fn matches(&self, track: &Track) -> bool {
+        let stats = track.stats.cloned().unwrap_or_default();
+        match self {
+            Self::And(left, right) => left.matches(track) && right.matches(track),
+            Self::Or(left, right) => left.matches(track) || right.matches(track),
+            Self::Not(rule) => !rule.matches(track),
+            Self::Favorite => stats.favorite,
+            Self::Imported => track.metadata.is_some_and(|metadata| metadata.imported),
+            Self::Number(field, comparison, value) => {
+                let actual = match field {
+                    NumberField::Rating => Some(f64::from(stats.rating)),
+                    NumberField::Plays => Some(f64::from(stats.plays)),
+                    NumberField::Skips => Some(f64::from(stats.skips)),
+                    NumberField::Year => track
+                        .metadata
+                        .and_then(|metadata| metadata.year)
+                        .map(f64::from),
+                    NumberField::Duration => track
+                        .metadata
+                        .and_then(|metadata| metadata.duration)
+                        .map(|duration| duration.as_secs_f64()),
+                };
+                actual.is_some_and(|actual| comparison.holds(actual, *value))
+            }
+            Self::Age(field, comparison, seconds) => {
+                let at = match field {
+                    AgeField::Added => track.metadata.and_then(|metadata| metadata.added_at),
+                    AgeField::Played => stats.last_played,
+                };
+                match at {
+                    Some(at) => comparison.holds(track.now.saturating_sub(at) as f64, *seconds),
+                    // A song that never played is older than any age.
+                    None => {
+                        matches!(field, AgeField::Played)
+                            && matches!(
+                                comparison,
+                                Comparison::Greater
+                                    | Comparison::GreaterOrEqual
+                                    | Comparison::NotEqual
+                            )
+                    }
+                }
+            }
+            Self::Text(field, test) => {
+                let value = match field {
+                    TextField::Title => Some(track.title),
+                    TextField::Artist => track
+                        .metadata
+                        .and_then(|metadata| metadata.artist.as_deref()),
+                    TextField::Album => track
+                        .metadata
+                        .and_then(|metadata| metadata.album.as_deref()),
+                };
+                match test {
+                    TextTest::Equals(expected) => {
+                        value.is_some_and(|value| value.to_lowercase() == *expected)
+                    }
+                    TextTest::NotEquals(expected) => {
+                        value.is_none_or(|value| value.to_lowercase() != *expected)
+                    }
+                    TextTest::Matches(pattern) => {
+                        value.is_some_and(|value| pattern.is_match(value))
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Pattern(String),
+    Operator(&'static str),
+    Open,
+    Close,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Self::Word(word) | Self::Quoted(word) => format!("`{word}`"),
+            Self::Pattern(pattern) => format!("`/{pattern}/`"),
+            Self::Operator(operator) => format!("`{operator}`"),
+            Self::Open => "`(`".to_string(),
+            Self::Close => "`)`".to_string(),
+        }
+    }
+}
+
+const OPERATORS: [(&str, &str); 12] = [
+    ("<=", "<="),
+    (">=", ">="),
+    ("!=", "!="),
+    ("==", "="),
+    ("≤", "<="),
+    ("≥", ">="),
+    ("≠", "!="),
+    ("<", "<"),
+    (">", ">"),
+    ("=", "="),
+    ("~", "~"),
+    ("!", "not"),
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(character) = rest.chars().next() {
+        if let Some((spelling, operator)) = OPERATORS
+            .iter()
+            .find(|(spelling, _)| rest.starts_with(spelling))
+        {
+            tokens.push(if *operator == "not" {
+                Token::Word("not".to_string())
+            } else {
+                Token::Operator(operator)
+            });
+            rest = &rest[spelling.len()..];
+        } else if character == '(' || character == ')' {
+            tokens.push(if character == '(' {
+                Token::Open
+            } else {
+                Token::Close
+            });
+            rest = &rest[1..];
+        } else if character == '/' || character == '"' {
+            let (literal, remainder) = delimited(&rest[1..], character)?;
+            tokens.push(if character == '/' {
+                Token::Pattern(literal)
+            } else {
+                Token::Quoted(literal)
+            });
+            rest = remainder;
+        } else {
+            let end = rest
+                .find(|character: char| {
+                    character.is_whitespace() || "()<>=!~≤≥≠\"".contains(character)
+                })
+                .unwrap_or(rest.len());
+            tokens.push(Token::Word(rest[..end].to_lowercase()));
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Text up to an unescaped `end`, and what follows it.
+fn delimited(text: &str, end: char) -> Result<(String, &str), String> {
+    let mut literal = String::new();
+    let mut characters = text.char_indices();
+    while let Some((index, character)) = characters.next() {
+        match character {
+            '\\' => match characters.next() {
+                Some((_, escaped)) if escaped == end => literal.push(escaped),
+                Some((_, escaped)) => {
+                    literal.push('\\');
+                    literal.push(escaped);
+                }
+                None => literal.push('\\'),
+            },
+            _ if character == end => return Ok((literal, &text[index + end.len_utf8()..])),
+            _ => literal.push(character),
+        }
+    }
+    Err(format!("Missing closing {end} in the rule."))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.next).cloned();
+        self.next += 1;
+        token
+    }
+
+    fn keyword(&mut self, keywords: &[&str]) -> bool {
+        let found =
+            matches!(self.peek(), Some(Token::Word(word)) if keywords.contains(&word.as_str()));
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Rule, String> {
+        let mut rule = self.and()?;
+        while self.keyword(&["or", "||"]) {
+            rule = Rule::Or(Box::new(rule), Box::new(self.and()?));
+        }
+        Ok(rule)
+    }
+
+    fn and(&mut self) -> Result<Rule, String> {
+        let mut rule = self.unary()?;
+        while self.keyword(&["and", "&&"]) {
+            rule = Rule::And(Box::new(rule), Box::new(self.unary()?));
+        }
+        Ok(rule)
+    }
+
+    fn unary(&mut self) -> Result<Rule, String> {
+        if self.keyword(&["not"]) {
+            return Ok(Rule::Not(Box::new(self.unary()?)));
+        }
+        if self.peek() == Some(&Token::Open) {
+            self.next += 1;
+            let rule = self.or()?;
+            return match self.advance() {
+                Some(Token::Close) => Ok(rule),
+                _ => Err("Missing `)` in the rule.".to_string()),
+            };
+        }
+        self.condition()
+    }
+
+    fn condition(&mut self) -> Result<Rule, String> {
+        let field = match self.advance() {
+            Some(Token::Word(word)) => word,
+            Some(token) => return Err(format!("Expected a field, found {}.", token.describe())),
+            None => return Err("The rule ends too early.".to_string()),
+        };
+        match field.as_str() {
+            "favorite" | "favourite" | "favorites" | "favourites" => return Ok(Rule::Favorite),
+            "imported" => return Ok(Rule::Imported),
+            "unplayed" => return Ok(Rule::Number(NumberField::Plays, Comparison::Equal, 0.0)),
+            "never" if self.keyword(&["played"]) => {
+                return Ok(Rule::Number(NumberField::Plays, Comparison::Equal, 0.0));
+            }
+            _ => {}
+        }
+        let operator = match self.advance() {
+            Some(Token::Operator(operator)) => operator,
+            _ => return Err(format!("Expected a comparison after `{field}`.")),
+        };
+        let value = match self.advance() {
+            Some(Token::Word(value) | Token::Quoted(value)) => value,
+            Some(Token::Pattern(pattern)) if operator == "~" => {
+                return text_field(&field).and_then(|field| {
+                    Ok(Rule::Text(
+                        field,
+                        TextTest::Matches(pattern_regex(&pattern)?),
+                    ))
+                });
+            }
+            Some(token) => return Err(format!("Unexpected {} after `{field}`.", token.describe())),
+            None => return Err(format!("Missing a value after `{field} {operator}`.")),
+        };
+        if let Ok(field) = text_field(&field) {
+            return Ok(Rule::Text(
+                field,
+                match operator {
+                    "~" => TextTest::Matches(pattern_regex(&value)?),
+                    "=" => TextTest::Equals(value.to_lowercase()),
+                    "!=" => TextTest::NotEquals(value.to_lowercase()),
+                    _ => {
+                        return Err(format!(
+                            "Text fields use `=`, `!=` or `~`, not `{operator}`."
+                        ));
+                    }
+                },
+            ));
+        }
+        let comparison = match operator {
+            "<" => Comparison::Less,
+            "<=" => Comparison::LessOrEqual,
+            ">" => Comparison::Greater,
+            ">=" => Comparison::GreaterOrEqual,
+            "=" => Comparison::Equal,
+            "!=" => Comparison::NotEqual,
+            _ => {
+                return Err(format!(
+                    "`{operator}` only applies to title, artist and album."
+                ));
+            }
+        };
+        let number = |value: &str| {
+            value
+                .parse::<f64>()
+                .ok()
+                .filter(|number| number.is_finite())
+                .ok_or_else(|| format!("`{value}` is not a number."))
+        };
+        Ok(match field.as_str() {
+            "rating" | "stars" => Rule::Number(NumberField::Rating, comparison, number(&value)?),
+            "plays" => Rule::Number(NumberField::Plays, comparison, number(&value)?),
+            "skips" => Rule::Number(NumberField::Skips, comparison, number(&value)?),
+            "year" => Rule::Number(NumberField::Year, comparison, number(&value)?),
+            "duration" | "length" => {
+                Rule::Number(NumberField::Duration, comparison, duration_seconds(&value)?)
+            }
+            "added" => Rule::Age(AgeField::Added, comparison, age_seconds(&value)?),
+            "played" => Rule::Age(AgeField::Played, comparison, age_seconds(&value)?),
+            _ => return Err(format!("Unknown field `{field}`.")),
+        })
+    }
+}
+
+fn text_field(field: &str) -> Result<TextField, String> {
+    match field {
+        "title" => Ok(TextField::Title),
+        "artist" => Ok(TextField::Artist),
+        "album" => Ok(TextField::Album),
+        _ => Err(format!("`{field}` cannot be matched against a pattern.")),
+    }
+}
+
+fn pattern_regex(pattern: &str) -> Result<Regex, String> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .size_limit(1 << 20)
+        .build()
+        .map_err(|_| format!("`{pattern}` is not a valid pattern."))
+}
+
+/// `90`, `90s`, `5m`, `1h` or `3:30`.
+fn duration_seconds(value: &str) -> Result<f64, String> {
+    let invalid = || format!("`{value}` is not a duration; try 90s, 5m or 3:30.");
+    if let Some((minutes, seconds)) = value.split_once(':') {
+        let minutes: f64 = minutes.parse().map_err(|_| invalid())?;
+        let seconds: f64 = seconds.parse().map_err(|_| invalid())?;
+        return Ok(minutes * 60.0 + seconds);
+    }
+    let (number, unit) = split_unit(value);
+    let scale = match unit {
+        "" | "s" | "sec" => 1.0,
+        "m" | "min" => 60.0,
+        "h" => 3_600.0,
+        _ => return Err(invalid()),
+    };
+    number
+        .parse::<f64>()
+        .ok()
+        .filter(|number| number.is_finite())
+        .map(|number| number * scale)
+        .ok_or_else(invalid)
+}
+
+/// `12h`, `30d`, `2w` or `1y`; a bare number counts days.
+fn age_seconds(value: &str) -> Result<f64, String> {
+    let invalid = || format!("`{value}` is not an age; try 12h, 30d, 2w or 1y.");
+    let (number, unit) = split_unit(value);
+    let scale = match unit {
+        "h" => DAY / 24.0,
+        "" | "d" => DAY,
+        "w" => 7.0 * DAY,
+        "y" => 365.0 * DAY,
+        _ => return Err(invalid()),
+    };
+    number
+        .parse::<f64>()
+        .ok()
+        .filter(|number| number.is_finite())
+        .map(|number| number * scale)
+        .ok_or_else(invalid)
+}
+
+fn split_unit(value: &str) -> (&str, &str) {
+    let split = value
+        .find(|character: char| character.is_ascii_alphabetic())
+        .unwrap_or(value.len());
+    value.split_at(split)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DAY, SmartPlaylists};
+    use crate::app::TrackStats;
+    use crate::track_metadata::TrackMetadata;
+    use std::time::Duration;
+
+    #[test]
+    fn rules_combine_stats_metadata_and_patterns() {
+        let mut playlists = SmartPlaylists::default();
+        playlists
+            .define("Keepers", "rating >= 4 and duration < 5m")
+            .unwrap();
+        playlists
+            .define("Fresh", "added < 30d and never played")
+            .unwrap();
+        playlists
+            .define(
+                "Remixes",
+                "title ~ /remix/ or (artist = \"DJ X\" and not favorite)",
+            )
+            .unwrap();
+        let now = 1_800_000_000;
+        let metadata = TrackMetadata {
+            artist: Some("DJ X".to_string()),
+            duration: Some(Duration::from_secs(200)),
+            added_at: Some(now - 10 * DAY as u64),
+            ..TrackMetadata::default()
+        };
+        let rated = TrackStats {
+            rating: 4,
+            ..TrackStats::default()
+        };
+        let played = TrackStats {
+            plays: 2,
+            favorite: true,
+            ..TrackStats::default()
+        };
+        let matches = |name: &str, title: &str, stats: Option<&TrackStats>| {
+            playlists
+                .find(name)
+                .unwrap()
+                .matches(title, Some(&metadata), stats, now)
+        };
+        assert!(matches("keepers", "Song", Some(&rated)));
+        assert!(!matches("keepers", "Song", None));
+        assert!(matches("Fresh", "Song", Some(&rated)));
+        assert!(!matches("Fresh", "Song", Some(&played)));
+        assert!(matches("Remixes", "Song (Club REMIX)", Some(&played)));
+        assert!(matches("Remixes", "Song", None));
+        assert!(!matches("Remixes", "Song", Some(&played)));
+    }
+
+    #[test]
+    fn invalid_rules_explain_the_problem() {
+        let mut playlists = SmartPlaylists::default();
+        for rule in [
+            "",
+            "rating >",
+            "colour = red",
+            "duration < soon",
+            "title ~ /(/",
+            "(plays = 0",
+            "title < 4",
+        ] {
+            assert!(playlists.define("Broken", rule).is_err(), "{rule}");
+        }
+        assert!(playlists.is_empty());
+        assert!(!playlists.define("Old", "played > 1y").unwrap());
+        assert!(playlists.define("old", "played >= 1y").unwrap());
+        assert_eq!(playlists.len(), 1);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::app::load_library;
+use crate::app::{load_library, load_library_roots};
 use crate::security::{bounded_output, external_command};
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::path::{Path, PathBuf};
@@ -73,6 +73,36 @@ pub fn display_storage() -> Result<(), String> {
     if missing_music > 0 {
         println!("Missing music files:   {missing_music}");
     }
+    let roots = load_library_roots();
+    let root_paths = roots.paths();
+    if root_paths.len() > 1 {
+        println!();
+        println!("Library roots:");
+        for root in &root_paths {
+            let mut label = root.display().to_string();
+            if roots.is_download_root(root) {
+                label.push_str(" (downloads)");
+            }
+            let in_root = |paths: &HashSet<PathBuf>| -> HashSet<PathBuf> {
+                paths
+                    .iter()
+                    .filter(|path| roots.root_of(&path.to_string_lossy()).as_ref() == Some(root))
+                    .cloned()
+                    .collect()
+            };
+            let songs = in_root(&music_paths);
+            if roots.is_offline(root) {
+                println!("  {label}: offline, {} indexed song(s)", songs.len());
+                continue;
+            }
+            let (music, files) = total_existing_size(&songs);
+            let (video, _) = total_existing_size(&in_root(&video_paths));
+            println!(
+                "  {label}: {} across {files} song(s)",
+                format_bytes(music.saturating_add(video))
+            );
+        }
+    }
     Ok(())
 }
 
@@ -193,7 +223,7 @@ fn total_existing_size(paths: &HashSet<PathBuf>) -> (u64, usize) {
     })
 }
 
-fn format_bytes(bytes: u64) -> String {
+pub fn format_bytes(bytes: u64) -> String {
     const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
     if bytes < 1024 {
         return format!("{bytes} B");
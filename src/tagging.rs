@@ -0,0 +1,223 @@
+use crate::download_format::AudioEncoding;
+use crate::search::playable_audio_file;
+use crate::security::{external_command, read_response_limited, valid_youtube_id};
+use crate::track_metadata::TrackMetadata;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+const MAX_COVER_BYTES: usize = 8 * 1024 * 1024;
+
+/// Rewrite `path`, written as `encoding`, with tags (ID3v2.4 for MP3) and,
+/// when the source video is known and the container allows it, its thumbnail
+/// as front cover art. Audio is copied, not re-encoded.
+pub fn write_tags(
+    path: &Path,
+    encoding: AudioEncoding,
+    title: &str,
+    metadata: &TrackMetadata,
+) -> Result<(), String> {
+    let tagged_path = sibling(path, ".tagging");
+    let cover_path = sibling(path, ".cover");
+    let cover = metadata
+        .video_id
+        .as_deref()
+        .filter(|_| encoding.supports_cover_art())
+        .and_then(fetch_cover_art)
+        .filter(|bytes| std::fs::write(&cover_path, bytes).is_ok());
+    let mut command = external_command("ffmpeg");
+    command.args(["-y", "-nostdin", "-loglevel", "error", "-i"]);
+    command.arg(path);
+    if cover.is_some() {
+        command.arg("-i").arg(&cover_path);
+        command.args([
+            "-map",
+            "0:a",
+            "-map",
+            "1:v",
+            "-c:v",
+            "mjpeg",
+            "-disposition:v",
+            "attached_pic",
+            "-metadata:s:v",
+            "title=Album cover",
+            "-metadata:s:v",
+            "comment=Cover (front)",
+        ]);
+    } else {
+        command.args(["-map", "0:a"]);
+    }
+    command
+        .args(["-c:a", "copy", "-map_metadata", "-1"])
+        .args(tag_arguments(title, metadata, encoding.is_mp3()))
+        .args(["-f", encoding.muxer])
+        .arg(&tagged_path)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+    let result = command
+        .status()
+        .map_err(|error| format!("could not start ffmpeg: {error}"))
+        .and_then(|status| {
+            if status.success() && playable_audio_file(&tagged_path) {
+                std::fs::rename(&tagged_path, path)
+                    .map_err(|error| format!("could not replace the audio file: {error}"))
+            } else {
+                Err(format!("tagging failed with {status}"))
+            }
+        });
+    let _ = std::fs::remove_file(&cover_path);
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tagged_path);
+    }
+    result
+}
+
+fn tag_arguments(title: &str, metadata: &TrackMetadata, id3: bool) -> Vec<String> {
+    let mut tags = vec![("title", title.to_string())];
+    if let Some(artist) = &metadata.artist {
+        tags.push(("artist", artist.clone()));
+    }
+    if let Some(album) = &metadata.album {
+        tags.push(("album", album.clone()));
+    }
+    if let Some(year) = metadata.year {
+        tags.push(("date", year.to_string()));
+    }
+    if let Some(video_id) = &metadata.video_id {
+        tags.push((
+            "comment",
+            format!("https://www.youtube.com/watch?v={video_id}"),
+        ));
+    }
+    let mut arguments = Vec::new();
+    if id3 {
+        arguments.extend(["-id3v2_version", "4", "-write_id3v1", "0"].map(String::from));
+    }
+    for (key, value) in tags {
+        arguments.push("-metadata".to_string());
+        arguments.push(format!("{key}={value}"));
+    }
+    arguments
+}
+
+/// YouTube serves `maxresdefault` only for HD uploads; `hqdefault` always exists.
+pub fn fetch_cover_art(video_id: &str) -> Option<Vec<u8>> {
+    if !valid_youtube_id(video_id) {
+        return None;
+    }
+    let client = reqwest::blocking::Client::new();
+    ["maxresdefault", "hqdefault"].iter().find_map(|size| {
+        let response = client
+            .get(format!("https://i.ytimg.com/vi/{video_id}/{size}.jpg"))
+            .header(reqwest::header::USER_AGENT, "crest-player/0.1.0")
+            .timeout(Duration::from_secs(10))
+            .send()
+            .ok()?;
+        if !response.status().is_success() {
+            return None;
+        }
+        read_response_limited(response, MAX_COVER_BYTES)
+            .ok()
+            .filter(|bytes| !bytes.is_empty())
+    })
+}
+
+/// Library downloads are named `<title> [<video id>]_ytmusic.<extension>`, which
+/// recovers the source for files indexed before it was stored.
+pub fn video_id_from_filename(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    let (rest, _) = name.rsplit_once("]_ytmusic.")?;
+    let (_, video_id) = rest.rsplit_once(" [")?;
+    valid_youtube_id(video_id).then(|| video_id.to_string())
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// A background pass that retags library files one at a time.
+pub struct RetagJob {
+    pub receiver: Receiver<(String, Result<(), String>)>,
+    pub total: usize,
+    pub tagged: usize,
+    pub failed: usize,
+}
+
+impl RetagJob {
+    /// `tracks` holds the title, path and stored metadata of each file.
+    pub fn start(tracks: Vec<(String, String, TrackMetadata)>) -> Self {
+        let total = tracks.len();
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            for (title, path, mut metadata) in tracks {
+                if metadata.video_id.is_none() {
+                    metadata.video_id = video_id_from_filename(Path::new(&path));
+                }
+                let result = AudioEncoding::of_file(Path::new(&path))
+                    .ok_or_else(|| "the file format cannot be tagged".to_string())
+                    .and_then(|encoding| write_tags(Path::new(&path), encoding, &title, &metadata));
+                if sender.send((path, result)).is_err() {
+                    return;
+                }
+            }
+        });
+        Self {
+            receiver,
+            total,
+            tagged: 0,
+            failed: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tag_arguments, video_id_from_filename};
+    use crate::track_metadata::TrackMetadata;
+    use std::path::Path;
+
+    #[test]
+    fn tags_use_id3v2_4_and_only_known_fields() {
+        let metadata = TrackMetadata {
+            artist: Some("Artist".to_string()),
+            year: Some(1987),
+            video_id: Some("dQw4w9WgXcQ".to_string()),
+            ..TrackMetadata::default()
+        };
+        let arguments = tag_arguments("Song", &metadata, true);
+        assert_eq!(arguments[..2], ["-id3v2_version", "4"]);
+        assert_eq!(
+            tag_arguments("Song", &metadata, false)[..2],
+            ["-metadata", "title=Song"]
+        );
+        assert!(arguments.contains(&"title=Song".to_string()));
+        assert!(arguments.contains(&"artist=Artist".to_string()));
+        assert!(arguments.contains(&"date=1987".to_string()));
+        assert!(
+            arguments.contains(&"comment=https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string())
+        );
+        assert!(
+            !arguments
+                .iter()
+                .any(|argument| argument.starts_with("album="))
+        );
+    }
+
+    #[test]
+    fn recovers_the_video_id_from_library_filenames() {
+        assert_eq!(
+            video_id_from_filename(Path::new("/music/Song [x] [dQw4w9WgXcQ]_ytmusic.mp3"))
+                .as_deref(),
+            Some("dQw4w9WgXcQ")
+        );
+        assert_eq!(
+            video_id_from_filename(Path::new("/music/Song [dQw4w9WgXcQ]_ytmusic.opus")).as_deref(),
+            Some("dQw4w9WgXcQ")
+        );
+        assert_eq!(video_id_from_filename(Path::new("/music/Song.mp3")), None);
+    }
+}
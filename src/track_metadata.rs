@@ -0,0 +1,142 @@
+use crate::security::{sanitize_display_text_limited, valid_youtube_id};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Descriptive fields stored alongside a library entry in the index.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrackMetadata {
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub year: Option<u16>,
+    pub duration: Option<Duration>,
+    /// The YouTube video the file was downloaded from.
+    pub video_id: Option<String>,
+    /// Seconds since the Unix epoch.
+    pub added_at: Option<u64>,
+    pub file_size: Option<u64>,
+    /// Modification time in seconds since the Unix epoch, used to skip
+    /// unchanged files when rescanning.
+    pub modified_at: Option<u64>,
+    /// Found by the folder scanner rather than downloaded. Crest Player never
+    /// deletes these files; removing one only drops it from the index.
+    pub imported: bool,
+}
+
+impl TrackMetadata {
+    /// Read the fields of the video JSON yt-dlp writes with
+    /// `--write-info-json`. Music uploads carry `artist`; other videos fall
+    /// back to the channel name.
+    pub fn from_yt_dlp_json(json: &serde_json::Value) -> Self {
+        let text = |keys: &[&str]| {
+            keys.iter()
+                .filter_map(|key| json.get(*key)?.as_str())
+                .map(|value| sanitize_display_text_limited(value.trim(), 256))
+                .find(|value| !value.is_empty())
+        };
+        Self {
+            artist: text(&["artist", "creator", "uploader", "channel"]),
+            album: text(&["album"]),
+            // Music uploads carry a release year; otherwise use the upload date.
+            year: json
+                .get("release_year")
+                .and_then(serde_json::Value::as_u64)
+                .and_then(|year| u16::try_from(year).ok())
+                .or_else(|| json.get("upload_date")?.as_str()?.get(..4)?.parse().ok()),
+            duration: json
+                .get("duration")
+                .and_then(serde_json::Value::as_f64)
+                .filter(|seconds| seconds.is_finite() && *seconds > 0.0)
+                .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok()),
+            video_id: json
+                .get("id")
+                .and_then(serde_json::Value::as_str)
+                .filter(|id| valid_youtube_id(id))
+                .map(str::to_string),
+            ..Self::default()
+        }
+    }
+
+    /// Fill in what the file itself can tell us. Migrated entries use the
+    /// modification time as the date they were added.
+    pub fn fill_from_file(&mut self, path: &Path) {
+        let Ok(metadata) = path.metadata() else {
+            return;
+        };
+        self.file_size = Some(metadata.len());
+        self.modified_at = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|elapsed| elapsed.as_secs());
+        if self.added_at.is_none() {
+            self.added_at = self.modified_at;
+        }
+    }
+
+    pub fn mark_added_now(&mut self) {
+        self.added_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .ok();
+    }
+
+    /// Duration and artist for list rows, e.g. `3:33 · Rick Astley`.
+    pub fn detail_label(&self) -> String {
+        [self.duration_label(), self.artist.clone()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" · ")
+    }
+
+    pub fn duration_label(&self) -> Option<String> {
+        let seconds = self.duration?.as_secs();
+        Some(if seconds >= 3_600 {
+            format!(
+                "{}:{:02}:{:02}",
+                seconds / 3_600,
+                seconds / 60 % 60,
+                seconds % 60
+            )
+        } else {
+            format!("{}:{:02}", seconds / 60, seconds % 60)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TrackMetadata;
+    use std::time::Duration;
+
+    #[test]
+    fn reads_yt_dlp_fields_with_channel_fallback() {
+        let music = TrackMetadata::from_yt_dlp_json(&serde_json::json!({
+            "id": "dQw4w9WgXcQ",
+            "artist": "Rick Astley",
+            "uploader": "RickAstleyVEVO",
+            "album": "Whenever You Need Somebody",
+            "duration": 213.0,
+            "release_year": 1987,
+            "upload_date": "20091025",
+        }));
+        assert_eq!(music.artist.as_deref(), Some("Rick Astley"));
+        assert_eq!(music.album.as_deref(), Some("Whenever You Need Somebody"));
+        assert_eq!(music.year, Some(1987));
+        assert_eq!(music.duration, Some(Duration::from_secs(213)));
+        assert_eq!(music.video_id.as_deref(), Some("dQw4w9WgXcQ"));
+        assert_eq!(music.duration_label().as_deref(), Some("3:33"));
+
+        let video = TrackMetadata::from_yt_dlp_json(&serde_json::json!({
+            "id": "not a video id",
+            "artist": "  ",
+            "channel": "Some Channel",
+            "duration": -1,
+            "upload_date": "20240102",
+        }));
+        assert_eq!(video.artist.as_deref(), Some("Some Channel"));
+        assert_eq!(video.year, Some(2024));
+        assert_eq!(video.duration, None);
+        assert_eq!(video.video_id, None);
+    }
+}
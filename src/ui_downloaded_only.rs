@@ -1,9 +1,15 @@
+use crate::album_art::render_cover_panel;
 use crate::download_queue_ui::render_download_queue;
+use crate::duplicates_ui::render_duplicates;
+use crate::history_ui::render_history;
+use crate::library_filter::{LibraryQuery, filter_text};
+use crate::playlists_ui::render_playlists;
 use crate::{App, Player};
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout},
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
+    text::Span,
     widgets::{Block, Borders, List, ListItem, Paragraph},
 };
 
@@ -34,61 +40,98 @@ pub fn ui_downloaded_only(f: &mut Frame, app: &App, player: &Player) {
         ])
         .split(main_chunks[0]);
 
-    let input = Paragraph::new(app.input.as_str()).block(
-        Block::default()
-            .borders(Borders::ALL)
-            .title("Commands (:shuffle queue · :shuffle all · :clear · :cast …)"),
-    );
+    let input =
+        Paragraph::new(app.input.as_str()).block(Block::default().borders(Borders::ALL).title(
+            "Type to filter · Commands (:shuffle queue · :shuffle all · :clear · :sort · :smart … · :retag · :check · :duplicates · :scan · :roots · :playlist … · :cast …)",
+        ));
     f.render_widget(input, vchunks[0]);
 
-    // Results panel is the downloaded songs
+    // Results panel is the downloaded songs. Only the visible rows are built,
+    // so libraries with tens of thousands of songs stay responsive.
     use ratatui::widgets::ListState;
+    let query = LibraryQuery::new(filter_text(&app.input));
+    let visible_rows = usize::from(vchunks[1].height.saturating_sub(2)).max(1);
+    let offset = (app.selected + 1).saturating_sub(visible_rows);
     let items: Vec<ListItem> = app
         .results
         .iter()
         .enumerate()
+        .skip(offset)
+        .take(visible_rows)
         .map(|(i, (title, path))| {
             use ratatui::text::{Line, Span};
+            let offline = app.is_library_track_offline(path);
             let style = if i == app.selected {
                 Style::default().bg(Color::Green).fg(Color::Black)
+            } else if offline {
+                Style::default().fg(Color::DarkGray)
             } else {
                 Style::default()
             };
             let (status, color) = if app.is_library_file_available(path) {
                 ("●", Color::Green)
+            } else if offline {
+                ("○", Color::DarkGray)
             } else {
                 ("❌", Color::Red)
             };
-            let max_len = 28;
-            let short_title = if title.chars().count() > max_len {
-                let mut s = title.chars().take(max_len - 1).collect::<String>();
-                s.push('…');
-                s
-            } else {
-                title.clone()
-            };
-            ListItem::new(Line::from(vec![
-                Span::raw(short_title + " "),
-                Span::styled(status, Style::default().fg(color)),
-            ]))
-            .style(style)
+            let details = app
+                .track_metadata(path)
+                .map(|metadata| metadata.detail_label())
+                .filter(|details| !details.is_empty())
+                .map(|details| format!("  {details}"))
+                .unwrap_or_default();
+            let mut spans = highlighted_title(title, &query, 28);
+            spans.push(Span::raw(" "));
+            spans.push(Span::styled(status, Style::default().fg(color)));
+            if let Some(badge) = app
+                .track_stats(path)
+                .map(|stats| stats.badge())
+                .filter(|badge| !badge.is_empty())
+            {
+                spans.push(Span::styled(
+                    format!(" {badge}"),
+                    Style::default().fg(Color::Yellow),
+                ));
+            }
+            spans.push(Span::styled(details, Style::default().fg(Color::DarkGray)));
+            ListItem::new(Line::from(spans)).style(style)
         })
         .collect();
     let mut state = ListState::default();
-    state.select(Some(app.selected));
+    state.select(Some(app.selected - offset));
+    let count = if query.is_empty() {
+        format!("{}", app.results.len())
+    } else {
+        format!("{}/{}", app.results.len(), app.library.len())
+    };
+    let source = app
+        .smart_view
+        .as_deref()
+        .map(|name| format!(" · smart: {name}"))
+        .unwrap_or_default();
     let list = List::new(items)
-        .block(Block::default().borders(Borders::ALL).title(
-            "Downloaded Songs (arrows, Enter play/queue, Delete remove, Ctrl+n next, Ctrl+← home, Ctrl+q quit)"
-        ))
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            "Downloaded Songs{source} · {count} · by {} (arrows, Enter play/queue, Ctrl+t sort, Ctrl+f favorite, Delete remove, Ctrl+n next, Ctrl+← home, Ctrl+q quit)",
+            app.library_sort.label()
+        )))
         .highlight_symbol("▶ ");
-    f.render_stateful_widget(list, vchunks[1], &mut state);
+    if app.duplicates.is_some() {
+        render_duplicates(f, vchunks[1], app);
+    } else {
+        f.render_stateful_widget(list, vchunks[1], &mut state);
+    }
 
     let help = if let Some(message) = &app.error {
         message.as_str()
+    } else if app.results.is_empty() && !query.is_empty() {
+        "No songs match the filter. Esc clears it."
+    } else if app.results.is_empty() && app.smart_view.is_some() {
+        "No songs match this smart playlist. :smart show returns to the library."
     } else if app.results.is_empty() {
         "No downloaded songs found."
     } else {
-        "Arrows navigate, Enter plays or queues, Delete removes, Ctrl+n skips, Ctrl+← returns home, Ctrl+q quits."
+        "Type to filter, arrows navigate, Enter plays or queues, Ctrl+t sorts, Ctrl+f favorites, Delete removes, Ctrl+o playlists, Ctrl+n skips, Ctrl+← returns home, Ctrl+q quits."
     };
     let help = Paragraph::new(help).block(Block::default().borders(Borders::ALL));
     f.render_widget(help, vchunks[2]);
@@ -96,13 +139,24 @@ pub fn ui_downloaded_only(f: &mut Frame, app: &App, player: &Player) {
     // Player bar
     let player_text = if let Some(title) = &player.title {
         format!(
-            "▶ {} [{}] (Alt+± seek 5s, Ctrl+p pause, Ctrl+n next, Ctrl+← home)",
-            title, player.status
+            "▶ {} [{}] · {}{} (Alt+± seek 5s, Alt+↑/↓ volume, Alt+m mute, Alt+r repeat, Alt+s shuffle, Ctrl+p pause, Ctrl+b back, Ctrl+n next, Ctrl+r history, Ctrl+← home)",
+            title,
+            player.status,
+            player.volume_label(),
+            player
+                .mode_label()
+                .map(|modes| format!(" · {modes}"))
+                .unwrap_or_default()
         )
     } else {
         format!(
-            "▶ [No song playing] [{}] (Alt+± seek 5s, Ctrl+p pause, Ctrl+n next, Ctrl+← home)",
-            player.status
+            "▶ [No song playing] [{}] · {}{} (Alt+± seek 5s, Alt+↑/↓ volume, Alt+m mute, Alt+r repeat, Alt+s shuffle, Ctrl+p pause, Ctrl+b back, Ctrl+n next, Ctrl+r history, Ctrl+← home)",
+            player.status,
+            player.volume_label(),
+            player
+                .mode_label()
+                .map(|modes| format!(" · {modes}"))
+                .unwrap_or_default()
         )
     };
     let player_bar =
@@ -131,18 +185,46 @@ pub fn ui_downloaded_only(f: &mut Frame, app: &App, player: &Player) {
             ListItem::new(format!("{} {}", short_title, status))
         })
         .collect();
+    let right_title = if app.queue_focused {
+        "Queue (Shift+↑/↓ move, Enter play, n next, Del remove, Tab done)"
+    } else if right_title == "Queue" {
+        "Queue (Tab edit)"
+    } else {
+        right_title
+    };
     let right_list = List::new(right_items)
-        .block(Block::default().borders(Borders::ALL).title(right_title))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(if app.queue_focused {
+                    Style::default().fg(Color::Yellow)
+                } else {
+                    Style::default()
+                })
+                .title(right_title),
+        )
         .highlight_style(Style::default().bg(Color::Green).fg(Color::Black));
-    if !app.has_active_downloads() {
-        f.render_widget(right_list, main_chunks[1]);
+    let mut queue_state = ListState::default();
+    if app.queue_focused && !player.queue.is_empty() {
+        queue_state.select(Some(app.queue_selected.min(player.queue.len() - 1)));
+    }
+    let column = render_cover_panel(f, main_chunks[1], app);
+    let list_area = if !app.has_active_downloads() {
+        column
     } else {
         let right_chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-            .split(main_chunks[1]);
-        f.render_widget(right_list, right_chunks[0]);
+            .split(column);
         render_download_queue(f, right_chunks[1], app);
+        right_chunks[0]
+    };
+    if app.show_history {
+        render_history(f, list_area, app, false);
+    } else if app.show_playlists {
+        render_playlists(f, list_area, app);
+    } else {
+        f.render_stateful_widget(right_list, list_area, &mut queue_state);
     }
 
     if app.lyrics_enabled {
@@ -205,3 +287,44 @@ pub fn ui_downloaded_only(f: &mut Frame, app: &App, player: &Player) {
         f.render_widget(lyrics, main_chunks[2]);
     }
 }
+
+/// The title shortened to `max_len` characters with filter matches in yellow.
+fn highlighted_title(title: &str, query: &LibraryQuery, max_len: usize) -> Vec<Span<'static>> {
+    let truncated = title.chars().count() > max_len;
+    let shown: String = if truncated {
+        title.chars().take(max_len - 1).collect()
+    } else {
+        title.to_string()
+    };
+    let marks = query.highlights(title);
+    let mut spans = Vec::new();
+    let mut run = String::new();
+    let mut run_marked = false;
+    for (character, marked) in shown.chars().zip(marks) {
+        if marked != run_marked && !run.is_empty() {
+            spans.push(title_span(std::mem::take(&mut run), run_marked));
+        }
+        run_marked = marked;
+        run.push(character);
+    }
+    if !run.is_empty() {
+        spans.push(title_span(run, run_marked));
+    }
+    if truncated {
+        spans.push(Span::raw("…"));
+    }
+    spans
+}
+
+fn title_span(text: String, marked: bool) -> Span<'static> {
+    if marked {
+        Span::styled(
+            text,
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )
+    } else {
+        Span::raw(text)
+    }
+}
@@ -1,4 +1,6 @@
+use crate::album_art::render_cover_panel;
 use crate::download_queue_ui::render_download_queue;
+use crate::history_ui::render_history;
 use crate::{App, Player};
 use ratatui::{
     Frame,
@@ -105,13 +107,24 @@ pub fn ui_with_player(f: &mut Frame, app: &App, player: &Player) {
     // Player bar
     let player_text = if let Some(title) = &player.title {
         format!(
-            "▶ {} [{}] (Alt+± seek 5s, Ctrl+p pause, Ctrl+n next, Ctrl+← home)",
-            title, player.status
+            "▶ {} [{}] · {}{} (Alt+± seek 5s, Alt+↑/↓ volume, Alt+m mute, Alt+r repeat, Alt+s shuffle, Ctrl+p pause, Ctrl+b back, Ctrl+n next, Ctrl+r history, Ctrl+← home)",
+            title,
+            player.status,
+            player.volume_label(),
+            player
+                .mode_label()
+                .map(|modes| format!(" · {modes}"))
+                .unwrap_or_default()
         )
     } else {
         format!(
-            "▶ [No song playing] [{}] (Alt+± seek 5s, Ctrl+p pause, Ctrl+n next, Ctrl+← home)",
-            player.status
+            "▶ [No song playing] [{}] · {}{} (Alt+± seek 5s, Alt+↑/↓ volume, Alt+m mute, Alt+r repeat, Alt+s shuffle, Ctrl+p pause, Ctrl+b back, Ctrl+n next, Ctrl+r history, Ctrl+← home)",
+            player.status,
+            player.volume_label(),
+            player
+                .mode_label()
+                .map(|modes| format!(" · {modes}"))
+                .unwrap_or_default()
         )
     };
     let player_bar =
@@ -137,15 +150,37 @@ pub fn ui_with_player(f: &mut Frame, app: &App, player: &Player) {
                 } else {
                     title.clone()
                 };
+                let offline = app.is_library_track_offline(path);
                 let (status, color) = if app.is_library_file_available(path) {
                     ("●", Color::Green)
+                } else if offline {
+                    ("○", Color::DarkGray)
                 } else {
                     ("❌", Color::Red)
                 };
-                ListItem::new(Line::from(vec![
+                let details = app
+                    .track_metadata(path)
+                    .map(|metadata| metadata.detail_label())
+                    .filter(|details| !details.is_empty())
+                    .map(|details| format!("  {details}"))
+                    .unwrap_or_default();
+                let badge = app
+                    .track_stats(path)
+                    .map(|stats| stats.badge())
+                    .filter(|badge| !badge.is_empty())
+                    .map(|badge| format!(" {badge}"))
+                    .unwrap_or_default();
+                let item = ListItem::new(Line::from(vec![
                     Span::raw(short_title + " "),
                     Span::styled(status, Style::default().fg(color)),
-                ]))
+                    Span::styled(badge, Style::default().fg(Color::Yellow)),
+                    Span::styled(details, Style::default().fg(Color::DarkGray)),
+                ]));
+                if offline {
+                    item.style(Style::default().fg(Color::DarkGray))
+                } else {
+                    item
+                }
             })
             .collect()
     } else {
@@ -184,18 +219,44 @@ pub fn ui_with_player(f: &mut Frame, app: &App, player: &Player) {
             })
             .collect()
     };
+    let right_title = if app.queue_focused {
+        "Queue (Shift+↑/↓ move, Enter play, n next, Del remove, Tab done)"
+    } else if right_title == "Queue" {
+        "Queue (Tab edit)"
+    } else {
+        right_title
+    };
     let right_list = List::new(right_items)
-        .block(Block::default().borders(Borders::ALL).title(right_title))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(if app.queue_focused {
+                    Style::default().fg(Color::Yellow)
+                } else {
+                    Style::default()
+                })
+                .title(right_title),
+        )
         .highlight_style(Style::default().bg(Color::Green).fg(Color::Black));
-    if !app.has_active_downloads() {
-        f.render_widget(right_list, main_chunks[1]);
+    let mut queue_state = ListState::default();
+    if app.queue_focused && !player.queue.is_empty() {
+        queue_state.select(Some(app.queue_selected.min(player.queue.len() - 1)));
+    }
+    let column = render_cover_panel(f, main_chunks[1], app);
+    let list_area = if !app.has_active_downloads() {
+        column
     } else {
         let right_chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-            .split(main_chunks[1]);
-        f.render_widget(right_list, right_chunks[0]);
+            .split(column);
         render_download_queue(f, right_chunks[1], app);
+        right_chunks[0]
+    };
+    if app.show_history {
+        render_history(f, list_area, app, true);
+    } else {
+        f.render_stateful_widget(right_list, list_area, &mut queue_state);
     }
 
     if app.lyrics_enabled {
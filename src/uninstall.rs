@@ -1,6 +1,7 @@
 use crate::app::{App, save_library};
 #[cfg(target_os = "linux")]
 use crate::security::bounded_output;
+use crate::security::containing_root;
 #[cfg(any(target_os = "linux", target_os = "macos", windows))]
 use crate::security::external_command;
 use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
@@ -225,7 +226,11 @@ fn installation_size(installation: &Installation) -> u64 {
 
 fn media_size() -> u64 {
     let mut files = HashSet::new();
-    for (_, path) in App::new().library {
+    let app = App::new();
+    for (_, path) in &app.library {
+        if app.is_imported_track(path) {
+            continue;
+        }
         let music = PathBuf::from(path);
         files.insert(music.with_extension("crestvid"));
         files.insert(music.with_extension("video.cache"));
@@ -236,6 +241,7 @@ fn media_size() -> u64 {
     }
     if let Some(audio_directory) = dirs::audio_dir() {
         files.insert(audio_directory.join("ytmusic_library.csv"));
+        files.insert(audio_directory.join("ytmusic_history.json"));
     }
     files
         .iter()
@@ -273,6 +279,7 @@ fn remove_media_data() -> Result<(), String> {
     let indexed_paths: Vec<PathBuf> = app
         .library
         .iter()
+        .filter(|(_, path)| !app.is_imported_track(path))
         .map(|(_, path)| PathBuf::from(path))
         .collect();
     let errors = app.delete_all_library_media();
@@ -282,18 +289,13 @@ fn remove_media_data() -> Result<(), String> {
             errors.join("\n")
         ));
     }
-    save_library(&app.library);
+    save_library(&app);
 
     let audio_directory =
         dirs::audio_dir().ok_or_else(|| "could not locate the Music directory".to_string())?;
-    let canonical_audio_directory = audio_directory
-        .canonicalize()
-        .map_err(|error| format!("could not inspect the Music directory: {error}"))?;
+    let roots = app.library_roots.paths();
     for path in indexed_paths {
-        let Some(parent) = path.parent() else {
-            continue;
-        };
-        if parent.canonicalize().ok().as_deref() != Some(canonical_audio_directory.as_path()) {
+        if containing_root(&path, &roots).is_none() {
             continue;
         }
         remove_file_if_present(&path.with_extension("video.cache"))?;
@@ -302,6 +304,7 @@ fn remove_media_data() -> Result<(), String> {
         remove_file_if_present(Path::new(&partial_name))?;
     }
     remove_file_if_present(&audio_directory.join("ytmusic_library.csv"))?;
+    remove_file_if_present(&audio_directory.join("ytmusic_history.json"))?;
 
     Ok(())
 }
@@ -1,5 +1,5 @@
 use crate::lyrics::Lyrics;
-use crate::security::{cancellable_status, external_command};
+use crate::security::{cancellable_status_with_lines, external_command};
 use std::{
     fs::File,
     io::{self, Read, Seek, SeekFrom},
@@ -10,6 +10,8 @@ use std::{
 
 const MAGIC: &[u8; 8] = b"CRESTV1\0";
 const DELTA_MAGIC: &[u8; 8] = b"CRESTV2\0";
+/// Current caches are Matroska files, which open with an EBML header.
+const MATROSKA_MAGIC: &[u8; 4] = b"\x1a\x45\xdf\xa3";
 const MAX_CACHE_DIMENSION: u16 = 4096;
 const MAX_FRAME_BYTES: usize = 64 * 1024 * 1024;
 const MAX_COMPRESSED_FRAME_BYTES: usize = 64 * 1024 * 1024;
@@ -41,18 +43,24 @@ pub fn build_video_cache(
 ) -> io::Result<()> {
     let cancelled = AtomicBool::new(false);
     build_video_cache_cancellable(
-        video_path, cache_path, width, height, fps, lyrics, &cancelled,
+        video_path,
+        cache_path,
+        (width, height, fps),
+        lyrics,
+        &cancelled,
+        &mut |_| {},
     )
 }
 
+/// Encode `video_path` into a `.crestvid` cache at `cache_path`. ffmpeg's
+/// `-progress` lines are passed to `on_progress` while it runs.
 pub fn build_video_cache_cancellable(
     video_path: &str,
     cache_path: &str,
-    width: u16,
-    height: u16,
-    fps: u16,
+    (width, height, fps): (u16, u16, u16),
     lyrics: Option<&Lyrics>,
     cancelled: &AtomicBool,
+    on_progress: &mut dyn FnMut(&str),
 ) -> io::Result<()> {
     if validate_video_parameters(width, height, fps).is_err() {
         return Err(io::Error::new(
@@ -75,6 +83,7 @@ pub fn build_video_cache_cancellable(
         lyrics_path: lyrics.map(|_| lyrics_path.as_str()),
         lyrics_synced: lyrics.map(|lyrics| lyrics.synced),
         cancelled,
+        on_progress,
     });
     let _ = std::fs::remove_file(&lyrics_path);
     if result.is_err() {
@@ -94,6 +103,7 @@ struct CacheBuildOptions<'a> {
     lyrics_path: Option<&'a str>,
     lyrics_synced: Option<bool>,
     cancelled: &'a AtomicBool,
+    on_progress: &'a mut dyn FnMut(&str),
 }
 
 fn build_video_cache_inner(options: CacheBuildOptions<'_>) -> io::Result<()> {
@@ -107,6 +117,7 @@ fn build_video_cache_inner(options: CacheBuildOptions<'_>) -> io::Result<()> {
         lyrics_path,
         lyrics_synced,
         cancelled,
+        on_progress,
     } = options;
     let filter = format!(
         "fps={fps}:round=near,scale={width}:{height}:force_original_aspect_ratio=decrease:flags=lanczos,pad={width}:{height}:(ow-iw)/2:(oh-ih)/2:black"
@@ -181,11 +192,11 @@ fn build_video_cache_inner(options: CacheBuildOptions<'_>) -> io::Result<()> {
         ]);
     }
     command
+        .args(["-progress", "pipe:1", "-nostats"])
         .args(["-pix_fmt", "yuv420p", "-f", "matroska", temporary_path])
         .stdin(Stdio::null())
-        .stdout(Stdio::null())
         .stderr(Stdio::null());
-    let status = cancellable_status(command, cancelled)?;
+    let status = cancellable_status_with_lines(command, cancelled, on_progress)?;
     if !status.success()
         || std::fs::metadata(temporary_path)
             .map(|metadata| metadata.len() == 0)
@@ -196,6 +207,19 @@ fn build_video_cache_inner(options: CacheBuildOptions<'_>) -> io::Result<()> {
     std::fs::rename(temporary_path, cache_path)
 }
 
+/// Whether `path` holds a cache Crest Player can play: a Matroska cache, or
+/// an older frame-indexed one whose index is intact.
+pub fn is_readable_cache(path: impl AsRef<Path>) -> bool {
+    let mut magic = [0; 4];
+    let Ok(mut file) = File::open(path.as_ref()) else {
+        return false;
+    };
+    if file.read_exact(&mut magic).is_err() {
+        return false;
+    }
+    &magic == MATROSKA_MAGIC || VideoCache::open(path).is_ok()
+}
+
 fn lyrics_as_webvtt(lyrics: &Lyrics) -> String {
     let mut output = String::from("WEBVTT\n\n");
     if !lyrics.synced {
@@ -405,7 +429,10 @@ fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
 
 #[cfg(test)]
 mod tests {
-    use super::{DELTA_MAGIC, MAGIC, MAX_FRAME_COUNT, VideoCache, build_video_cache};
+    use super::{
+        DELTA_MAGIC, MAGIC, MATROSKA_MAGIC, MAX_FRAME_COUNT, VideoCache, build_video_cache,
+        is_readable_cache,
+    };
     use crate::lyrics::{LyricLine, Lyrics};
     use std::io::Write;
     use std::process::{Command, Stdio};
@@ -430,6 +457,7 @@ mod tests {
         let mut cache = VideoCache::open(&path).unwrap();
         assert_eq!(cache.frame_count(), 1);
         assert_eq!(cache.read_frame(0).unwrap(), vec![10, 20, 30]);
+        assert!(is_readable_cache(&path));
         let _ = std::fs::remove_file(path);
     }
 
@@ -447,9 +475,26 @@ mod tests {
         file.write_all(&0u64.to_le_bytes()).unwrap();
         drop(file);
         assert!(VideoCache::open(&path).is_err());
+        assert!(!is_readable_cache(&path));
         let _ = std::fs::remove_file(path);
     }
 
+    #[test]
+    fn recognizes_matroska_caches_and_rejects_other_files() {
+        let path = std::env::temp_dir().join(format!(
+            "crest-matroska-cache-test-{}.crestvid",
+            std::process::id()
+        ));
+        std::fs::write(&path, [MATROSKA_MAGIC.as_slice(), &[0x42, 0x86]].concat()).unwrap();
+        assert!(is_readable_cache(&path));
+        std::fs::write(&path, b"<html>").unwrap();
+        assert!(!is_readable_cache(&path));
+        std::fs::write(&path, b"").unwrap();
+        assert!(!is_readable_cache(&path));
+        let _ = std::fs::remove_file(&path);
+        assert!(!is_readable_cache(&path));
+    }
+
     #[test]
     fn rejects_excessive_frame_counts_and_truncated_payloads() {
         for (name, frame_count, payload_length) in [
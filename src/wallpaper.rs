@@ -8,7 +8,7 @@ const FILE_MAGIC: &[u8; 4] = b"CWP2";
 const LEGACY_FILE_MAGIC: &[u8; 4] = b"CWP1";
 const HEADER_LENGTH: usize = 10;
 const LEGACY_HEADER_LENGTH: usize = 9;
-const FILE_NAME: &str = "home-wallpaper.rgb";
+pub const FILE_NAME: &str = "home-wallpaper.rgb";
 const MAX_WALLPAPER_BYTES: usize = 64 * 1024 * 1024 + HEADER_LENGTH;
 
 pub struct HomeWallpaper {